@@ -0,0 +1,34 @@
+use std::process;
+use std::time::SystemTime;
+
+use devstats::lib;
+
+// Sets gha_actors.is_bot from current bots patterns (GHA2DB_BOTS_YAML)
+fn main() {
+    let dt_start = SystemTime::now();
+    let ctx = lib::Ctx::new();
+    let mut client = lib::pg_conn(&ctx);
+    let ok = match lib::update_actors_bots(&mut client, &ctx) {
+        Ok(n) => {
+            if ctx.debug >= 0 {
+                lib::printf(&format!("{}: {n} actors bot flag changed", ctx.pg_db));
+            }
+            true
+        }
+        Err(e) => {
+            lib::printf(&format!("error: {e}"));
+            false
+        }
+    };
+    let res_elapsed = dt_start.elapsed();
+    lib::fatal_no_log(&res_elapsed);
+    if ctx.debug >= 0 {
+        lib::printf(&format!(
+            "Time: {:?}",
+            res_elapsed.expect("cannot get elapsed time")
+        ));
+    }
+    if !ok {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::SystemTime;
+use std::{env, process};
+
+use devstats::lib;
+
+fn fatal(msg: &str) -> ! {
+    lib::printf(&format!("error: {msg}"));
+    process::exit(1)
+}
+
+fn main() {
+    let dt_start = SystemTime::now();
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 5 {
+        println!(
+            "{}: required args: 'metric sql' 'YYYY-MM-DD HH:MI:SS' 'YYYY-MM-DD HH:MI:SS' 'period' [series_name_or_func]",
+            args[0]
+        );
+        println!("Metric is read from GHA2DB_METRICS_YAML, period is h, d, w, m, q, y or y10 optionally followed by moving aggregate window, like 'd7'");
+        process::exit(1);
+    }
+    let ctx = lib::Ctx::new();
+    let from = lib::time_parse_any(&args[2]).unwrap_or_else(|e| fatal(&e));
+    let to = lib::time_parse_any(&args[3]).unwrap_or_else(|e| fatal(&e));
+    let yaml = lib::metrics_yaml(&ctx).unwrap_or_else(|e| fatal(&e));
+    let metrics: Vec<&lib::Metric> = yaml
+        .metrics
+        .iter()
+        .filter(|m| m.sql == args[1] && args.get(5).is_none_or(|s| *s == m.series_name_or_func))
+        .collect();
+    let metric = match metrics.as_slice() {
+        [m] => *m,
+        [] => fatal(&format!(
+            "metric sql {:?} not found in {}",
+            args[1], ctx.metrics_yaml
+        )),
+        _ => fatal(&format!(
+            "metric sql {:?} is defined {} times in {}, specify series_name_or_func",
+            args[1],
+            metrics.len(),
+            ctx.metrics_yaml
+        )),
+    };
+    let mut client = lib::pg_conn(&ctx);
+    let stats = lib::calc_metric(&mut client, &ctx, metric, from, to, &args[4])
+        .unwrap_or_else(|e| fatal(&e));
+    if ctx.debug >= 0 {
+        lib::printf(&format!(
+            "{} {}: {} intervals, {} rows, {} points, {} written",
+            metric.sql, args[4], stats.intervals, stats.rows, stats.points, stats.written
+        ));
+    }
+    let res_elapsed = dt_start.elapsed();
+    lib::fatal_no_log(&res_elapsed);
+    if ctx.debug >= 0 {
+        lib::printf(&format!(
+            "Time: {:?}",
+            res_elapsed.expect("cannot get elapsed time")
+        ));
+    }
+}
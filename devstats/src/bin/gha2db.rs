@@ -0,0 +1,58 @@
+use std::time::SystemTime;
+use std::{env, process};
+
+use devstats::lib;
+
+fn main() {
+    let dt_start = SystemTime::now();
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 5 {
+        println!(
+            "{}: required args: 'YYYY-MM-DD' 'HH' 'YYYY-MM-DD' 'HH' [org1,org2,...] [repo1,repo2,...]",
+            args[0]
+        );
+        process::exit(1);
+    }
+    let ctx = lib::Ctx::new();
+    let from = lib::parse_gha_hour(&args[1], &args[2]).unwrap_or_else(|e| {
+        lib::printf(&format!("error: {e}"));
+        process::exit(1)
+    });
+    let to = lib::parse_gha_hour(&args[3], &args[4]).unwrap_or_else(|e| {
+        lib::printf(&format!("error: {e}"));
+        process::exit(1)
+    });
+    let empty = String::new();
+    let filter = lib::GhaRepoFilter::new(
+        &ctx,
+        args.get(5).unwrap_or(&empty),
+        args.get(6).unwrap_or(&empty),
+    );
+    let stats = lib::gha2db(&ctx, from, to, &filter).unwrap_or_else(|e| {
+        lib::printf(&format!("error: {e}"));
+        process::exit(1)
+    });
+    if ctx.debug >= 0 {
+        lib::printf(&format!(
+            "Processed {} files ({} missing, {} skipped, {} already imported), {} events, {} written, {} already present, {} filtered out by repo, {} filtered out by actor, {} broken JSONs",
+            stats.files,
+            stats.files_missing,
+            stats.skipped,
+            stats.imported,
+            stats.events,
+            stats.written,
+            stats.existing,
+            stats.filtered_repo,
+            stats.filtered_actor,
+            stats.broken_json
+        ));
+    }
+    let res_elapsed = dt_start.elapsed();
+    lib::fatal_no_log(&res_elapsed);
+    if ctx.debug >= 0 {
+        lib::printf(&format!(
+            "Time: {:?}",
+            res_elapsed.expect("cannot get elapsed time")
+        ));
+    }
+}
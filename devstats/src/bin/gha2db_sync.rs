@@ -0,0 +1,54 @@
+use std::time::SystemTime;
+use std::{env, process};
+
+use devstats::lib;
+
+fn main() {
+    let dt_start = SystemTime::now();
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).is_some_and(|a| a == "-h" || a == "--help") {
+        println!(
+            "{}: optional args: [org1,org2,...] [repo1,repo2,...]",
+            args[0]
+        );
+        println!("Syncs GHA2DB_PROJECT: imports new GHA hours, calls ghapi2db, get_repos, calculates metrics, calls tags, columns, annotations and vars");
+        process::exit(1);
+    }
+    let mut ctx = lib::Ctx::new();
+    // Failed steps are reported, sync continues with the next ones
+    ctx.exec_fatal = false;
+    let empty = String::new();
+    let filter = lib::GhaRepoFilter::new(
+        &ctx,
+        args.get(1).unwrap_or(&empty),
+        args.get(2).unwrap_or(&empty),
+    );
+    let report = lib::gha2db_sync(&ctx, &filter).unwrap_or_else(|e| {
+        lib::printf(&format!("error: {e}"));
+        process::exit(1)
+    });
+    if ctx.debug >= 0 {
+        for step in &report.steps {
+            match &step.result {
+                Ok(msg) => lib::printf(&format!("{}: {msg} ({:?})", step.name, step.took)),
+                Err(e) => lib::printf(&format!("{}: error: {e} ({:?})", step.name, step.took)),
+            }
+        }
+    }
+    let res_elapsed = dt_start.elapsed();
+    lib::fatal_no_log(&res_elapsed);
+    if ctx.debug >= 0 {
+        lib::printf(&format!(
+            "Time: {:?}",
+            res_elapsed.expect("cannot get elapsed time")
+        ));
+    }
+    if report.failed() > 0 {
+        lib::printf(&format!(
+            "error: {} of {} steps failed",
+            report.failed(),
+            report.steps.len()
+        ));
+        process::exit(1);
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::SystemTime;
+use std::{env, process};
+
+use chrono::{DateTime, Duration, Utc};
+use devstats::lib;
+
+// Parses optional 'YYYY-MM-DD' 'HH' pair of arguments starting at `i`
+fn arg_hour(args: &[String], i: usize) -> Option<DateTime<Utc>> {
+    let date = args.get(i)?;
+    let hour = args.get(i + 1).map_or("0", |h| h.as_str());
+    Some(lib::parse_gha_hour(date, hour).unwrap_or_else(|e| {
+        lib::printf(&format!("error: {e}"));
+        process::exit(1)
+    }))
+}
+
+fn main() {
+    let dt_start = SystemTime::now();
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 2 || args.len() == 4 || args.len() > 7 {
+        println!(
+            "{}: optional args: 'YYYY-MM-DD' 'HH' ['YYYY-MM-DD' 'HH' [org1,org2,...] [repo1,repo2,...]], default range is GHA2DB_STARTDT - last full hour",
+            args[0]
+        );
+        println!("Orgs and repos (and GHA2DB_EXCLUDE_REPOS, GHA2DB_ACTORS_*) must be the same as the gha2db ones to verify a filtered import");
+        process::exit(1);
+    }
+    let ctx = lib::Ctx::new();
+    let from = arg_hour(&args, 1).unwrap_or(ctx.default_start_date);
+    let to = arg_hour(&args, 3).unwrap_or_else(|| Utc::now() - Duration::hours(1));
+    let empty = String::new();
+    let filter = lib::GhaRepoFilter::new(
+        &ctx,
+        args.get(5).unwrap_or(&empty),
+        args.get(6).unwrap_or(&empty),
+    );
+    let key = lib::import_key(&ctx, &filter);
+    let mut client = lib::pg_conn(&ctx);
+    let gaps = lib::verify_import_hours(&mut client, &ctx, &key, from, to).unwrap_or_else(|e| {
+        lib::printf(&format!("error: {e}"));
+        process::exit(1)
+    });
+    for gap in &gaps {
+        if gap.hours == 1 {
+            println!("{}: {}", lib::gha_file_name(gap.from), gap.status);
+        } else {
+            println!(
+                "{} - {}: {} ({} hours)",
+                lib::gha_file_name(gap.from),
+                lib::gha_file_name(gap.to),
+                gap.status,
+                gap.hours
+            );
+        }
+    }
+    if ctx.debug >= 0 {
+        lib::printf(&format!(
+            "{key}: {} hours not fully imported between {} and {}",
+            gaps.iter().map(|gap| gap.hours).sum::<u64>(),
+            lib::hour_start(from).format("%Y-%m-%d %H:%M"),
+            lib::hour_start(to).format("%Y-%m-%d %H:%M")
+        ));
+    }
+    let res_elapsed = dt_start.elapsed();
+    lib::fatal_no_log(&res_elapsed);
+    if ctx.debug >= 0 {
+        lib::printf(&format!(
+            "Time: {:?}",
+            res_elapsed.expect("cannot get elapsed time")
+        ));
+    }
+    if !gaps.is_empty() {
+        process::exit(1);
+    }
+}
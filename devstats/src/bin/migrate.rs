@@ -0,0 +1,52 @@
+use std::time::SystemTime;
+use std::{env, process};
+
+use devstats::lib;
+
+// Applies pending migrations to all given databases (or PG_DB when none given)
+fn migrate(ctx: &mut lib::Ctx, dbs: &[String]) -> bool {
+    let dbs = if dbs.is_empty() {
+        vec![ctx.pg_db.clone()]
+    } else {
+        dbs.to_vec()
+    };
+    let mut ok = true;
+    for db in dbs {
+        ctx.pg_db = db;
+        let mut client = lib::pg_conn(ctx);
+        match lib::migrate(&mut client, ctx) {
+            Ok(n) => {
+                if ctx.debug >= 0 {
+                    lib::printf(&format!(
+                        "{}: {n} migrations applied, binary schema version {}",
+                        ctx.pg_db,
+                        lib::migrations_version()
+                    ));
+                }
+            }
+            Err(e) => {
+                lib::printf(&format!("error: {e}"));
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+fn main() {
+    let dt_start = SystemTime::now();
+    let args: Vec<String> = env::args().collect();
+    let mut ctx = lib::Ctx::new();
+    let ok = migrate(&mut ctx, &args[1..]);
+    let res_elapsed = dt_start.elapsed();
+    lib::fatal_no_log(&res_elapsed);
+    if ctx.debug >= 0 {
+        lib::printf(&format!(
+            "Time: {:?}",
+            res_elapsed.expect("cannot get elapsed time")
+        ));
+    }
+    if !ok {
+        process::exit(1);
+    }
+}
@@ -8,8 +8,7 @@ fn runq(sql_file: &String, params: &[String]) -> lib::Ctx {
     println!("file: {sql_file:?}");
     // xxx
     println!("params: {params:?}");
-    let ctx = lib::Ctx::new();
-    return ctx;
+    lib::Ctx::new()
 }
 
 fn main() {
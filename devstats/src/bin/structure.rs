@@ -0,0 +1,18 @@
+use std::time::SystemTime;
+
+use devstats::lib;
+
+fn main() {
+    let dt_start = SystemTime::now();
+    let ctx = lib::Ctx::new();
+    let mut client = lib::pg_conn(&ctx);
+    lib::structure(&mut client, &ctx);
+    let res_elapsed = dt_start.elapsed();
+    lib::fatal_no_log(&res_elapsed);
+    if ctx.debug >= 0 {
+        lib::printf(&format!(
+            "Time: {:?}",
+            res_elapsed.expect("cannot get elapsed time")
+        ));
+    }
+}
@@ -0,0 +1,214 @@
+// bots.yaml: bot/automation accounts patterns, replaces the built-in list when present
+// suffixes: ['[bot]', '-bot']
+// logins: ['travis-ci']
+// regexps: ['^ci-.*-runner$']
+#[derive(Debug, Default, Deserialize)]
+struct BotsYaml {
+    #[serde(default)]
+    suffixes: Vec<String>,
+    #[serde(default)]
+    logins: Vec<String>,
+    #[serde(default)]
+    regexps: Vec<String>,
+}
+
+// Built-in bots patterns, used when there is no bots.yaml
+const BOTS_SUFFIXES: &[&str] = &["[bot]", "-bot", "-robot", "_bot"];
+const BOTS_LOGINS: &[&str] = &[
+    "appveyor",
+    "circleci",
+    "codecov-io",
+    "coveralls",
+    "dependabot",
+    "github-actions",
+    "greenkeeperio",
+    "jenkins",
+    "netlify",
+    "renovate",
+    "travis-ci",
+    "vercel",
+    "web-flow",
+];
+
+// Classifies actor logins as bot/automation accounts (case insensitive)
+// All patterns are combined into one regexp, so import time classification and the {{exclude_bots}} SQL condition always agree
+#[derive(Debug, Clone)]
+pub struct BotClassifier {
+    pattern: String,
+    re: Option<Regex>,
+}
+
+impl BotClassifier {
+    // Login ends with one of `suffixes`, is equal to one of `logins` or matches one of `regexps`
+    pub fn new<S: AsRef<str>>(suffixes: &[S], logins: &[S], regexps: &[S]) -> Result<Self, String> {
+        let mut alts: Vec<String> = Vec::new();
+        let non_empty = |v: &'_ [S]| {
+            v.iter()
+                .map(|s| s.as_ref().to_string())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<String>>()
+        };
+        alts.extend(non_empty(suffixes).iter().map(|s| format!("{}$", regex::escape(s))));
+        alts.extend(non_empty(logins).iter().map(|l| format!("^{}$", regex::escape(l))));
+        for r in non_empty(regexps) {
+            Regex::new(&r).map_err(|e| format!("invalid bots regexp {r:?}: {e}"))?;
+            alts.push(format!("({r})"));
+        }
+        if alts.is_empty() {
+            return Ok(BotClassifier {
+                pattern: String::new(),
+                re: None,
+            });
+        }
+        let pattern = alts.join("|");
+        let re = regex::RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(BotClassifier {
+            pattern,
+            re: Some(re),
+        })
+    }
+
+    // Built-in suffixes and CI accounts
+    pub fn builtin() -> Self {
+        Self::new(BOTS_SUFFIXES, BOTS_LOGINS, &[]).expect("invalid built-in bots patterns")
+    }
+
+    // Parses bots.yaml contents
+    pub fn from_yaml(data: &str) -> Result<Self, String> {
+        let yaml: BotsYaml = serde_yaml::from_str(data).map_err(|e| e.to_string())?;
+        Self::new(&yaml.suffixes, &yaml.logins, &yaml.regexps)
+    }
+
+    pub fn is_bot(&self, login: &str) -> bool {
+        self.re.as_ref().is_some_and(|re| re.is_match(login))
+    }
+
+    // Combined case insensitive regexp, empty when nothing is classified as a bot
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    // SQL condition to append to a login expression, like "lower(e.dup_actor_login) {{exclude_bots}}"
+    pub fn exclude_sql(&self) -> String {
+        if self.pattern.is_empty() {
+            return "is not null".to_string();
+        }
+        format!("!~* '{}'", self.pattern.replace('\'', "''"))
+    }
+}
+
+// Reads bots patterns from GHA2DB_BOTS_YAML (resolved via GHA2DB_DATADIR)
+// A missing default file means built-in patterns, a missing file set explicitly is an error
+pub fn bot_classifier(data_dir: &str, bots_yaml: &str) -> Result<BotClassifier, String> {
+    let path = if bots_yaml.starts_with('/') {
+        bots_yaml.to_string()
+    } else {
+        format!("{data_dir}{bots_yaml}")
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(data) => BotClassifier::from_yaml(&data).map_err(|e| format!("{path}: {e}")),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !env_is_set("GHA2DB_BOTS_YAML") => {
+            Ok(BotClassifier::builtin())
+        }
+        Err(e) => Err(format!("{path}: {e}")),
+    }
+}
+
+// Replaces {{exclude_bots}} in SQL with the condition excluding bot logins
+pub fn sql_exclude_bots(ctx: &Ctx, sql: &str) -> String {
+    if !sql.contains("{{exclude_bots}}") {
+        return sql.to_string();
+    }
+    sql.replace("{{exclude_bots}}", &ctx.bots().exclude_sql())
+}
+
+// Sets gha_actors.is_bot from current bots patterns, returns number of changed actors
+pub fn update_actors_bots(client: &mut Client, ctx: &Ctx) -> Result<u64, postgres::Error> {
+    if ctx.bots().pattern().is_empty() {
+        return exec_sql_with_err(client, ctx, "update gha_actors set is_bot = false where is_bot", &[]);
+    }
+    exec_sql_with_err(
+        client,
+        ctx,
+        "update gha_actors set is_bot = (login ~* $1) where is_bot <> (login ~* $1)",
+        &[&ctx.bots().pattern()],
+    )
+}
+
+#[cfg(test)]
+mod bots_tests {
+    use super::*;
+
+    #[test]
+    fn test_is_bot() {
+        let bots =
+            BotClassifier::new(&["[bot]", "-ci"], &["web-flow", ""], &["^k8s-.*-robot$"]).unwrap();
+        for (login, bot) in [
+            ("dependabot[bot]", true),
+            ("Renovate[BOT]", true),
+            ("[bot]x", false),
+            ("acme-ci", true),
+            ("acme-CI", true),
+            ("acmeci", false),
+            ("web-flow", true),
+            ("WEB-FLOW", true),
+            ("web-flow2", false),
+            ("my-web-flow", false),
+            ("k8s-ci-robot", true),
+            ("K8S-Release-Robot", true),
+            ("k8s-robot", false),
+            ("alice", false),
+            ("", false),
+        ] {
+            assert_eq!(bots.is_bot(login), bot, "{login}");
+        }
+        // Regexp special characters in suffixes and logins are literal
+        let bots = BotClassifier::new(&["."], &["a+b"], &[]).unwrap();
+        assert!(bots.is_bot("x.") && bots.is_bot("a+b"));
+        assert!(!bots.is_bot("xy") && !bots.is_bot("aab"));
+        assert!(BotClassifier::new(&[], &[], &["("]).is_err());
+    }
+
+    #[test]
+    fn test_builtin_and_yaml() {
+        let bots = BotClassifier::builtin();
+        assert!(bots.is_bot("github-actions") && bots.is_bot("some_bot"));
+        assert!(!bots.is_bot("botanist"));
+        let yaml = "logins: ['Travis-CI']\nregexps: ['^ci-.*-runner$']";
+        let bots = BotClassifier::from_yaml(yaml).unwrap();
+        assert!(bots.is_bot("travis-ci") && bots.is_bot("ci-x-runner") && !bots.is_bot("x[bot]"));
+        let bots = BotClassifier::from_yaml("suffixes: []").unwrap();
+        assert!(bots.pattern().is_empty() && !bots.is_bot("x[bot]"));
+        assert!(BotClassifier::from_yaml("suffixes: 1").is_err());
+    }
+
+    #[test]
+    fn test_exclude_sql() {
+        let bots = BotClassifier::new(&["'s-bot"], &["o'brien"], &[]).unwrap();
+        assert_eq!(bots.exclude_sql(), r"!~* '''s\-bot$|^o''brien$'");
+        let bots = BotClassifier::new::<&str>(&[], &[], &[]).unwrap();
+        assert_eq!(bots.exclude_sql(), "is not null");
+    }
+
+    #[test]
+    fn test_lazy_loading() {
+        let dir = format!("{}/bots_tests/", std::env::temp_dir().display());
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(format!("{dir}bots.yaml"), "regexps: ['(']").unwrap();
+        // Broken bots.yaml doesn't stop tools that don't classify actors
+        let ctx = Ctx {
+            data_dir: dir.clone(),
+            bots_yaml: "bots.yaml".to_string(),
+            ..Default::default()
+        };
+        let err = ctx.load_bots().unwrap_err();
+        assert!(err.contains("invalid bots regexp"), "{err}");
+        std::fs::write(format!("{dir}bots.yaml"), "logins: [alice]").unwrap();
+        assert!(ctx.load_bots().unwrap().is_bot("Alice"));
+        assert_eq!(sql_exclude_bots(&ctx, "login {{exclude_bots}}"), "login !~* '^alice$'");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,454 @@
+// Postgres limits the number of bind parameters in a single statement
+const PG_MAX_PARAMS: usize = 65535;
+
+// Single SQL parameter that can be buffered and sent to another thread
+pub type SqlParam = Box<dyn ToSql + Sync + Send>;
+
+// Borrows buffered parameters in a form accepted by postgres client calls
+pub fn sql_params(params: &[SqlParam]) -> Vec<&(dyn ToSql + Sync)> {
+    params
+        .iter()
+        .map(|p| p.as_ref() as &(dyn ToSql + Sync))
+        .collect()
+}
+
+// Multi-row parametrised insert: "insert into table(cols) values($1, ..), ($n+1, ..), .. suffix"
+// Rows are buffered and written when batch size or Postgres parameters limit is reached
+// `add`/`flush` report errors as text, `add_with_err`/`flush_with_err` keep the Postgres error (to retry on deadlocks)
+pub struct BulkInsert<'a> {
+    ctx: &'a Ctx,
+    table: String,
+    columns: Vec<String>,
+    suffix: String,
+    returning: String,
+    returned: Vec<Row>,
+    batch_rows: usize,
+    rows: usize,
+    params: Vec<SqlParam>,
+}
+
+impl<'a> BulkInsert<'a> {
+    pub fn new(ctx: &'a Ctx, table: &str, columns: &[&str]) -> Self {
+        let mut bulk = BulkInsert {
+            ctx,
+            table: table.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            suffix: "".to_string(),
+            returning: "".to_string(),
+            returned: Vec::new(),
+            batch_rows: 1,
+            rows: 0,
+            params: Vec::new(),
+        };
+        bulk.set_batch_size(ctx.bulk_size);
+        bulk
+    }
+
+    // Sets maximum number of rows per statement, automatically lowered to fit in Postgres parameters limit
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        let max_rows = PG_MAX_PARAMS / self.columns.len().max(1);
+        self.batch_rows = batch_size.clamp(1, max_rows);
+    }
+
+    // Appends SQL to each statement, for example "on conflict do nothing"
+    pub fn set_suffix(&mut self, suffix: &str) {
+        self.suffix = suffix.to_string();
+    }
+
+    // Adds "returning <columns>" to each statement, returned rows are collected until `take_returned`
+    pub fn set_returning(&mut self, columns: &str) {
+        self.returning = columns.to_string();
+    }
+
+    pub fn take_returned(&mut self) -> Vec<Row> {
+        std::mem::take(&mut self.returned)
+    }
+
+    pub fn batch_rows(&self) -> usize {
+        self.batch_rows
+    }
+
+    // Number of buffered rows not written yet
+    pub fn pending(&self) -> usize {
+        self.rows
+    }
+
+    // Buffers a single row, writes the batch when it is full, returns number of inserted rows
+    pub fn add(&mut self, client: &mut Client, row: Vec<SqlParam>) -> Result<u64, String> {
+        bulk_check_len(&self.table, row.len(), self.columns.len())?;
+        self.add_with_err(client, row).map_err(|e| self.error(e))
+    }
+
+    // Like `add`, row must have a value for every column
+    pub fn add_with_err(
+        &mut self,
+        client: &mut Client,
+        row: Vec<SqlParam>,
+    ) -> Result<u64, postgres::Error> {
+        debug_assert_eq!(row.len(), self.columns.len(), "{}: invalid row", self.table);
+        self.params.extend(row);
+        self.rows += 1;
+        if self.rows >= self.batch_rows {
+            return self.flush_with_err(client);
+        }
+        Ok(0)
+    }
+
+    // Writes all buffered rows, returns number of inserted rows
+    pub fn flush(&mut self, client: &mut Client) -> Result<u64, String> {
+        self.flush_with_err(client).map_err(|e| self.error(e))
+    }
+
+    // Like `flush`, in dry run mode the statement is only recorded and nothing is returned
+    pub fn flush_with_err(&mut self, client: &mut Client) -> Result<u64, postgres::Error> {
+        if self.rows == 0 {
+            return Ok(0);
+        }
+        let sql = self.statement();
+        let params = sql_params(&self.params);
+        // Buffered rows are kept when the statement fails, so the caller can retry the flush
+        let n = if self.returning.is_empty() {
+            exec_sql_with_err(client, self.ctx, &sql, &params)?
+        } else {
+            let rows = query_sql_with_err(client, self.ctx, &sql, &params)?;
+            let n = rows.len() as u64;
+            self.returned.extend(rows);
+            n
+        };
+        self.params.clear();
+        self.rows = 0;
+        Ok(n)
+    }
+
+    // SQL statement writing all buffered rows
+    fn statement(&self) -> String {
+        let mut sql = format!(
+            "insert into {}({}) {}",
+            self.table,
+            self.columns.join(", "),
+            n_values_rows(self.rows, self.columns.len())
+        );
+        if !self.suffix.is_empty() {
+            sql += " ";
+            sql += &self.suffix;
+        }
+        if !self.returning.is_empty() {
+            sql += " returning ";
+            sql += &self.returning;
+        }
+        sql
+    }
+
+    fn error(&self, e: postgres::Error) -> String {
+        format!("{}: bulk insert failed: {e}", self.table)
+    }
+}
+
+// COPY FROM STDIN data format
+pub enum CopyFormat {
+    Text,
+    Binary(Vec<Type>), // Postgres types of all columns, in order
+}
+
+// Streams rows into a table using COPY FROM STDIN, in batches
+// Text format rows are added via `add_text`, binary format rows via `add`
+pub struct BulkCopy<'a> {
+    ctx: &'a Ctx,
+    table: String,
+    columns: Vec<String>,
+    format: CopyFormat,
+    batch_rows: usize,
+    rows: usize,
+    text: String,
+    params: Vec<SqlParam>,
+}
+
+impl<'a> BulkCopy<'a> {
+    pub fn new(ctx: &'a Ctx, table: &str, columns: &[&str], format: CopyFormat) -> Self {
+        BulkCopy {
+            ctx,
+            table: table.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            format,
+            batch_rows: ctx.bulk_size.max(1),
+            rows: 0,
+            text: String::new(),
+            params: Vec::new(),
+        }
+    }
+
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_rows = batch_size.max(1);
+    }
+
+    // Number of buffered rows not copied yet
+    pub fn pending(&self) -> usize {
+        self.rows
+    }
+
+    // Buffers a single text format row, None values are NULLs
+    pub fn add_text(&mut self, client: &mut Client, row: &[Option<&str>]) -> Result<u64, String> {
+        if !matches!(self.format, CopyFormat::Text) {
+            return Err(format!("{}: text row added to binary COPY", self.table));
+        }
+        bulk_check_len(&self.table, row.len(), self.columns.len())?;
+        self.text += &copy_text_row(row);
+        self.added(client).map_err(|e| self.error(e))
+    }
+
+    // Buffers a single binary format row
+    pub fn add(&mut self, client: &mut Client, row: Vec<SqlParam>) -> Result<u64, String> {
+        if !matches!(self.format, CopyFormat::Binary(_)) {
+            return Err(format!("{}: binary row added to text COPY", self.table));
+        }
+        bulk_check_len(&self.table, row.len(), self.columns.len())?;
+        self.add_with_err(client, row).map_err(|e| self.error(e))
+    }
+
+    // Like `add`, row must have a value for every column
+    pub fn add_with_err(
+        &mut self,
+        client: &mut Client,
+        row: Vec<SqlParam>,
+    ) -> Result<u64, postgres::Error> {
+        debug_assert_eq!(row.len(), self.columns.len(), "{}: invalid row", self.table);
+        self.params.extend(row);
+        self.added(client)
+    }
+
+    // Streams all buffered rows, returns number of copied rows
+    pub fn flush(&mut self, client: &mut Client) -> Result<u64, String> {
+        self.flush_with_err(client).map_err(|e| self.error(e))
+    }
+
+    // Like `flush`, buffered rows are kept when COPY fails, so the caller can retry the flush
+    pub fn flush_with_err(&mut self, client: &mut Client) -> Result<u64, postgres::Error> {
+        if self.rows == 0 {
+            return Ok(0);
+        }
+        let mut sql = format!("copy {}({}) from stdin", self.table, self.columns.join(", "));
+        if matches!(self.format, CopyFormat::Binary(_)) {
+            sql += " binary";
+        }
+        if self.ctx.dry_run {
+            let data = match &self.format {
+                CopyFormat::Text => self.text.clone(),
+                CopyFormat::Binary(_) => format!("{:?}\n", sql_params(&self.params)),
+            };
+            record_dry_run(self.ctx, &format!("{sql}\n{data}\\."), &[]);
+            self.clear();
+            return Ok(0);
+        }
+        let start = Instant::now();
+        let res = self.copy(client, &sql);
+        log_sql(
+            self.ctx,
+            &format!("{sql} ({} rows)", self.rows),
+            &[],
+            start.elapsed(),
+            res.as_ref().ok().copied(),
+        );
+        let n = res?;
+        self.clear();
+        Ok(n)
+    }
+
+    fn copy(&self, client: &mut Client, sql: &str) -> Result<u64, postgres::Error> {
+        let mut writer = client.copy_in(sql)?;
+        match &self.format {
+            CopyFormat::Text => {
+                // A fresh writer only buffers data, it is sent (and errors are reported) by `finish`
+                let _ = writer.write_all(self.text.as_bytes());
+                writer.finish()
+            }
+            CopyFormat::Binary(types) => {
+                let mut writer = BinaryCopyInWriter::new(writer, types);
+                for row in self.params.chunks(self.columns.len()) {
+                    writer.write(&sql_params(row))?;
+                }
+                writer.finish()
+            }
+        }
+    }
+
+    fn added(&mut self, client: &mut Client) -> Result<u64, postgres::Error> {
+        self.rows += 1;
+        if self.rows >= self.batch_rows {
+            return self.flush_with_err(client);
+        }
+        Ok(0)
+    }
+
+    fn clear(&mut self) {
+        self.text.clear();
+        self.params.clear();
+        self.rows = 0;
+    }
+
+    fn error(&self, e: postgres::Error) -> String {
+        format!("{}: COPY failed: {e}", self.table)
+    }
+}
+
+fn bulk_check_len(table: &str, n: usize, columns: usize) -> Result<(), String> {
+    if n != columns {
+        return Err(format!("{table}: row has {n} values, expected {columns}"));
+    }
+    Ok(())
+}
+
+// Single COPY text format line: tab separated escaped values, None values are NULLs (\N)
+fn copy_text_row(row: &[Option<&str>]) -> String {
+    let fields: Vec<String> = row
+        .iter()
+        .map(|v| match v {
+            Some(v) => copy_text_escape(v),
+            None => "\\N".to_string(),
+        })
+        .collect();
+    format!("{}\n", fields.join("\t"))
+}
+
+// Escapes value for COPY text format
+fn copy_text_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            '\r' => escaped += "\\r",
+            '\t' => escaped += "\\t",
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod bulk_tests {
+    use super::*;
+
+    fn row(id: i64, name: Option<&str>) -> Vec<SqlParam> {
+        vec![Box::new(id), Box::new(name.map(|s| s.to_string()))]
+    }
+
+    #[test]
+    fn test_n_values() {
+        assert_eq!(n_values(1), "values($1)");
+        assert_eq!(n_values(3), "values($1, $2, $3)");
+        assert_eq!(n_array(2, 3), "($4, $5)");
+        assert_eq!(n_array(0, 0), "()");
+        assert_eq!(n_values_rows(1, 2), "values($1, $2)");
+        assert_eq!(n_values_rows(3, 2), "values($1, $2), ($3, $4), ($5, $6)");
+    }
+
+    #[test]
+    fn test_batch_size() {
+        let ctx = Ctx {
+            bulk_size: 1000,
+            ..Default::default()
+        };
+        let cols: Vec<String> = (0..100).map(|i| format!("c{i}")).collect();
+        let cols: Vec<&str> = cols.iter().map(|c| c.as_str()).collect();
+        let mut bulk = BulkInsert::new(&ctx, "t", &cols[..3]);
+        assert_eq!(bulk.batch_rows(), 1000);
+        bulk.set_batch_size(0);
+        assert_eq!(bulk.batch_rows(), 1);
+        // 100 columns: at most 655 rows fit in 65535 parameters
+        let mut bulk = BulkInsert::new(&ctx, "t", &cols);
+        assert_eq!(bulk.batch_rows(), 655);
+        bulk.set_batch_size(100);
+        assert_eq!(bulk.batch_rows(), 100);
+    }
+
+    #[test]
+    fn test_copy_text_row() {
+        assert_eq!(copy_text_row(&[Some("a"), None, Some("")]), "a\t\\N\t\n");
+        assert_eq!(
+            copy_text_row(&[Some("tab\there"), Some("new\nline\r"), Some("back\\slash")]),
+            "tab\\there\tnew\\nline\\r\tback\\\\slash\n"
+        );
+    }
+
+    #[test]
+    #[ignore = "needs Postgres (PG_* variables)"]
+    fn test_bulk_insert() {
+        let ctx = Ctx {
+            pg_db: "postgres".to_string(),
+            bulk_size: 2,
+            ..Default::default()
+        };
+        let mut client = pg_conn(&ctx);
+        let mut bulk = BulkInsert::new(&ctx, "bulk_test", &["id", "name"]);
+        assert!(bulk.add(&mut client, vec![Box::new(1i64)]).unwrap_err().contains("1 values"));
+
+        // Failed batch is kept and written by the next flush
+        assert!(bulk.add(&mut client, row(1, Some("a"))).is_ok());
+        assert!(bulk.add(&mut client, row(2, None)).is_err());
+        assert_eq!(bulk.pending(), 2);
+        client
+            .batch_execute("create temp table bulk_test(id bigint primary key, name text)")
+            .unwrap();
+        assert_eq!(bulk.flush(&mut client).unwrap(), 2);
+        assert_eq!(bulk.pending(), 0);
+
+        // Rows are written in batches of 2, conflicts are ignored (2 is already present)
+        bulk.set_suffix("on conflict do nothing");
+        bulk.set_returning("id");
+        let added: Vec<u64> = (2..7)
+            .map(|id| bulk.add(&mut client, row(id, Some("b"))).unwrap())
+            .collect();
+        assert_eq!(added, vec![0, 1, 0, 2, 0]);
+        assert_eq!(bulk.pending(), 1);
+        assert_eq!(bulk.flush(&mut client).unwrap(), 1);
+        assert_eq!(bulk.flush(&mut client).unwrap(), 0);
+        let ids: Vec<i64> = bulk.take_returned().iter().map(|r| r.get(0)).collect();
+        assert_eq!(ids, vec![3, 4, 5, 6]);
+        let n: i64 = client.query_one("select count(*) from bulk_test", &[]).unwrap().get(0);
+        assert_eq!(n, 6);
+    }
+
+    #[test]
+    #[ignore = "needs Postgres (PG_* variables)"]
+    fn test_bulk_copy() {
+        let ctx = Ctx {
+            pg_db: "postgres".to_string(),
+            bulk_size: 2,
+            ..Default::default()
+        };
+        let mut client = pg_conn(&ctx);
+        client.batch_execute("create temp table copy_test(id bigint, name text)").unwrap();
+        let cols = ["id", "name"];
+        let mut text = BulkCopy::new(&ctx, "copy_test", &cols, CopyFormat::Text);
+        assert!(text.add(&mut client, row(1, None)).is_err());
+        assert_eq!(text.add_text(&mut client, &[Some("1"), Some("a\tb\\c\nd")]).unwrap(), 0);
+        assert_eq!(text.add_text(&mut client, &[Some("2"), None]).unwrap(), 2);
+        assert_eq!(text.add_text(&mut client, &[Some("3"), Some("")]).unwrap(), 0);
+        assert_eq!(text.flush(&mut client).unwrap(), 1);
+
+        let types = vec![Type::INT8, Type::TEXT];
+        let mut binary = BulkCopy::new(&ctx, "copy_test", &cols, CopyFormat::Binary(types));
+        assert!(binary.add_text(&mut client, &[Some("1"), None]).is_err());
+        assert_eq!(binary.add(&mut client, row(4, Some("x\ty"))).unwrap(), 0);
+        assert_eq!(binary.add(&mut client, row(5, None)).unwrap(), 2);
+        // Failed COPY keeps buffered rows
+        binary.add(&mut client, vec![Box::new("6".to_string()), Box::new(None::<String>)]).unwrap();
+        assert!(binary.flush(&mut client).is_err());
+        assert_eq!(binary.pending(), 1);
+
+        let rows: Vec<(i64, Option<String>)> = client
+            .query("select id, name from copy_test order by id", &[])
+            .unwrap()
+            .iter()
+            .map(|r| (r.get(0), r.get(1)))
+            .collect();
+        let expected = vec![
+            (1, Some("a\tb\\c\nd".to_string())),
+            (2, None),
+            (3, Some("".to_string())),
+            (4, Some("x\ty".to_string())),
+            (5, None),
+        ];
+        assert_eq!(rows, expected);
+    }
+}
@@ -0,0 +1,233 @@
+// calc_metric statistics
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MetricStats {
+    pub intervals: u64, // Calculated intervals
+    pub rows: u64,      // Rows returned by metric SQL
+    pub points: u64,    // Time series points generated
+    pub written: u64,   // Points written (not already present)
+}
+
+// Metric SQL file: <sql>.sql next to metrics.yaml
+pub fn metric_sql_file(ctx: &Ctx, metric: &Metric) -> String {
+    let dir = match ctx.metrics_yaml.rfind('/') {
+        Some(i) => &ctx.metrics_yaml[..=i],
+        None => "",
+    };
+    ctx.data_file(&format!("{dir}{}.sql", metric.sql))
+}
+
+// Renders metric SQL for a single interval: {{from}}, {{to}} (dates), {{n}} (number of periods in moving aggregate, float),
+// {{project_scale}} (GHA2DB_PROJECT_SCALE or metric's project_scale) and {{exclude_bots}}
+pub fn render_metric_sql(
+    ctx: &Ctx,
+    metric: &Metric,
+    sql: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    n: u32,
+) -> String {
+    let scale = metric.project_scale.unwrap_or(ctx.project_scale);
+    let sql = sql
+        .replace("{{from}}", &from.format("%Y-%m-%d %H:%M:%S").to_string())
+        .replace("{{to}}", &to.format("%Y-%m-%d %H:%M:%S").to_string())
+        .replace("{{n}}", &format!("{n}.0"))
+        .replace("{{project_scale}}", &scale.to_string());
+    sql_exclude_bots(ctx, &sql)
+}
+
+// Series name for a name returned by SQL (or series_name_or_func), renamed by series_name_map,
+// with "_<period>" if add_period_to_name
+fn metric_series_name(metric: &Metric, name: &str, period: &str) -> String {
+    let name = normalize_name(metric.series_name_map.get(name).map_or(name, |n| n.as_str()));
+    if metric.add_period_to_name {
+        return format!("{name}_{period}");
+    }
+    name
+}
+
+fn metric_field_name(metric: &Metric, name: &str) -> String {
+    if metric.escape_value_name {
+        return normalize_name(name);
+    }
+    name.to_string()
+}
+
+// Converts metric SQL result for a single interval into time series points, result shape decides the format:
+// - one column: single value of series_name_or_func
+// - two columns (series, value): one series per row, or with multi_value one series_name_or_func series with a column per row
+//   (and desc in the "descr" column)
+// - more columns (series, value1, value2, ...): one series per row with columns named as result columns
+// With merge_series all points go into the merge_series table, their names are stored in the series column
+pub fn metric_points(metric: &Metric, period: &str, t: DateTime<Utc>, res: &TextRows) -> Result<Vec<TsPoint>, String> {
+    let mut points = Vec::new();
+    let ncols = res.columns.len();
+    let value = |row: &[Option<String>], i: usize| row.get(i).cloned().flatten().map(|v| TsValue::parse(&v));
+    let row_name = |row: &[Option<String>]| match row.first().cloned().flatten() {
+        Some(name) if !name.is_empty() => Ok(name),
+        _ => Err(format!("{}: empty series name in the first column", metric.sql)),
+    };
+    match ncols {
+        0 => {}
+        1 => {
+            if let Some(v) = res.rows.first().and_then(|row| value(row, 0)) {
+                let mut p = TsPoint::new(t, &metric_series_name(metric, &metric.series_name_or_func, period), period);
+                p.fields.insert("value".to_string(), v);
+                points.push(p);
+            }
+        }
+        2 if metric.multi_value => {
+            let mut p = TsPoint::new(t, &metric_series_name(metric, &metric.series_name_or_func, period), period);
+            for row in &res.rows {
+                if let Some(v) = value(row, 1) {
+                    p.fields.insert(metric_field_name(metric, &row_name(row)?), v);
+                }
+            }
+            if !p.fields.is_empty() {
+                if !metric.desc.is_empty() {
+                    p.fields.insert("descr".to_string(), TsValue::Text(metric.desc.clone()));
+                }
+                points.push(p);
+            }
+        }
+        _ => {
+            for row in &res.rows {
+                let mut p = TsPoint::new(t, &metric_series_name(metric, &row_name(row)?, period), period);
+                for i in 1..ncols {
+                    if let Some(v) = value(row, i) {
+                        let field = if ncols == 2 {
+                            "value".to_string()
+                        } else {
+                            metric_field_name(metric, &res.columns[i])
+                        };
+                        p.fields.insert(field, v);
+                    }
+                }
+                if !p.fields.is_empty() {
+                    points.push(p);
+                }
+            }
+        }
+    }
+    if !metric.merge_series.is_empty() {
+        let merged = normalize_name(&metric.merge_series);
+        for p in &mut points {
+            p.series = std::mem::replace(&mut p.name, merged.clone());
+        }
+    }
+    Ok(points)
+}
+
+// Calculates metric for all periods (like "d" or "w7": moving 7 weeks) starting between from and to, writes time series points
+// Histogram metrics are calculated for quick ranges ending at to instead: period is a comma separated list like "d,w,m",
+// "anno" (or annotations_ranges metric) gives ranges between annotations
+pub fn calc_metric(
+    client: &mut Client,
+    ctx: &Ctx,
+    metric: &Metric,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    period: &str,
+) -> Result<MetricStats, String> {
+    if metric.histogram {
+        let periods = if metric.annotations_ranges { "anno" } else { period };
+        let ranges = histogram_quick_ranges(client, ctx, periods, to)?;
+        return calc_histogram(ctx, metric, &ranges);
+    }
+    let (base, n) = parse_period(period)?;
+    let path = metric_sql_file(ctx, metric);
+    let sql = std::fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+    let mut stats = MetricStats::default();
+    let mut points = Vec::new();
+    let mut dt = period_start(from, base);
+    while dt <= to {
+        let next = period_add(dt, base, 1);
+        let sql_from = period_add(next, base, -(n as i32));
+        let rendered = render_metric_sql(ctx, metric, &sql, sql_from, next, n);
+        let res = text_query_sql_with_err(client, ctx, &rendered)
+            .map_err(|e| format!("{}: {} - {}: {e}", metric.sql, sql_from, next))?;
+        let mut pts = metric_points(metric, period, dt, &res)?;
+        stats.intervals += 1;
+        stats.rows += res.rows.len() as u64;
+        stats.points += pts.len() as u64;
+        points.append(&mut pts);
+        dt = next;
+    }
+    stats.written = TsWriter::default().write(client, ctx, &points)?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod calc_metric_tests {
+    use super::*;
+
+    fn rows(columns: &[&str], rows: &[&[&str]]) -> TextRows {
+        TextRows {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: rows
+                .iter()
+                .map(|r| r.iter().map(|v| Some(v.to_string())).collect())
+                .collect(),
+        }
+    }
+
+    fn names(points: &[TsPoint]) -> Vec<&str> {
+        points.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_series_name_map() {
+        let metric = Metric {
+            sql: "prs".to_string(),
+            series_name_or_func: "All PRs".to_string(),
+            add_period_to_name: true,
+            series_name_map: HashMap::from([
+                ("Kubernetes/kubectl".to_string(), "kubectl".to_string()),
+                ("All PRs".to_string(), "prs_all".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let t = parse_gha_time("2021-03-01T00:00:00Z").unwrap();
+        let res = rows(&["name", "value"], &[&["Kubernetes/kubectl", "3"], &["Other Repo", "4"]]);
+        let points = metric_points(&metric, "w", t, &res).unwrap();
+        assert_eq!(names(&points), ["kubectl_w", "other_repo_w"]);
+        let points = metric_points(&metric, "d", t, &rows(&["value"], &[&["5"]])).unwrap();
+        assert_eq!(names(&points), ["prs_all_d"]);
+        // Names without ASCII characters normalize to "" (or just the period), an empty name is skipped on write
+        let res = rows(&["name", "value"], &[&["日本 Org", "1"], &["日本", "2"]]);
+        let points = metric_points(&metric, "w", t, &res).unwrap();
+        assert_eq!(names(&points), ["_org_w", "_w"]);
+        let metric = Metric {
+            add_period_to_name: false,
+            ..metric
+        };
+        let points = metric_points(&metric, "w", t, &res).unwrap();
+        assert_eq!(names(&points), ["_org", ""]);
+        assert_eq!(ts_table(&points[1].name), None);
+    }
+
+    #[test]
+    fn test_multi_value_desc() {
+        let metric = Metric {
+            sql: "langs".to_string(),
+            series_name_or_func: "Languages".to_string(),
+            multi_value: true,
+            desc: "Languages used".to_string(),
+            ..Default::default()
+        };
+        let t = parse_gha_time("2021-03-01T00:00:00Z").unwrap();
+        let res = rows(&["name", "value"], &[&["Go", "3"], &["Rust", "4"]]);
+        let points = metric_points(&metric, "m", t, &res).unwrap();
+        assert_eq!(names(&points), ["languages"]);
+        let fields = &points[0].fields;
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["Go", "Rust", "descr"]);
+        assert_eq!(fields["descr"], TsValue::Text("Languages used".to_string()));
+        // No descr column without desc, no point without values
+        let metric = Metric {
+            desc: String::new(),
+            ..metric
+        };
+        let points = metric_points(&metric, "m", t, &res).unwrap();
+        assert!(!points[0].fields.contains_key("descr"));
+        assert!(metric_points(&metric, "m", t, &rows(&["name", "value"], &[])).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,318 @@
+// gha2db import checkpoints stored in gha_import_hours, so interrupted imports resume with the first hour not imported yet
+// Hour is "started" before its events are written, "done" (or "missing" when there was no GHA file) when it is complete
+// Checkpoints are kept per import key (import_key), hours imported with another repository or actor filter are not done
+
+// Project of import checkpoints: GHA2DB_PROJECT, or the database name when not set
+pub fn import_project(ctx: &Ctx) -> &str {
+    if ctx.project.is_empty() {
+        &ctx.pg_db
+    } else {
+        &ctx.project
+    }
+}
+
+// Checkpoints key: import_project, followed by a hash of the filter when events are filtered by repository
+// (orgs/repos, GHA2DB_EXCLUDE_REPOS) or actor (GHA2DB_ACTORS_FILTER), e.g. "kubernetes#1f0c3b7e9a2d4c58"
+pub fn import_key(ctx: &Ctx, filter: &GhaRepoFilter) -> String {
+    let mut parts = filter.definition();
+    if ctx.actors_filter {
+        let re = |re: &Option<Regex>| re.as_ref().map_or(String::new(), |re| re.to_string());
+        parts.push(format!("actors_allow:{}", re(&ctx.actors_allow)));
+        parts.push(format!("actors_forbid:{}", re(&ctx.actors_forbid)));
+    }
+    if parts.is_empty() {
+        return import_project(ctx).to_string();
+    }
+    let parts: Vec<&str> = parts.iter().map(|p| p.as_str()).collect();
+    format!("{}#{:016x}", import_project(ctx), hash_strings(&parts))
+}
+
+// Returns statuses of all hours in [from, to] that have a checkpoint
+pub fn import_hours_status(
+    client: &mut Client,
+    ctx: &Ctx,
+    key: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<HashMap<DateTime<Utc>, String>, postgres::Error> {
+    let rows = query_sql_with_err(
+        client,
+        ctx,
+        "select dt, status from gha_import_hours where project = $1 and dt between $2 and $3",
+        &[&key, &from.naive_utc(), &to.naive_utc()],
+    )?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get::<_, NaiveDateTime>(0).and_utc(), row.get(1)))
+        .collect())
+}
+
+// Marks hour as being imported
+pub fn import_hour_started(
+    client: &mut Client,
+    ctx: &Ctx,
+    key: &str,
+    dt: DateTime<Utc>,
+) -> Result<(), postgres::Error> {
+    exec_sql_with_err(
+        client,
+        ctx,
+        "insert into gha_import_hours(project, dt, status) values($1, $2, 'started')
+        on conflict(project, dt) do update set status = 'started', events = 0, written = 0, started_at = now(), finished_at = null",
+        &[&key, &dt.naive_utc()],
+    )?;
+    Ok(())
+}
+
+// Marks hour as imported, or as missing when there was no GHA file for it
+pub fn import_hour_finished(
+    client: &mut Client,
+    ctx: &Ctx,
+    key: &str,
+    dt: DateTime<Utc>,
+    stats: &GhaStats,
+) -> Result<(), postgres::Error> {
+    let status = if stats.files_missing > 0 { "missing" } else { "done" };
+    exec_sql_with_err(
+        client,
+        ctx,
+        "update gha_import_hours set status = $3, events = $4, written = $5, finished_at = now()
+        where project = $1 and dt = $2",
+        &[
+            &key,
+            &dt.naive_utc(),
+            &status,
+            &(stats.events as i64),
+            &(stats.written as i64),
+        ],
+    )?;
+    Ok(())
+}
+
+// Marks imported hour as being imported again to add events of renamed repositories, keeps its counts
+pub fn import_hour_reopened(
+    client: &mut Client,
+    ctx: &Ctx,
+    key: &str,
+    dt: DateTime<Utc>,
+) -> Result<(), postgres::Error> {
+    exec_sql_with_err(
+        client,
+        ctx,
+        "update gha_import_hours set status = 'started', finished_at = null where project = $1 and dt = $2",
+        &[&key, &dt.naive_utc()],
+    )?;
+    Ok(())
+}
+
+// Marks reopened hour as imported, adds events written by catching up
+pub fn import_hour_caught_up(
+    client: &mut Client,
+    ctx: &Ctx,
+    key: &str,
+    dt: DateTime<Utc>,
+    stats: &GhaStats,
+) -> Result<(), postgres::Error> {
+    exec_sql_with_err(
+        client,
+        ctx,
+        "update gha_import_hours set status = 'done', written = written + $3, finished_at = now()
+        where project = $1 and dt = $2",
+        &[&key, &dt.naive_utc(), &(stats.written as i64)],
+    )?;
+    Ok(())
+}
+
+// Last imported hour, None when no hour is imported yet
+pub fn last_imported_hour(
+    client: &mut Client,
+    ctx: &Ctx,
+    key: &str,
+) -> Result<Option<DateTime<Utc>>, postgres::Error> {
+    let rows = query_sql_with_err(
+        client,
+        ctx,
+        "select max(dt) from gha_import_hours where project = $1 and status = 'done'",
+        &[&key],
+    )?;
+    Ok(rows
+        .first()
+        .and_then(|row| row.get::<_, Option<NaiveDateTime>>(0))
+        .map(|dt| dt.and_utc()))
+}
+
+// Range of consecutive hours that are not fully imported, all with the same status
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportGap {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub hours: u64,
+    pub status: &'static str, // "not imported", "partial" (started but not finished) or "missing" (no GHA file)
+}
+
+// Lists hours in [from, to] that are not imported, partially imported or had no GHA file, hours from skip_dates.yaml are not reported
+// Consecutive hours with the same status are merged into a single gap
+pub fn verify_import_hours(
+    client: &mut Client,
+    ctx: &Ctx,
+    key: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<ImportGap>, String> {
+    let skip = skip_dates(ctx)?;
+    let (from, to) = (hour_start(from), hour_start(to));
+    let statuses = import_hours_status(client, ctx, key, from, to).map_err(|e| e.to_string())?;
+    Ok(import_gaps(&statuses, &skip, from, to))
+}
+
+// Gaps of hours [from, to] given checkpoint statuses of hours and hours to skip
+fn import_gaps(
+    statuses: &HashMap<DateTime<Utc>, String>,
+    skip: &HashSet<DateTime<Utc>>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<ImportGap> {
+    let mut gaps: Vec<ImportGap> = Vec::new();
+    let mut dt = from;
+    while dt <= to {
+        let status = match statuses.get(&dt).map(|s| s.as_str()) {
+            _ if skip.contains(&dt) => None,
+            Some("done") => None,
+            Some("missing") => Some("missing"),
+            Some(_) => Some("partial"),
+            None => Some("not imported"),
+        };
+        if let Some(status) = status {
+            match gaps.last_mut() {
+                Some(gap) if gap.status == status && gap.to + chrono::Duration::hours(1) == dt => {
+                    gap.to = dt;
+                    gap.hours += 1;
+                }
+                _ => gaps.push(ImportGap {
+                    from: dt,
+                    to: dt,
+                    hours: 1,
+                    status,
+                }),
+            }
+        }
+        dt += chrono::Duration::hours(1);
+    }
+    gaps
+}
+
+#[cfg(test)]
+mod checkpoints_tests {
+    use super::*;
+
+    fn dt(h: i64) -> DateTime<Utc> {
+        parse_gha_time("2021-03-17T00:00:00Z").unwrap() + chrono::Duration::hours(h)
+    }
+
+    fn gap(from: i64, to: i64, status: &'static str) -> ImportGap {
+        ImportGap {
+            from: dt(from),
+            to: dt(to),
+            hours: (to - from + 1) as u64,
+            status,
+        }
+    }
+
+    #[test]
+    fn test_import_gaps() {
+        let statuses: HashMap<DateTime<Utc>, String> = [
+            (0, "done"),
+            (1, "started"),
+            (2, "started"),
+            (3, "missing"),
+            (4, "missing"),
+            (5, "done"),
+            (8, "missing"),
+            (10, "started"),
+            (11, "done"),
+        ]
+        .into_iter()
+        .map(|(h, status)| (dt(h), status.to_string()))
+        .collect();
+        // Hours 6-7, 9 and 12-13 have no checkpoint
+        let gaps = import_gaps(&statuses, &HashSet::new(), dt(0), dt(13));
+        assert_eq!(
+            gaps,
+            vec![
+                gap(1, 2, "partial"),
+                gap(3, 4, "missing"),
+                gap(6, 7, "not imported"),
+                gap(8, 8, "missing"),
+                gap(9, 9, "not imported"),
+                gap(10, 10, "partial"),
+                gap(12, 13, "not imported"),
+            ]
+        );
+
+        // Skipped hours are not reported and split gaps
+        let skip = HashSet::from([dt(2), dt(7)]);
+        let gaps = import_gaps(&statuses, &skip, dt(0), dt(9));
+        assert_eq!(
+            gaps,
+            vec![
+                gap(1, 1, "partial"),
+                gap(3, 4, "missing"),
+                gap(6, 6, "not imported"),
+                gap(8, 8, "missing"),
+                gap(9, 9, "not imported"),
+            ]
+        );
+
+        assert!(import_gaps(&statuses, &HashSet::new(), dt(5), dt(5)).is_empty());
+        let gaps = import_gaps(&HashMap::new(), &HashSet::new(), dt(0), dt(23));
+        assert_eq!(gaps, vec![gap(0, 23, "not imported")]);
+    }
+
+    #[test]
+    fn test_import_key() {
+        let project = || Ctx {
+            project: "kubernetes".to_string(),
+            ..Default::default()
+        };
+        let ctx = project();
+        let key = |ctx: &Ctx, orgs: &str, repos: &str| {
+            import_key(ctx, &GhaRepoFilter::new(ctx, orgs, repos))
+        };
+        assert_eq!(key(&ctx, "", ""), "kubernetes");
+        let filtered = key(&ctx, "kubernetes,kubernetes-sigs", "");
+        assert!(filtered.starts_with("kubernetes#"), "{filtered}");
+        assert_eq!(filtered, key(&ctx, " kubernetes-sigs,kubernetes", ""));
+        let other = [
+            key(&ctx, "kubernetes", ""),
+            key(&ctx, "kubernetes,kubernetes-sigs", "test-infra"),
+            key(&ctx, "kubernetes/kubernetes", ""),
+            key(
+                &Ctx {
+                    exclude_repos: HashMap::from([("kubernetes/website".to_string(), true)]),
+                    ..project()
+                },
+                "kubernetes,kubernetes-sigs",
+                "",
+            ),
+            key(
+                &Ctx {
+                    actors_filter: true,
+                    actors_forbid: Some(Regex::new("bot$").unwrap()),
+                    ..project()
+                },
+                "kubernetes,kubernetes-sigs",
+                "",
+            ),
+        ];
+        let keys: HashSet<&String> = other.iter().chain([&filtered]).collect();
+        assert_eq!(keys.len(), other.len() + 1, "{keys:?}");
+
+        // Excludes and actor filters alone make a filtered import too
+        let ctx = Ctx {
+            actors_filter: true,
+            actors_allow: Some(Regex::new("^a").unwrap()),
+            ..ctx
+        };
+        assert_ne!(key(&ctx, "", ""), "kubernetes");
+    }
+}
@@ -1,5 +1,8 @@
 const DEFAULT_DATA_DIR: &str = "/etc/gha2db/";
 
+// Postgres truncates longer identifiers (table and column names)
+const PG_MAX_IDENT: usize = 63;
+
 const DEFAULT_PG_HOST: &str = "localhost";
 const DEFAULT_PG_PORT: &str = "5432";
 const DEFAULT_PG_DB: &str = "gha";
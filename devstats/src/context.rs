@@ -4,8 +4,11 @@ pub struct Ctx {
     pub debug: i8, // From GHA2DB_DEBUG Debug level: 0-no, 1-info, 2-verbose, including SQLs, default 0
     pub cmd_debug: i8, // From GHA2DB_CMDDEBUG Commands execution Debug level: 0-no, 1-only output commands, 2-output commands and their output, 3-output full environment as well, default 0
     pub github_debug: i8, // From GHA2DB_GITHUB_DEBUG debug GitHub rate limits
-    pub dry_run: bool, // From GHA2DB_DRY_RUN, import_affs tool - stop before doing any updates
-    pub json_out: bool, // From GHA2DB_JSON gha2db: write JSON files? default false
+    pub dry_run: bool, // From GHA2DB_DRY_RUN, all tools - DB layer records INSERT/UPDATE/DELETE/DDL statements instead of executing them, reads are executed
+    pub dry_run_file: String, // From GHA2DB_DRY_RUN_FILE, all tools - append statements recorded in dry run mode to this file instead of printing them, default "" (print)
+    pub json_out: bool, // From GHA2DB_JSON gha2db: write normalised JSON files of accepted events? default false
+    pub json_dir: String, // From GHA2DB_JSON_DIR gha2db: directory for JSON output and quarantined broken JSONs, default "jsons/"
+    pub json_ndjson: bool, // From GHA2DB_JSON_NDJSON gha2db: write one YYYY-MM-DD-H.ndjson file per hour instead of one file per event, default false
     pub db_out: bool,  // From GHA2DB_NODB gha2db: write to SQL database, default true
     pub st: bool, // From GHA2DB_ST true: use single threaded version, false: use multi threaded version, default false
     pub ncpus: u16, // From GHA2DB_NCPUS, set to override number of CPUs to run, this overwrites GHA2DB_ST, default 0 (which means do not use it)
@@ -14,34 +17,42 @@ pub struct Ctx {
     pub pg_db: String, // From PG_DB, default "gha"
     pub pg_user: String, // From PG_USER, default "gha_admin"
     pub pg_pass: String, // From PG_PASS, default "password"
-    pub pg_ssl: String, // From PG_SSL, default "disable"
-    // pub index: bool, // From GHA2DB_INDEX Create DB index? default false
-    // pub table: bool, // From GHA2DB_SKIPTABLE Create table structure? default true
-    // pub tools: bool, // From GHA2DB_SKIPTOOLS Create DB tools (like views, summary tables, materialized views etc)? default true
+    pub pg_ssl: String, // From PG_SSL, default "disable", "allow" and "prefer" are accepted too (plain connection), other values are fatal
+    pub import_memory: usize, // From GHA2DB_IMPORT_MEMORY gha2db tool, maximum size (in MB) of JSONs read but not yet written when importing with multiple threads, default 512
+    pub bulk_size: usize, // From GHA2DB_BULK_SIZE, number of rows written by a single multi-row insert or COPY batch, default 1000
+    pub index: bool, // From GHA2DB_INDEX Create DB index? default false
+    pub table: bool, // From GHA2DB_SKIPTABLE Create table structure? default true
+    pub tools: bool, // From GHA2DB_SKIPTOOLS Create DB tools (like views, summary tables, materialized views etc)? default true
     // pub mgetc: String, // From GHA2DB_MGETC Character returned by mgetc (if non empty), default ""
     pub q_out: bool,   // From GHA2DB_QOUT output all SQL queries?, default false
     pub ctx_out: bool, // From GHA2DB_CTXOUT output all context data (this struct), default false
+    pub slow_query: Duration, // From GHA2DB_SLOW_QUERY, log queries taking at least that many seconds (fractions allowed) at debug level 1, default 0 (disabled)
     // pub log_time: bool, // From GHA2DB_SKIPTIME, output time with all lib.Printf(...) calls, default true, use GHA2DB_SKIPTIME to disable
-    // pub default_start_date: SystemTime, // From GHA2DB_STARTDT, default `2012-07-01 00:00 UTC`, expects format "YYYY-MM-DD HH:MI:SS", can be set in `projects.yaml` via `start_date:`, value from projects.yaml (if set) has the highest priority.
-    // pub force_start_date: bool,         // From GHA2DB_STARTDT_FORCE, default false
+    pub default_start_date: DateTime<Utc>, // From GHA2DB_STARTDT, default `2012-07-01 00:00 UTC`, expects format "YYYY-MM-DD HH:MI:SS", can be set in `projects.yaml` via `start_date:`, value from projects.yaml (if set) has the highest priority.
+    pub force_start_date: bool,         // From GHA2DB_STARTDT_FORCE, default false, gha2db tool re-imports hours already marked as imported in gha_import_hours
     // pub last_series: String, // From GHA2DB_LASTSERIES, use this TSDB series to determine last timestamp date, default "events_h"
-    // pub skip_tsdb: bool, // From GHA2DB_SKIPTSDB gha2db_sync tool, skip TS DB processing? for calc_metric it skips final series write, default false
-    // pub skip_pdb: bool, // From GHA2DB_SKIPPDB gha2db_sync tool, skip Postgres DB processing (gha2db part) default false
-    // pub reset_tsdb: bool, // From GHA2DB_RESETTSDB sync tool, regenerate all TS points? default false
-    // pub reset_ranges: bool, // From GHA2DB_RESETRANGES sync tool, regenerate all past quick ranges? default false
+    pub skip_tsdb: bool, // From GHA2DB_SKIPTSDB gha2db_sync tool, skip TS DB processing? for calc_metric it skips final series write, default false
+    pub skip_pdb: bool, // From GHA2DB_SKIPPDB gha2db_sync tool, skip Postgres DB processing (gha2db part) default false
+    pub reset_tsdb: bool, // From GHA2DB_RESETTSDB sync tool, regenerate all TS points? default false
+    pub reset_ranges: bool, // From GHA2DB_RESETRANGES sync tool, regenerate all past quick ranges? default false
     // pub explain: bool, // From GHA2DB_EXPLAIN runq tool, prefix query with "explain " - it will display query plan instead of executing real query, default false
-    // pub old_format: bool, // From GHA2DB_OLDFMT gha2db tool, if set then use pre 2015 GHA JSONs format
-    // pub exact: bool, // From GHA2DB_EXACT gha2db tool, if set then orgs list provided from commandline is used as a list of exact repository full names, like "a/b,c/d,e", if not only full names "a/b,x/y" can be treated like this, names without "/" are either orgs or repos.
+    pub old_format: bool, // From GHA2DB_OLDFMT gha2db tool, if set then use pre 2015 GHA JSONs format
+    pub gha_dir: String, // From GHA2DB_GHA_DIR gha2db tool, read hourly YYYY-MM-DD-H.json.gz files from this local directory instead of downloading them, default "" (download)
+    pub gha_url: String, // From GHA2DB_GHA_URL gha2db tool, base URL of hourly GHA archives, default "https://data.gharchive.org/"
+    pub gha_cache: String, // From GHA2DB_GHA_CACHE gha2db tool, keep verified downloaded hourly files in this directory and reuse them, default "" (no cache)
+    pub exact: bool, // From GHA2DB_EXACT gha2db tool, if set then orgs list provided from commandline is used as a list of exact repository full names, like "a/b,c/d,e", if not only full names "a/b,x/y" can be treated like this, names without "/" are either orgs or repos.
     // pub log_to_db: bool, // From GHA2DB_SKIPLOG all tools, if set, DB logging into Postgres table `gha_logs` in `devstats` database will be disabled
     // pub local: bool, // From GHA2DB_LOCAL many tools, if set it will use data files prefixed with "./" to use local ones. Otherwise it will search for data files in /etc/gha2db.
     // pub absolute: bool, // From GHA2DB_ABSOLUTE runq tool, if set it will use data files without any prefix (allowing absolute paths as well). Otherwise it will search for data files in /etc/gha2db.
-    // pub local_cmd: bool, // From GHA2DB_LOCAL_CMD many tools, if set it will call other tools prefixed with "./" to use locally compiled ones. Otherwise it will call binaries without prefix (so it will use those in $PATH).
-    // pub metrics_yaml: String, // From GHA2DB_METRICS_YAML gha2db_sync tool, set other metrics.yaml file, default is "metrics/{{project}}metrics.yaml"
+    pub local_cmd: bool, // From GHA2DB_LOCAL_CMD many tools, if set it will call other tools prefixed with "./" to use locally compiled ones. Otherwise it will call binaries without prefix (so it will use those in $PATH).
+    pub metrics_yaml: String, // From GHA2DB_METRICS_YAML gha2db_sync tool, set other metrics.yaml file, default is "metrics/{{project}}/metrics.yaml"
     // pub tags_yaml: String, // From GHA2DB_TAGS_YAML tags tool, set other tags.yaml file, default is "metrics/{{project}}/tags.yaml"
     // pub columns_yaml: String, // From GHA2DB_COLUMNS_YAML tags tool, set other columns.yaml file, default is "metrics/{{project}}/columns.yaml"
     // pub vars_yaml: String, // From GHA2DB_VARS_YAML db_vars tool, set other vars.yaml file (full path), default is "metrics/{{project}}/vars.yaml"
     // pub vars_fn_yamlaml: String, // From GHA2DB_VARS_FN_YAML db_vars tool, set other vars.yaml file (final file name without path), default is "vars.yaml"
-    // pub skip_dates_yaml: String, // From GHA2DB_SKIP_DATES_YAML gha2db tool, set other skip_dates.yaml file, default is "skip_dates.yaml"
+    pub skip_dates_yaml: String, // From GHA2DB_SKIP_DATES_YAML gha2db tool, set other skip_dates.yaml file, default is "skip_dates.yaml"
+    pub bots_yaml: String, // From GHA2DB_BOTS_YAML gha2db tool and {{exclude_bots}} SQL helper, set other bots.yaml file, default is "bots.yaml" (built-in patterns if it doesn't exist)
+    pub bots: OnceLock<BotClassifier>, // Bot/automation accounts patterns read from bots_yaml on first use, see Ctx::bots
     // pub github_oauth: String, // From GHA2DB_GITHUB_OAUTH ghapi2db tool, if not set reads from /etc/github/oauth file, set to "-" to force public access.
     // pub clear_db_period: String, // From GHA2DB_MAXLOGAGE gha2db_sync tool, maximum age of devstats.gha_logs entries, default "1 week"
     // pub clear_affs_lock_period: String, // From GHA2DB_MAX_AFFS_LOCK_AGE devstats tool, maximum age of devstats.gha_metrics "affs_lock" age, default "16 hours"
@@ -60,7 +71,7 @@ pub struct Ctx {
     pub exec_fatal: bool, // default true, set this manually to false to avoid lib.ExecCommand calling os.Exit() on failure and return error instead
     pub exec_quiet: bool, // default false, set this manually to true to have quite exec failures (for example `get_repos` git-clones or git-pulls on errors).
     pub exec_output: bool, // default false, set to true to capture commands STDOUT
-    // pub project: String, // From GHA2DB_PROJECT, gha2db_sync default "", You should set it to something like "kubernetes", "prometheus" etc.
+    pub project: String, // From GHA2DB_PROJECT, gha2db_sync default "", You should set it to something like "kubernetes", "prometheus" etc.
     // pub tests_yaml: String, // From GHA2DB_TESTS_YAML ./dbtest.sh tool, set other tests.yaml file, default is "tests.yaml"
     // pub repos_dir: String,  // From GHA2DB_REPOS_DIR get_repos tool, default "~/devstats_repos/"
     // pub process_repos: bool, // From GHA2DB_PROCESS_REPOS get_repos tool, enable processing (cloning/pulling) all devstats repos, default false
@@ -72,10 +83,10 @@ pub struct Ctx {
     // pub company_acq_yaml: String, // From GHA2DB_COMPANY_ACQ_YAML, import_affs tool, set non-standard "companies.yaml" file
     // pub projects_override: HashMap<String, bool>, // From GHA2DB_PROJECTS_OVERRIDE, get_repos and ./devstats tools - for example "-pro1,+pro2" means never sync pro1 and always sync pro2 (even if disabled in `projects.yaml`).
     // pub affiliations_json: String, // From GHA2DB_AFFILIATIONS_JSON, import_affs tool - set main affiliations file, default "github_users.json"
-    // pub exclude_repos: HashMap<String, bool>, // From GHA2DB_EXCLUDE_REPOS, gha2db tool, default "" - comma separated list of repos to exclude, example: "theupdateframework/notary,theupdateframework/other"
+    pub exclude_repos: HashMap<String, bool>, // From GHA2DB_EXCLUDE_REPOS, gha2db tool, default "" - comma separated list of repos to exclude, example: "theupdateframework/notary,theupdateframework/other"
     // pub input_dbs: Vec<String>, // From GHA2DB_INPUT_DBS, merge_dbs tool - list of input databases to merge, order matters - first one will insert on a clean DB, next will do insert ignore (to avoid constraints failure due to common data)
     // pub output_db: String, // From GHA2DB_OUTPUT_DB, merge_dbs tool - output database to merge into
-    // pub mm_offset: i8, // From GHA2DB_TMOFFSET, gha2db_sync tool - uses time offset to decide when to calculate various metrics, default offset is 0 which means UTC, good offset for USA is -6, and for Poland is 1 or 2
+    pub mm_offset: i8, // From GHA2DB_TMOFFSET, gha2db_sync tool - uses time offset to decide when to calculate various metrics, default offset is 0 which means UTC, good offset for USA is -6, and for Poland is 1 or 2
     // pub default_hostname: String, // "devstats.cncf.io"
     // pub recent_range: String, // From GHA2DB_RECENT_RANGE, ghapi2db tool, default '12 hours'. This is a recent period to check open issues/PR to fix their labels and milestones.
     // pub recent_repos_range: String, // From GHA2DB_RECENT_REPOS_RANGE, ghapi2db tool, default '1 day'. This is a recent period to check modified repositories.
@@ -83,33 +94,34 @@ pub struct Ctx {
     pub max_ghapi_wait_seconds: i16, // From GHA2DB_MAX_GHAPI_WAIT, ghapi2db tool, maximum wait time for GitHub API points reset (in seconds).
     pub max_ghapi_retry: i16, // From GHA2DB_MAX_GHAPI_RETRY, ghapi2db tool, maximum wait retries
     // pub ghapi_error_is_fatal: bool, // From GHA2DB_GHAPI_ERROR_FATAL, ghapi2db tool, make any GH API error fatal, default false
-    // pub skip_ghapi: bool, // From GHA2DB_GHAPISKIP, ghapi2db tool, if set then tool is skipping GH API calls (all: events (artificial events to make sure we are in sync with GH) and commits (enriches obfuscated GHA commits data)
+    pub skip_ghapi: bool, // From GHA2DB_GHAPISKIP, ghapi2db tool, if set then tool is skipping GH API calls (all: events (artificial events to make sure we are in sync with GH) and commits (enriches obfuscated GHA commits data)
     // pub skip_api_events: bool, // From GHA2DB_GHAPISKIPEVENTS, ghapi2db tool, if set then tool is skipping GH API events sync
     // pub skip_api_commits: bool, // From GHA2DB_GHAPISKIPCOMMITS, ghapi2db tool, if set then tool is skipping GH API commits enrichment
     // pub skip_api_licenses: bool, // From GHA2DB_GHAPISKIPLICENSES, ghapi2db tool, if set then tool is skipping GH API licenses enrichment
     // pub force_api_licenses: bool, // From GHA2DB_GHAPIFORCELICENSES, ghapi2db tool, if set, recheck licenses on repos that already have licenses fetched
     // pub skip_api_langs: bool, // From GHA2DB_GHAPISKIPLANGS, ghapi2db tool, if set then tool is skipping GH API repos programming languages enrichment
     // pub force_api_langs: bool, // From GHA2DB_GHAPIFORCELANGS, ghapi2db tool, if set, recheck programming languages on repos that already have them fetched
-    // pub skip_set_repos: bool, // From GHA2DB_GETREPOSSKIP, get_repos tool, if set then tool does nothing
+    pub skip_get_repos: bool, // From GHA2DB_GETREPOSSKIP, get_repos tool, if set then tool does nothing
     // pub csv_file: String, // From GHA2DB_CSVOUT, runq tool, if set, saves result in this file
-    // pub compute_all: bool, // From GHA2DB_COMPUTE_ALL, all tools, if set then no period decisions are taken based on time, but all possible periods are recalculated
-    // pub actors_filter: bool, // From GHA2DB_ACTORS_FILTER gha2db tool, if enabled then actor filterning will be added, default false
-    // pub actors_allow: Regex, // From GHA2DB_ACTORS_ALLOW, gha2db tool, process JSON if actor matches this regexp, default "" which means skip this check
-    // pub actors_forbid: Regex, // From GHA2DB_ACTORS_FORBID, gha2db tool, process JSON if actor doesn't match this regexp, default "" which means skip this check
-    // pub skip_metrics: HashMap<String, bool>, // From GHA2DB_SKIP_METRICS, gha2db_sync tool, default "" - comma separated list of metrics to skip, as given by "sql: name" in the "metrics.yaml" file. Those metrics will be skipped.
-    // pub only_metrics: HashMap<String, bool>, // From GHA2DB_ONLY_METRICS, gha2db_sync tool, default "" - comma separated list of metrics to process, as given by "sql: name" in the "metrics.yaml" file. Only those metrics will be calculated.
-    // pub allow_broken_json: bool, // From GHA2DB_ALLOW_BROKEN_JSON, gha2db tool, default false. If set then gha2db skips broken jsons and saves them as jsons/error_YYYY-MM-DD-h-n-m.json (n is the JSON number (1-m) of m JSONS array)
+    pub compute_all: bool, // From GHA2DB_COMPUTE_ALL, all tools, if set then no period decisions are taken based on time, but all possible periods are recalculated
+    pub actors_filter: bool, // From GHA2DB_ACTORS_FILTER gha2db tool, if enabled then actor filterning will be added, default false
+    pub actors_allow: Option<Regex>, // From GHA2DB_ACTORS_ALLOW, gha2db tool, process JSON if actor matches this regexp, default "" which means skip this check
+    pub actors_forbid: Option<Regex>, // From GHA2DB_ACTORS_FORBID, gha2db tool, process JSON if actor doesn't match this regexp, default "" which means skip this check
+    pub skip_metrics: HashMap<String, bool>, // From GHA2DB_SKIP_METRICS, gha2db_sync tool, default "" - comma separated list of metrics to skip, as given by "sql: name" in the "metrics.yaml" file. Those metrics will be skipped.
+    pub only_metrics: HashMap<String, bool>, // From GHA2DB_ONLY_METRICS, gha2db_sync tool, default "" - comma separated list of metrics to process, as given by "sql: name" in the "metrics.yaml" file. Only those metrics will be calculated.
+    pub allow_broken_json: bool, // From GHA2DB_ALLOW_BROKEN_JSON, gha2db tool, default false. If set then gha2db skips broken jsons and saves them as <GHA2DB_JSON_DIR>error_YYYY-MM-DD-h-n-m.json (n is the JSON number (1-m) of m JSONS array)
+    pub max_broken_json_ratio: f64, // From GHA2DB_MAX_BROKEN_JSON_RATIO, gha2db tool, fail the run when more than this fraction of an hour's JSONs was broken (with GHA2DB_ALLOW_BROKEN_JSON), default 0.01
     // pub jsons_dir: String,       // From GHA2DB_JSONS_DIR, website_data tool, default "./jsons/"
     // pub website_data: bool, // From GHA2DB_WEBSITEDATA, devstats tool, run website_data just after sync is complete, default false.
     // pub skip_update_events: bool, // From GHA2DB_SKIP_UPDATE_EVENTS, ghapi2db tool, drop and recreate artificial events if their state differs, default false
-    // pub compute_periods: HashMap<String, HashSet<bool>>, // From GHA2DB_FORCE_PERIODS, gha2db_sync tool, force recompute only given periods, "y10:t,m:f,...", default ""
+    pub compute_periods: HashMap<String, HashSet<bool>>, // From GHA2DB_FORCE_PERIODS, gha2db_sync tool, force recompute only given periods, "y10:t,m:f,...", default ""
     // pub auto_fetch_commits: bool, // From GHA2DB_NO_AUTOFETCHCOMMITS, ghapi2db, disable fetching from last enriched commit data, it will fetch from 'RecentRange instead, AutoFetchCommits is enabled by default
-    // pub skip_tags: bool, // From GHA2DB_SKIP_TAGS, gha2db_sync tool, skip calling tags tool, default false
-    // pub skip_annotations: bool, // From GHA2DB_SKIP_ANNOTATIONS, gha2db_sync tool, skip calling annotations tool, default false
-    // pub skip_columns: bool, // From GHA2DB_SKIP_COLUMNS, gha2db_sync tool, skip calling columns tool, default false
-    // pub run_columns: bool, // From GHA2DB_RUN_COLUMNS, gha2db_sync tool, force calling columns tool, default false
-    // pub skip_vars: bool, // From GHA2DB_SKIP_VARS, gha2db_sync tool, skip calling vars tool, default false
-    // pub skip_rand: bool, // From GHA2DB_SKIP_RAND, gha2db_sync tool, skip randomizing metrics calculation, default false
+    pub skip_tags: bool, // From GHA2DB_SKIP_TAGS, gha2db_sync tool, skip calling tags tool, default false
+    pub skip_annotations: bool, // From GHA2DB_SKIP_ANNOTATIONS, gha2db_sync tool, skip calling annotations tool, default false
+    pub skip_columns: bool, // From GHA2DB_SKIP_COLUMNS, gha2db_sync tool, skip calling columns tool, default false
+    pub run_columns: bool, // From GHA2DB_RUN_COLUMNS, gha2db_sync tool, force calling columns tool, default false
+    pub skip_vars: bool, // From GHA2DB_SKIP_VARS, gha2db_sync tool, skip calling vars tool, default false
+    pub skip_rand: bool, // From GHA2DB_SKIP_RAND, gha2db_sync tool, skip randomizing metrics calculation, default false
     // pub exclude_vars: HashMap<String, bool>, // From GHA2DB_EXCLUDE_VARS, vars tool, default "" - comma separated list of variable names to exclude, example: "hostname,projects_health_partial_html"
     // pub only_vars: HashMap<String, bool>, // From GHA2DB_ONLY_VARS, vars tool, default "" - comma separated list of variable names to write (and skip all others): "hostname,projects_health_partial_html", not used if empty
     // pub skip_shared_db: bool, // From GHA2DB_SKIP_SHAREDDB, annotations tool, default false, will skip writing to shared_db (from projects.yaml) if set
@@ -122,9 +134,9 @@ pub struct Ctx {
     // pub check_imported_sha: bool, // From GHA2DB_CHECK_IMPORTED_SHA, import_affs tool - check if given JSON was already imported using 'gha_imported_shas' table
     // pub only_check_imported_sha: bool, // From GHA2DB_ONLY_CHECK_IMPORTED_SHA, import_affs tool - check if given JSON was already imported using 'gha_imported_shas' table, do not attempt to import, only return status: 3=imported, 0=not imported
     // pub enable_metrics_drop: bool, // From GHA2DB_ENABLE_METRICS_DROP, if enabled will process each metric's 'drop:' property if present - use when regenerating affiliations data or reinitializing entire TSDB data
-    // pub http_timeout: i16, // From GHA2DB_HTTP_TIMEOUT, gha2db - data.gharchive.org timeout value in minutes, default 2
-    // pub http_retry: i8, // From GHA2DB_HTTP_RETRY, gha2db - data.gharchive.org data fetch retries, default 4 (each retry takes 1*timeout*N), so in default config it will try timeouts: 1min, 2min, 3min, but if timeout is 3 and retry is 2, it will try 3min, 6min
-    // pub project_scale: f64, // From GHA2DB_PROJECT_SCALE, calc_metric tool, project scale (default 1), some metrics can use this to adapt their SQLs to bigger/smaller projects
+    pub http_timeout: i16, // From GHA2DB_HTTP_TIMEOUT, gha2db - data.gharchive.org timeout value in minutes, default 3
+    pub http_retry: i8, // From GHA2DB_HTTP_RETRY, gha2db - data.gharchive.org data fetch retries, default 5 (each retry takes 1*timeout*N), so in default config it will try timeouts: 3min, 6min, 9min, 12min, 15min, but if timeout is 3 and retry is 2, it will try 3min, 6min
+    pub project_scale: f64, // From GHA2DB_PROJECT_SCALE, calc_metric tool, project scale (default 1), some metrics can use this to adapt their SQLs to bigger/smaller projects
     // pub pid_file_root: String, // From GHA2DB_PID_FILE_ROOT, devstats tool, use '/tmp/PidFileRoot.pid' as PID file, default 'devstats' -> '/tmp/devstats.pid'
     // pub shared_db: String, // Currently annotations tool read this from projects.yaml:shared_db and if set, outputs annotations data to the sharded DB in addition to the current DB
     // pub project_main_repo: String, // Used by annotations tool to store project's main repo name
@@ -132,12 +144,13 @@ pub struct Ctx {
     pub can_reconnect: bool, // True, unless connecting to a custom database, in this case there can be multiple threads sharing context and we don't want to write to a random database
     pub commits_files_stats_enabled: bool, // True, can be disabled by GHA2DB_SKIP_COMMITS_FILES, get_repos tool
     pub commits_loc_stats_enabled: bool, // True, can be disabled by GHA2DB_SKIP_COMMITS_LOC, get_repos tool
-    // pub recalc_reciprocal: i8, // From GHA2DB_RECALC_RECIPROCAL: 1/RecalcReciprocal of recalc metric at given datetime, even if it should be calculated at this datetime, default 24 (means 4.1(6)%, or about once/day)
-    // pub max_histograms: i8, // From GHA2DB_MAX_HIST: maximum histogram concurrency, default: 0 - means unlimited
+    pub recalc_reciprocal: i8, // From GHA2DB_RECALC_RECIPROCAL: 1/RecalcReciprocal of recalc metric at given datetime, even if it shouldn't be calculated at this datetime, default 24 (means 4.1(6)%, or about once/day)
+    pub max_histograms: i8, // From GHA2DB_MAX_HIST: maximum histogram concurrency, default: 0 - means unlimited
     // pub max_run_duration: HashMap<String, [i16; 2]>, // From GHA2DB_MAX_RUN_DURATION, how log given programs can run and exist status after timeout, for example "tags:1h:0,calc_metric:12h:1"
     pub rand_compute_at_this_date: bool, // Use rand to decide if a given date period must be calculated at this date or not.
     // pub refresh_commit_roles: bool, // From GHA2DB_REFRESH_COMMIT_ROLES - will process all commiths in DB and for every single one of them it will generate gha_commits_roles entries.
     pub allow_rand_tags_cols_compute: bool, // If set, then tags and columns will only be computed at random 0-5 hour, otherwise always when hour<6.
+    pub tool: String, // Name of the running tool (binary name), used in logs and as Postgres application_name
 }
 
 // Ctx implementation
@@ -149,17 +162,59 @@ impl Ctx {
     }
 
     fn set_cpus(&mut self) {
-        self.st = !env_is_empty("GHA2DB_ST");
-        if !env_is_empty("GHA2DB_NCPUS") {
-            let ncpus = env_number::<u16>("GHA2DB_NCPUS");
-            if ncpus > 0 {
-                self.ncpus = ncpus;
-                if ncpus == 1 {
-                    self.st = true;
-                }
+        let ncpus = if env_is_empty("GHA2DB_NCPUS") {
+            0
+        } else {
+            env_number::<u16>("GHA2DB_NCPUS")
+        };
+        self.set_cpus_from(!env_is_empty("GHA2DB_ST"), ncpus);
+    }
+
+    // GHA2DB_ST and GHA2DB_NCPUS (0 when not set), GHA2DB_NCPUS=1 means single threaded
+    fn set_cpus_from(&mut self, st: bool, ncpus: u16) {
+        self.st = st;
+        if ncpus > 0 {
+            self.ncpus = ncpus;
+            if ncpus == 1 {
+                self.st = true;
             }
         }
     }
+
+    // Number of threads to use: GHA2DB_NCPUS if set, 1 in single threaded mode, number of CPUs otherwise
+    pub fn threads_num(&self) -> usize {
+        if self.ncpus > 0 {
+            return self.ncpus as usize;
+        }
+        if self.st {
+            return 1;
+        }
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
+    // Path of a data file: absolute paths are used as they are, relative ones are resolved against GHA2DB_DATADIR
+    pub fn data_file(&self, name: &str) -> String {
+        if name.starts_with('/') {
+            return name.to_string();
+        }
+        format!("{}{name}", self.data_dir)
+    }
+
+    // Bots patterns, bots.yaml is only read by tools classifying actors, so a broken file doesn't stop other tools
+    pub fn load_bots(&self) -> Result<&BotClassifier, String> {
+        if let Some(bots) = self.bots.get() {
+            return Ok(bots);
+        }
+        let bots = bot_classifier(&self.data_dir, &self.bots_yaml)?;
+        Ok(self.bots.get_or_init(|| bots))
+    }
+
+    // Same as load_bots, fatal if bots.yaml cannot be read
+    pub fn bots(&self) -> &BotClassifier {
+        let bots = self.load_bots();
+        fatal_no_log(&bots);
+        bots.expect("cannot read bots patterns")
+    }
 }
 
 impl Default for Ctx {
@@ -180,6 +235,12 @@ impl Default for Ctx {
         let rand_compute_at_this_date = true;
         let allow_rand_tags_cols_compute = false;
 
+        // Tool name
+        let tool = env::args()
+            .next()
+            .and_then(|arg| arg.rsplit('/').next().map(|s| s.to_string()))
+            .unwrap_or_default();
+
         // Commits analysis
         let commits_files_stats_enabled = env_is_empty("GHA2DB_SKIP_COMMITS_FILES");
         let commits_loc_stats_enabled = env_is_empty("GHA2DB_SKIP_COMMITS_LOC");
@@ -192,10 +253,16 @@ impl Default for Ctx {
 
         // Outputs
         let json_out = !env_is_empty("GHA2DB_JSON");
+        let mut json_dir = env_or_default("GHA2DB_JSON_DIR", "jsons/".to_string());
+        if !json_dir.ends_with('/') {
+            json_dir += "/";
+        }
+        let json_ndjson = !env_is_empty("GHA2DB_JSON_NDJSON");
         let db_out = env_is_empty("GHA2DB_NODB");
 
         // Dry run
         let dry_run = !env_is_empty("GHA2DB_DRY_RUN");
+        let dry_run_file = env_or_default("GHA2DB_DRY_RUN_FILE", "".to_string());
 
         // GitHub API points and waiting for reset
         let mut min_ghapi_points = 1i16;
@@ -248,6 +315,187 @@ impl Default for Ctx {
         }
         let q_out = !env_is_empty("GHA2DB_QOUT");
         let ctx_out = !env_is_empty("GHA2DB_CTXOUT");
+        let mut slow_query = Duration::ZERO;
+        if !env_is_empty("GHA2DB_SLOW_QUERY") {
+            let secs = env_number::<f64>("GHA2DB_SLOW_QUERY");
+            if secs > 0.0 {
+                slow_query = Duration::from_secs_f64(secs);
+            }
+        }
+
+        // Bulk writes batch size
+        let mut bulk_size = 1000;
+        if !env_is_empty("GHA2DB_BULK_SIZE") {
+            let size = env_number::<usize>("GHA2DB_BULK_SIZE");
+            if size > 0 {
+                bulk_size = size;
+            }
+        }
+
+        // Multi threaded import memory budget
+        let mut import_memory = 512 << 20;
+        if !env_is_empty("GHA2DB_IMPORT_MEMORY") {
+            let mb = env_number::<usize>("GHA2DB_IMPORT_MEMORY");
+            if mb > 0 {
+                import_memory = mb << 20;
+            }
+        }
+
+        // Old (pre 2015) GHA JSONs format
+        let old_format = !env_is_empty("GHA2DB_OLDFMT");
+
+        // GHA archives source: local directory, or HTTP with an optional on-disk cache
+        let mut gha_dir = env_or_default("GHA2DB_GHA_DIR", "".to_string());
+        if !gha_dir.is_empty() && !gha_dir.ends_with('/') {
+            gha_dir += "/";
+        }
+        let mut gha_url = env_or_default("GHA2DB_GHA_URL", "https://data.gharchive.org/".to_string());
+        if !gha_url.ends_with('/') {
+            gha_url += "/";
+        }
+        let mut gha_cache = env_or_default("GHA2DB_GHA_CACHE", "".to_string());
+        if !gha_cache.is_empty() && !gha_cache.ends_with('/') {
+            gha_cache += "/";
+        }
+
+        // Default start date
+        let mut default_start_date = NaiveDate::from_ymd_opt(2012, 7, 1)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .expect("invalid default start date")
+            .and_utc();
+        if !env_is_empty("GHA2DB_STARTDT") {
+            let dt = time_parse_any(&env::var("GHA2DB_STARTDT").unwrap_or_default());
+            fatal_no_log(&dt);
+            default_start_date = dt.expect("invalid GHA2DB_STARTDT");
+        }
+        let force_start_date = !env_is_empty("GHA2DB_STARTDT_FORCE");
+
+        // Project
+        let project = env_or_default("GHA2DB_PROJECT", "".to_string());
+        let proj = if project.is_empty() {
+            "".to_string()
+        } else {
+            format!("{project}/")
+        };
+
+        // Project scale
+        let mut project_scale = 1.0;
+        if !env_is_empty("GHA2DB_PROJECT_SCALE") {
+            project_scale = env_number::<f64>("GHA2DB_PROJECT_SCALE");
+        }
+
+        // YAML config files
+        let metrics_yaml = env_or_default("GHA2DB_METRICS_YAML", format!("metrics/{proj}metrics.yaml"));
+
+        // Time offset for gha2db_sync
+        let mut mm_offset = 0;
+        if !env_is_empty("GHA2DB_TMOFFSET") {
+            mm_offset = env_number::<i8>("GHA2DB_TMOFFSET");
+        }
+
+        // Calculate all periods, or force compute given periods
+        let compute_all = !env_is_empty("GHA2DB_COMPUTE_ALL");
+        let compute_periods = parse_compute_periods(&env_or_default("GHA2DB_FORCE_PERIODS", "".to_string()));
+
+        // Random recalculations
+        let mut recalc_reciprocal = 24;
+        if !env_is_empty("GHA2DB_RECALC_RECIPROCAL") {
+            let rr = env_number::<i8>("GHA2DB_RECALC_RECIPROCAL");
+            if rr > 0 {
+                recalc_reciprocal = rr;
+            }
+        }
+
+        // Sync tool steps
+        let skip_pdb = !env_is_empty("GHA2DB_SKIPPDB");
+        let skip_ghapi = !env_is_empty("GHA2DB_GHAPISKIP");
+        let skip_get_repos = !env_is_empty("GHA2DB_GETREPOSSKIP");
+        let skip_tags = !env_is_empty("GHA2DB_SKIP_TAGS");
+        let skip_annotations = !env_is_empty("GHA2DB_SKIP_ANNOTATIONS");
+        let skip_columns = !env_is_empty("GHA2DB_SKIP_COLUMNS");
+        let run_columns = !env_is_empty("GHA2DB_RUN_COLUMNS");
+        let skip_vars = !env_is_empty("GHA2DB_SKIP_VARS");
+
+        // Metrics to calculate or skip (by "sql" name) and task order
+        let metrics_set = |name: &str| -> HashMap<String, bool> {
+            env_or_default(name, "".to_string())
+                .split(',')
+                .map(|m| m.trim())
+                .filter(|m| !m.is_empty())
+                .map(|m| (m.to_string(), true))
+                .collect()
+        };
+        let only_metrics = metrics_set("GHA2DB_ONLY_METRICS");
+        let skip_metrics = metrics_set("GHA2DB_SKIP_METRICS");
+        let skip_rand = !env_is_empty("GHA2DB_SKIP_RAND");
+
+        // Local binary/shell files mode
+        let local_cmd = !env_is_empty("GHA2DB_LOCAL_CMD");
+
+        // TS variables
+        let skip_tsdb = !env_is_empty("GHA2DB_SKIPTSDB");
+        let reset_tsdb = !env_is_empty("GHA2DB_RESETTSDB");
+
+        // Histograms
+        let reset_ranges = !env_is_empty("GHA2DB_RESETRANGES");
+        let mut max_histograms = 0;
+        if !env_is_empty("GHA2DB_MAX_HIST") {
+            let mh = env_number::<i8>("GHA2DB_MAX_HIST");
+            if mh > 0 {
+                max_histograms = mh;
+            }
+        }
+
+        // Exact repository full names to match
+        let exact = !env_is_empty("GHA2DB_EXACT");
+
+        // Exclude repos
+        let exclude_repos: HashMap<String, bool> = env_or_default("GHA2DB_EXCLUDE_REPOS", "".to_string())
+            .split(',')
+            .map(|r| r.trim())
+            .filter(|r| !r.is_empty())
+            .map(|r| (r.to_string(), true))
+            .collect();
+
+        // Allow broken JSON
+        let allow_broken_json = !env_is_empty("GHA2DB_ALLOW_BROKEN_JSON");
+        let mut max_broken_json_ratio = 0.01;
+        if !env_is_empty("GHA2DB_MAX_BROKEN_JSON_RATIO") {
+            let ratio = env_number::<f64>("GHA2DB_MAX_BROKEN_JSON_RATIO");
+            if ratio >= 0.0 {
+                max_broken_json_ratio = ratio;
+            }
+        }
+
+        // Skip dates
+        let skip_dates_yaml = env_or_default("GHA2DB_SKIP_DATES_YAML", "skip_dates.yaml".to_string());
+
+        // Bots classification, patterns are read on first use
+        let bots_yaml = env_or_default("GHA2DB_BOTS_YAML", "bots.yaml".to_string());
+
+        // Actors filtering
+        let actors_filter = !env_is_empty("GHA2DB_ACTORS_FILTER");
+        let mut actors_allow = None;
+        let mut actors_forbid = None;
+        if actors_filter {
+            actors_allow = env_regex("GHA2DB_ACTORS_ALLOW");
+            actors_forbid = env_regex("GHA2DB_ACTORS_FORBID");
+        }
+
+        // HTTP timeout (minutes) and retries
+        let mut http_timeout = 3;
+        if !env_is_empty("GHA2DB_HTTP_TIMEOUT") {
+            http_timeout = env_number::<i16>("GHA2DB_HTTP_TIMEOUT");
+        }
+        let mut http_retry = 5;
+        if !env_is_empty("GHA2DB_HTTP_RETRY") {
+            http_retry = env_number::<i8>("GHA2DB_HTTP_RETRY");
+        }
+
+        // Environment controlling index creation, table & tools
+        let index = !env_is_empty("GHA2DB_INDEX");
+        let table = env_is_empty("GHA2DB_SKIPTABLE");
+        let tools = env_is_empty("GHA2DB_SKIPTOOLS");
 
         // Postgres DB
         let mut pg_host = env_or_default("PG_HOST", "".to_string());
@@ -257,54 +505,107 @@ impl Default for Ctx {
         let mut pg_pass = env_or_default("PG_PASS", "".to_string());
         let mut pg_ssl = env_or_default("PG_SSL", "".to_string());
 
-        if pg_host == "" {
+        if pg_host.is_empty() {
             pg_host = DEFAULT_PG_HOST.to_string();
         }
-        if pg_port == "" {
+        if pg_port.is_empty() {
             pg_port = DEFAULT_PG_PORT.to_string();
         }
-        if pg_db == "" {
+        if pg_db.is_empty() {
             pg_db = DEFAULT_PG_DB.to_string();
         }
-        if pg_user == "" {
+        if pg_user.is_empty() {
             pg_user = DEFAULT_PG_USER.to_string();
         }
-        if pg_pass == "" {
+        if pg_pass.is_empty() {
             pg_pass = DEFAULT_PG_PASS.to_string();
         }
-        if pg_ssl == "" {
+        if pg_ssl.is_empty() {
             pg_ssl = DEFAULT_PG_SSL.to_string();
         }
+        // Connections are made without a TLS connector, modes requiring TLS could never connect
+        fatal_no_log(&pg_ssl_mode(&pg_ssl));
 
         Ctx {
             st: false,
             ncpus: 0,
-            exec_fatal: exec_fatal,
-            exec_quiet: exec_quiet,
-            exec_output: exec_output,
-            can_reconnect: can_reconnect,
-            rand_compute_at_this_date: rand_compute_at_this_date,
-            allow_rand_tags_cols_compute: allow_rand_tags_cols_compute,
-            commits_files_stats_enabled: commits_files_stats_enabled,
-            commits_loc_stats_enabled: commits_loc_stats_enabled,
-            data_dir: data_dir,
-            json_out: json_out,
-            db_out: db_out,
-            dry_run: dry_run,
-            min_ghapi_points: min_ghapi_points,
-            max_ghapi_wait_seconds: max_ghapi_wait_seconds,
-            max_ghapi_retry: max_ghapi_retry,
-            debug: debug,
-            cmd_debug: cmd_debug,
-            github_debug: github_debug,
-            q_out: q_out,
-            ctx_out: ctx_out,
-            pg_host: pg_host,
-            pg_port: pg_port,
-            pg_db: pg_db,
-            pg_user: pg_user,
-            pg_pass: pg_pass,
-            pg_ssl: pg_ssl,
+            exec_fatal,
+            exec_quiet,
+            exec_output,
+            can_reconnect,
+            rand_compute_at_this_date,
+            allow_rand_tags_cols_compute,
+            commits_files_stats_enabled,
+            commits_loc_stats_enabled,
+            data_dir,
+            json_out,
+            json_dir,
+            json_ndjson,
+            db_out,
+            dry_run,
+            dry_run_file,
+            min_ghapi_points,
+            max_ghapi_wait_seconds,
+            max_ghapi_retry,
+            debug,
+            cmd_debug,
+            github_debug,
+            q_out,
+            ctx_out,
+            slow_query,
+            pg_host,
+            pg_port,
+            pg_db,
+            pg_user,
+            pg_pass,
+            pg_ssl,
+            bulk_size,
+            index,
+            table,
+            tools,
+            import_memory,
+            old_format,
+            gha_dir,
+            gha_url,
+            gha_cache,
+            http_timeout,
+            http_retry,
+            default_start_date,
+            force_start_date,
+            project,
+            mm_offset,
+            compute_all,
+            compute_periods,
+            recalc_reciprocal,
+            skip_pdb,
+            skip_ghapi,
+            skip_get_repos,
+            skip_tags,
+            skip_annotations,
+            skip_columns,
+            run_columns,
+            skip_vars,
+            only_metrics,
+            skip_metrics,
+            skip_rand,
+            local_cmd,
+            skip_tsdb,
+            reset_tsdb,
+            reset_ranges,
+            max_histograms,
+            metrics_yaml,
+            project_scale,
+            exact,
+            exclude_repos,
+            allow_broken_json,
+            max_broken_json_ratio,
+            skip_dates_yaml,
+            bots_yaml,
+            bots: OnceLock::new(),
+            actors_filter,
+            actors_allow,
+            actors_forbid,
+            tool,
         }
         /*
         // PID file
@@ -313,10 +614,7 @@ impl Default for Ctx {
             ctx.PidFileRoot = Devstats
         }
 
-        // Environment controlling index creation, table & tools
-        ctx.Index = os.Getenv("GHA2DB_INDEX") != ""
-        ctx.Table = os.Getenv("GHA2DB_SKIPTABLE") == ""
-        ctx.Tools = os.Getenv("GHA2DB_SKIPTOOLS") == ""
+        // Mgetc
         ctx.Mgetc = os.Getenv("GHA2DB_MGETC")
         if len(ctx.Mgetc) > 1 {
             ctx.Mgetc = ctx.Mgetc[:1]
@@ -325,29 +623,7 @@ impl Default for Ctx {
         // Log Time
         ctx.LogTime = os.Getenv("GHA2DB_SKIPTIME") == ""
 
-        // Time offset for gha2db_sync
-        if os.Getenv("GHA2DB_TMOFFSET") == "" {
-            ctx.TmOffset = 0
-        } else {
-            off, err := strconv.Atoi(os.Getenv("GHA2DB_TMOFFSET"))
-            FatalNoLog(err)
-            ctx.TmOffset = off
-        }
-
-        // Default start date
-        if os.Getenv("GHA2DB_STARTDT") != "" {
-            ctx.DefaultStartDate = TimeParseAny(os.Getenv("GHA2DB_STARTDT"))
-        } else {
-            ctx.DefaultStartDate = time.Date(2012, 7, 1, 0, 0, 0, 0, time.UTC)
-        }
-        ctx.ForceStartDate = false
-        if os.Getenv("GHA2DB_STARTDT_FORCE") != "" {
-            ctx.ForceStartDate = true
-        }
-
         // Skip ghapi2db and/or get_repos
-        ctx.SkipGetRepos = os.Getenv("GHA2DB_GETREPOSSKIP") != ""
-        ctx.SkipGHAPI = os.Getenv("GHA2DB_GHAPISKIP") != ""
         ctx.SkipAPIEvents = os.Getenv("GHA2DB_GHAPISKIPEVENTS") != ""
         ctx.SkipAPICommits = os.Getenv("GHA2DB_GHAPISKIPCOMMITS") != ""
         ctx.SkipAPILicenses = os.Getenv("GHA2DB_GHAPISKIPLICENSES") != ""
@@ -363,63 +639,30 @@ impl Default for Ctx {
             ctx.LastSeries = "events_h"
         }
 
-        // Skip some tools
-        ctx.SkipTags = os.Getenv("GHA2DB_SKIP_TAGS") != ""
-        ctx.SkipAnnotations = os.Getenv("GHA2DB_SKIP_ANNOTATIONS") != ""
-        ctx.SkipColumns = os.Getenv("GHA2DB_SKIP_COLUMNS") != ""
-        ctx.RunColumns = os.Getenv("GHA2DB_RUN_COLUMNS") != ""
-        ctx.SkipVars = os.Getenv("GHA2DB_SKIP_VARS") != ""
-
-        // Skip randomizing task order
-        ctx.SkipRand = os.Getenv("GHA2DB_SKIP_RAND") != ""
-
-        // TS variables
-        ctx.SkipTSDB = os.Getenv("GHA2DB_SKIPTSDB") != ""
-        ctx.ResetTSDB = os.Getenv("GHA2DB_RESETTSDB") != ""
-        ctx.ResetRanges = os.Getenv("GHA2DB_RESETRANGES") != ""
-
-        // Allow broken JSON
-        ctx.AllowBrokenJSON = os.Getenv("GHA2DB_ALLOW_BROKEN_JSON") != ""
-
         // Run website_data tool after sync
         ctx.WebsiteData = os.Getenv("GHA2DB_WEBSITEDATA") != ""
 
         // Disable delete & recreate past events
         ctx.SkipUpdateEvents = os.Getenv("GHA2DB_SKIP_UPDATE_EVENTS") != ""
 
-        // Postgres DB variables
-        ctx.SkipPDB = os.Getenv("GHA2DB_SKIPPDB") != ""
-
         // Explain
         ctx.Explain = os.Getenv("GHA2DB_EXPLAIN") != ""
 
-        // Old (pre 2015) GHA JSONs format
-        ctx.OldFormat = os.Getenv("GHA2DB_OLDFMT") != ""
-
-        // Exact repository full names to match
-        ctx.Exact = os.Getenv("GHA2DB_EXACT") != ""
-
         // Log to Postgres DB, table `devstats`.`gha_logs`
         ctx.LogToDB = os.Getenv("GHA2DB_SKIPLOG") == ""
 
         // Local data files mode
         ctx.Local = os.Getenv("GHA2DB_LOCAL") != ""
 
-        // Local binary/shell files mode
-        ctx.LocalCmd = os.Getenv("GHA2DB_LOCAL_CMD") != ""
-
         // Absolute data files mode
         ctx.Absolute = os.Getenv("GHA2DB_ABSOLUTE") != ""
 
-        // Project
-        ctx.Project = os.Getenv("GHA2DB_PROJECT")
         proj := ""
         if ctx.Project != "" {
             proj = ctx.Project + "/"
         }
 
         // YAML config files
-        ctx.MetricsYaml = os.Getenv("GHA2DB_METRICS_YAML")
         ctx.TagsYaml = os.Getenv("GHA2DB_TAGS_YAML")
         ctx.ColumnsYaml = os.Getenv("GHA2DB_COLUMNS_YAML")
         ctx.VarsYaml = os.Getenv("GHA2DB_VARS_YAML")
@@ -427,9 +670,6 @@ impl Default for Ctx {
         if ctx.VarsFnYaml == "" {
             ctx.VarsFnYaml = "vars.yaml"
         }
-        if ctx.MetricsYaml == "" {
-            ctx.MetricsYaml = "metrics/" + proj + "metrics.yaml"
-        }
         if ctx.TagsYaml == "" {
             ctx.TagsYaml = "metrics/" + proj + "tags.yaml"
         }
@@ -545,20 +785,8 @@ impl Default for Ctx {
             }
         }
 
-        // Exclude repos
-        excludes := os.Getenv("GHA2DB_EXCLUDE_REPOS")
-        ctx.ExcludeRepos = make(map[string]bool)
-        if excludes != "" {
-            excludeArray := strings.Split(excludes, ",")
-            for _, exclude := range excludeArray {
-                if exclude != "" {
-                    ctx.ExcludeRepos[exclude] = true
-                }
-            }
-        }
-
         // Exclude vars
-        excludes = os.Getenv("GHA2DB_EXCLUDE_VARS")
+        excludes := os.Getenv("GHA2DB_EXCLUDE_VARS")
         ctx.ExcludeVars = make(map[string]bool)
         if excludes != "" {
             excludeArray := strings.Split(excludes, ",")
@@ -581,30 +809,6 @@ impl Default for Ctx {
             }
         }
 
-        // Only metrics
-        onlyMetrics := os.Getenv("GHA2DB_ONLY_METRICS")
-        ctx.OnlyMetrics = make(map[string]bool)
-        if onlyMetrics != "" {
-            ary := strings.Split(onlyMetrics, ",")
-            for _, metric := range ary {
-                if metric != "" {
-                    ctx.OnlyMetrics[metric] = true
-                }
-            }
-        }
-
-        // Exclude metrics
-        excludes = os.Getenv("GHA2DB_SKIP_METRICS")
-        ctx.SkipMetrics = make(map[string]bool)
-        if excludes != "" {
-            excludeArray := strings.Split(excludes, ",")
-            for _, exclude := range excludeArray {
-                if exclude != "" {
-                    ctx.SkipMetrics[exclude] = true
-                }
-            }
-        }
-
         // WebHook Host, Port, Root
         ctx.WebHookHost = os.Getenv("GHA2DB_WHHOST")
         if ctx.WebHookHost == "" {
@@ -631,12 +835,6 @@ impl Default for Ctx {
             ctx.TestsYaml = "tests.yaml"
         }
 
-        // Skip dates
-        ctx.SkipDatesYaml = os.Getenv("GHA2DB_SKIP_DATES_YAML")
-        if ctx.SkipDatesYaml == "" {
-            ctx.SkipDatesYaml = "skip_dates.yaml"
-        }
-
         // Main projects file
         ctx.ProjectsYaml = os.Getenv("GHA2DB_PROJECTS_YAML")
         if ctx.ProjectsYaml == "" {
@@ -689,23 +887,6 @@ impl Default for Ctx {
             ctx.JSONsDir += "/"
         }
 
-        // HTTP Timeout
-        if os.Getenv("GHA2DB_HTTP_TIMEOUT") == "" {
-            ctx.HTTPTimeout = 3
-        } else {
-            size, err := strconv.Atoi(os.Getenv("GHA2DB_HTTP_TIMEOUT"))
-            FatalNoLog(err)
-            ctx.HTTPTimeout = size
-        }
-        // HTTP RETRY
-        if os.Getenv("GHA2DB_HTTP_RETRY") == "" {
-            ctx.HTTPRetry = 5
-        } else {
-            retry, err := strconv.Atoi(os.Getenv("GHA2DB_HTTP_RETRY"))
-            FatalNoLog(err)
-            ctx.HTTPRetry = retry
-        }
-
         // Skip writing to shared_db from projects.yaml
         ctx.SkipSharedDB = os.Getenv("GHA2DB_SKIP_SHAREDDB") != ""
 
@@ -738,38 +919,6 @@ impl Default for Ctx {
         ctx.CheckImportedSHA = os.Getenv("GHA2DB_CHECK_IMPORTED_SHA") != ""
         ctx.OnlyCheckImportedSHA = os.Getenv("GHA2DB_ONLY_CHECK_IMPORTED_SHA") != ""
 
-        // Calculate all periods?
-        ctx.ComputeAll = os.Getenv("GHA2DB_COMPUTE_ALL") != ""
-
-        // Forece compute periods
-        periods := os.Getenv("GHA2DB_FORCE_PERIODS")
-        if periods != "" {
-            ary := strings.Split(periods, ",")
-            for _, data := range ary {
-                ary2 := strings.Split(data, ":")
-                if len(ary2) != 2 {
-                    continue
-                }
-                period := ary2[0]
-                shist := strings.TrimSpace(ary2[1])
-                if shist != "t" && shist != "f" {
-                    continue
-                }
-                hist := false
-                if shist == "t" {
-                    hist = true
-                }
-                if ctx.ComputePeriods == nil {
-                    ctx.ComputePeriods = make(map[string]map[bool]struct{})
-                }
-                _, ok := ctx.ComputePeriods[period]
-                if !ok {
-                    ctx.ComputePeriods[period] = make(map[bool]struct{})
-                }
-                ctx.ComputePeriods[period][hist] = struct{}{}
-            }
-        }
-
         // Max run durations
         // MaxRunDuration map[string][2]int // From GHA2DB_MAX_RUN_DURATION "tags:1h:0,calc_metric:12h:1"
         data := os.Getenv("GHA2DB_MAX_RUN_DURATION")
@@ -801,18 +950,6 @@ impl Default for Ctx {
         }
 
         // Actor filtering?
-        ctx.ActorsFilter = os.Getenv("GHA2DB_ACTORS_FILTER") != ""
-        if ctx.ActorsFilter {
-            actorsAllow := os.Getenv("GHA2DB_ACTORS_ALLOW")
-            if actorsAllow != "" {
-                ctx.ActorsAllow = regexp.MustCompile(actorsAllow)
-            }
-            actorsForbid := os.Getenv("GHA2DB_ACTORS_FORBID")
-            if actorsForbid != "" {
-                ctx.ActorsForbid = regexp.MustCompile(actorsForbid)
-            }
-        }
-
         // `merge_dbs` tool - input DBs and output DB
         dbs := os.Getenv("GHA2DB_INPUT_DBS")
         if dbs != "" {
@@ -836,40 +973,9 @@ impl Default for Ctx {
         // Enable drop metrics support
         ctx.RefreshCommitRoles = os.Getenv("GHA2DB_REFRESH_COMMIT_ROLES") != ""
 
-        // Project Scale
-        if os.Getenv("GHA2DB_PROJECT_SCALE") == "" {
-            ctx.ProjectScale = 1.0
-        } else {
-            projectScale, err := strconv.ParseFloat(os.Getenv("GHA2DB_PROJECT_SCALE"), 64)
-            FatalNoLog(err)
-            ctx.ProjectScale = projectScale
-        }
-
         // CSV file
         ctx.CSVFile = os.Getenv("GHA2DB_CSVOUT")
 
-        // RecalcReciprocal
-        if os.Getenv("GHA2DB_RECALC_RECIPROCAL") == "" {
-            ctx.RecalcReciprocal = 24
-        } else {
-            rr, err := strconv.Atoi(os.Getenv("GHA2DB_RECALC_RECIPROCAL"))
-            FatalNoLog(err)
-            if rr > 0 {
-                ctx.RecalcReciprocal = rr
-            } else {
-                ctx.RecalcReciprocal = 24
-            }
-        }
-
-        // MaxHistograms
-        if os.Getenv("GHA2DB_MAX_HIST") != "" {
-            mh, err := strconv.Atoi(os.Getenv("GHA2DB_MAX_HIST"))
-            FatalNoLog(err)
-            if mh > 0 {
-                ctx.MaxHistograms = mh
-            }
-        }
-
         // Context out if requested
         if ctx.CtxOut {
             ctx.Print()
@@ -877,3 +983,44 @@ impl Default for Ctx {
         */
     }
 }
+
+#[cfg(test)]
+mod context_tests {
+    use super::*;
+
+    #[test]
+    fn test_threads_num() {
+        let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+        for (st, ncpus, threads) in [
+            (false, 0, cpus),
+            (true, 0, 1),
+            (false, 1, 1),
+            (false, 6, 6),
+            (true, 6, 6),
+        ] {
+            let mut ctx = Ctx {
+                st: false,
+                ncpus: 0,
+                ..Default::default()
+            };
+            ctx.set_cpus_from(st, ncpus);
+            assert_eq!(ctx.threads_num(), threads, "st {st} ncpus {ncpus}");
+            assert_eq!(ctx.st, st || ncpus == 1, "st {st} ncpus {ncpus}");
+            // Pool is sized from the same settings, connections are opened lazily
+            assert_eq!(PgPool::new(&ctx).size(), threads, "st {st} ncpus {ncpus}");
+        }
+    }
+
+    #[test]
+    fn test_pg_ssl_mode() {
+        assert_eq!(pg_ssl_mode("disable"), Ok(postgres::config::SslMode::Disable));
+        assert_eq!(pg_ssl_mode("allow"), Ok(postgres::config::SslMode::Prefer));
+        assert_eq!(pg_ssl_mode("prefer"), Ok(postgres::config::SslMode::Prefer));
+        for ssl in ["require", "verify-ca", "verify-full"] {
+            assert!(pg_ssl_mode(ssl).unwrap_err().contains("not supported"), "{ssl}");
+        }
+        for ssl in ["", "on", "Disable", "true"] {
+            assert!(pg_ssl_mode(ssl).unwrap_err().contains("unknown PG_SSL"), "{ssl}");
+        }
+    }
+}
@@ -1,8 +1,5 @@
-fn env_is_set(var_name: &str) -> bool {
-    match env::var(var_name) {
-        Ok(_) => true,
-        _ => false,
-    }
+pub fn env_is_set(var_name: &str) -> bool {
+    env::var(var_name).is_ok()
 }
 
 fn env_is_empty(var_name: &str) -> bool {
@@ -37,3 +34,13 @@ where
         }
     }
 }
+
+// Compiles regexp from env variable, None if not set, fatal if invalid
+fn env_regex(var_name: &str) -> Option<Regex> {
+    if env_is_empty(var_name) {
+        return None;
+    }
+    let re = Regex::new(&env::var(var_name).unwrap_or_default());
+    fatal_no_log(&re);
+    re.ok()
+}
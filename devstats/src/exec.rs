@@ -0,0 +1,44 @@
+// Runs another devstats tool: command is prefixed with "./" when GHA2DB_LOCAL_CMD is set, env entries are added to the current environment
+// Output is captured: printed with GHA2DB_CMDDEBUG > 1 or on failure (unless exec_quiet), stdout is returned when exec_output is set
+// Failure exits the process when exec_fatal is set (default), otherwise it is returned as an error
+pub fn exec_command(ctx: &Ctx, cmd_and_args: &[&str], env: &HashMap<String, String>) -> Result<String, String> {
+    let Some((cmd, args)) = cmd_and_args.split_first() else {
+        return Err("exec_command: no command given".to_string());
+    };
+    let cmd = if ctx.local_cmd { format!("./{cmd}") } else { cmd.to_string() };
+    let cmd_line = std::iter::once(cmd.as_str())
+        .chain(args.iter().copied())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if ctx.cmd_debug > 0 {
+        printf(&format!("exec: {cmd_line}"));
+    }
+    if ctx.cmd_debug > 2 && !env.is_empty() {
+        let mut vars: Vec<String> = env.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        vars.sort();
+        printf(&format!("exec env: {}", vars.join(" ")));
+    }
+    let res = std::process::Command::new(&cmd).args(args).envs(env).output();
+    let err = match &res {
+        Ok(out) if out.status.success() => None,
+        Ok(out) => Some(format!("{cmd_line}: {}", out.status)),
+        Err(e) => Some(format!("{cmd_line}: {e}")),
+    };
+    if let Ok(out) = &res {
+        if ctx.cmd_debug > 1 || (err.is_some() && !ctx.exec_quiet) {
+            print!("{}", String::from_utf8_lossy(&out.stdout));
+            eprint!("{}", String::from_utf8_lossy(&out.stderr));
+        }
+    }
+    if let Some(err) = err {
+        if ctx.exec_fatal {
+            printf(&format!("error: {err}"));
+            std::process::exit(1);
+        }
+        return Err(err);
+    }
+    match res {
+        Ok(out) if ctx.exec_output => Ok(String::from_utf8_lossy(&out.stdout).to_string()),
+        _ => Ok(String::new()),
+    }
+}
@@ -0,0 +1,975 @@
+// GHA (GitHub Archive) event types imported by gha2db
+pub const GHA_EVENT_TYPES: &[&str] = &[
+    "CommitCommentEvent",
+    "CreateEvent",
+    "DeleteEvent",
+    "ForkEvent",
+    "GollumEvent",
+    "IssueCommentEvent",
+    "IssuesEvent",
+    "MemberEvent",
+    "PublicEvent",
+    "PullRequestEvent",
+    "PullRequestReviewEvent",
+    "PullRequestReviewCommentEvent",
+    "PushEvent",
+    "ReleaseEvent",
+    "TeamAddEvent",
+    "WatchEvent",
+];
+
+// GHA dates are RFC3339 strings, but some (mostly repository dates in PushEvents) are Unix timestamps
+fn gha_time_from_value(v: serde_json::Value) -> Result<Option<DateTime<Utc>>, String> {
+    match v {
+        serde_json::Value::Null => Ok(None),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(ts) => Ok(DateTime::from_timestamp(ts, 0)),
+            None => Err(format!("invalid timestamp: {n}")),
+        },
+        serde_json::Value::String(s) => parse_gha_time(&s).map(Some),
+        v => Err(format!("invalid date: {v}")),
+    }
+}
+
+// Parses GHA date, accepts RFC3339 and "YYYY-MM-DD HH:MI:SS +ZZZZ" / "YYYY/MM/DD HH:MI:SS +ZZZZ" used in old JSONs
+pub fn parse_gha_time(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    for fmt in ["%Y-%m-%d %H:%M:%S %z", "%Y/%m/%d %H:%M:%S %z"] {
+        if let Ok(dt) = DateTime::parse_from_str(s, fmt) {
+            return Ok(dt.with_timezone(&Utc));
+        }
+    }
+    Err(format!("cannot parse date: {s:?}"))
+}
+
+fn de_gha_time<'de, D: Deserializer<'de>>(d: D) -> Result<DateTime<Utc>, D::Error> {
+    let v = serde_json::Value::deserialize(d)?;
+    match gha_time_from_value(v) {
+        Ok(Some(dt)) => Ok(dt),
+        Ok(None) => Err(serde::de::Error::custom("missing date")),
+        Err(e) => Err(serde::de::Error::custom(e)),
+    }
+}
+
+fn de_gha_time_opt<'de, D: Deserializer<'de>>(d: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+    let v = serde_json::Value::deserialize(d)?;
+    gha_time_from_value(v).map_err(serde::de::Error::custom)
+}
+
+// Event - full GHA event structure (2015+ format)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub public: bool,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub created_at: DateTime<Utc>,
+    pub actor: Actor,
+    pub repo: Repo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<Org>,
+    #[serde(default)]
+    pub payload: Payload,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Actor {
+    pub id: i64,
+    pub login: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Repo {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Org {
+    pub id: i64,
+    pub login: String,
+}
+
+// Payload - union of all event types payloads, only fields used by given event type are set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Payload {
+    // PushEvent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distinct_size: Option<i32>,
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commits: Option<Vec<Commit>>,
+    // Most events with a subject (opened, closed, created, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    // CreateEvent, DeleteEvent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ref_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub master_branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pusher_type: Option<String>,
+    // PullRequestEvent, IssuesEvent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issue: Option<Issue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pull_request: Option<PullRequest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<Label>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<Actor>,
+    // IssueCommentEvent, CommitCommentEvent, PullRequestReviewCommentEvent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<Comment>,
+    // PullRequestReviewEvent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub review: Option<Review>,
+    // ForkEvent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forkee: Option<Forkee>,
+    // ReleaseEvent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release: Option<Release>,
+    // MemberEvent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member: Option<Actor>,
+    // GollumEvent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pages: Option<Vec<Page>>,
+    // TeamAddEvent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team: Option<Team>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<Forkee>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Commit {
+    pub sha: String,
+    pub author: CommitAuthor,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default = "default_true")]
+    pub distinct: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommitAuthor {
+    pub name: String,
+    pub email: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Issue {
+    pub id: i64,
+    pub number: i32,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub comments: i32,
+    pub user: Actor,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<Actor>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assignees: Vec<Actor>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<Label>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<Milestone>,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub created_at: DateTime<Utc>,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub updated_at: DateTime<Utc>,
+    #[serde(default, deserialize_with = "de_gha_time_opt", skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<DateTime<Utc>>,
+    // Only present (with links) when the issue is a pull request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub name: String,
+    #[serde(default)]
+    pub color: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Milestone {
+    pub id: i64,
+    pub number: i32,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub open_issues: i32,
+    #[serde(default)]
+    pub closed_issues: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator: Option<Actor>,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub created_at: DateTime<Utc>,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub updated_at: DateTime<Utc>,
+    #[serde(default, deserialize_with = "de_gha_time_opt", skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "de_gha_time_opt", skip_serializing_if = "Option::is_none")]
+    pub due_on: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub id: i64,
+    pub number: i32,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked: Option<bool>,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    pub user: Actor,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<Actor>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assignees: Vec<Actor>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requested_reviewers: Vec<Actor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<Milestone>,
+    pub base: Branch,
+    pub head: Branch,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged_by: Option<Actor>,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub created_at: DateTime<Utc>,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub updated_at: DateTime<Utc>,
+    #[serde(default, deserialize_with = "de_gha_time_opt", skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "de_gha_time_opt", skip_serializing_if = "Option::is_none")]
+    pub merged_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_commit_sha: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mergeable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rebaseable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mergeable_state: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comments: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_comments: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintainer_can_modify: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commits: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub additions: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletions: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_files: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Branch {
+    pub sha: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<Actor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<Forkee>,
+    #[serde(default)]
+    pub label: String,
+    #[serde(rename = "ref", default)]
+    pub git_ref: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: i64,
+    #[serde(default)]
+    pub body: String,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub created_at: DateTime<Utc>,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub updated_at: DateTime<Utc>,
+    pub user: Actor,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_commit_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_hunk: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_position: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_request_review_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Review {
+    pub id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<Actor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_id: Option<String>,
+    #[serde(default, deserialize_with = "de_gha_time_opt", skip_serializing_if = "Option::is_none")]
+    pub submitted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Forkee {
+    pub id: i64,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub full_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Actor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub fork: bool,
+    #[serde(default, deserialize_with = "de_gha_time_opt", skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "de_gha_time_opt", skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "de_gha_time_opt", skip_serializing_if = "Option::is_none")]
+    pub pushed_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    #[serde(default)]
+    pub size: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub stargazers_count: i32,
+    #[serde(default)]
+    pub has_issues: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_projects: Option<bool>,
+    #[serde(default)]
+    pub has_downloads: bool,
+    #[serde(default)]
+    pub has_wiki: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_pages: Option<bool>,
+    #[serde(default)]
+    pub forks: i32,
+    #[serde(default)]
+    pub default_branch: String,
+    #[serde(default)]
+    pub open_issues: i32,
+    #[serde(default)]
+    pub watchers: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Release {
+    pub id: i64,
+    #[serde(default)]
+    pub tag_name: String,
+    #[serde(default)]
+    pub target_commitish: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+    pub author: Actor,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default, deserialize_with = "de_gha_time_opt", skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Asset {
+    pub id: i64,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub uploader: Actor,
+    #[serde(default)]
+    pub content_type: String,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub size: i64,
+    #[serde(default)]
+    pub download_count: i32,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub created_at: DateTime<Utc>,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page {
+    #[serde(default)]
+    pub page_name: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub action: String,
+    #[serde(default)]
+    pub sha: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Team {
+    pub id: i64,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub slug: String,
+    #[serde(default)]
+    pub permission: String,
+}
+
+// EventOld - pre 2015 GHA event ("timeline" format), it has no event ID and no actor/org IDs
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventOld {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    #[serde(default)]
+    pub public: bool,
+    #[serde(deserialize_with = "de_gha_time")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub actor: Option<String>,
+    #[serde(default)]
+    pub repository: Option<ForkeeOld>,
+    #[serde(default)]
+    pub payload: PayloadOld,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForkeeOld {
+    pub id: i64,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub owner: String,
+    #[serde(default)]
+    pub organization: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub fork: bool,
+    #[serde(default, deserialize_with = "de_gha_time_opt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "de_gha_time_opt")]
+    pub pushed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    #[serde(default)]
+    pub size: i32,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub stargazers: i32,
+    #[serde(default)]
+    pub has_issues: bool,
+    #[serde(default)]
+    pub has_downloads: bool,
+    #[serde(default)]
+    pub has_wiki: bool,
+    #[serde(default)]
+    pub forks: i32,
+    #[serde(default)]
+    pub master_branch: Option<String>,
+    #[serde(default)]
+    pub open_issues: i32,
+    #[serde(default)]
+    pub watchers: i32,
+    #[serde(default)]
+    pub private: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PayloadOld {
+    pub head: Option<String>,
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>,
+    pub size: Option<i32>,
+    // Array of [sha, author email, message, author name, distinct]
+    pub shas: Option<Vec<Vec<serde_json::Value>>>,
+    pub action: Option<String>,
+    pub ref_type: Option<String>,
+    pub master_branch: Option<String>,
+    pub description: Option<String>,
+    pub number: Option<i32>,
+    // IssuesEvent has issue ID in "issue" (an object in some JSONs), IssueCommentEvent has "issue_id" and "comment_id",
+    // CommitCommentEvent has "comment_id" and "commit"
+    pub issue: Option<serde_json::Value>,
+    pub issue_id: Option<i64>,
+    pub comment_id: Option<i64>,
+    pub commit: Option<String>,
+    pub pull_request: Option<PullRequest>,
+    pub comment: Option<Comment>,
+    pub release: Option<Release>,
+    pub pages: Option<Vec<Page>>,
+    pub team: Option<Team>,
+    // Member login (MemberEvent) and forkee (ForkEvent) are objects or plain values depending on date
+    pub member: Option<serde_json::Value>,
+    pub forkee: Option<serde_json::Value>,
+}
+
+// Stable 63 bit hash of strings, used to generate IDs missing in old GHA format
+pub fn hash_strings(strs: &[&str]) -> i64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for s in strs {
+        for b in s.bytes().chain(std::iter::once(0)) {
+            h ^= b as u64;
+            h = h.wrapping_mul(0x100000001b3);
+        }
+    }
+    (h >> 1) as i64
+}
+
+fn old_actor(login: &str) -> Actor {
+    Actor {
+        id: hash_strings(&[login]),
+        login: login.to_string(),
+    }
+}
+
+fn old_issue(id: i64, number: Option<i32>, action: Option<&str>, user: &Actor, dt: DateTime<Utc>) -> Issue {
+    Issue {
+        id,
+        number: number.unwrap_or(0),
+        title: "".to_string(),
+        body: None,
+        state: if action == Some("closed") { "closed" } else { "open" }.to_string(),
+        locked: false,
+        comments: 0,
+        user: user.clone(),
+        assignee: None,
+        assignees: vec![],
+        labels: vec![],
+        milestone: None,
+        created_at: dt,
+        updated_at: dt,
+        closed_at: if action == Some("closed") { Some(dt) } else { None },
+        pull_request: None,
+    }
+}
+
+fn old_comment(id: i64, commit_id: Option<String>, user: &Actor, dt: DateTime<Utc>) -> Comment {
+    Comment {
+        id,
+        body: "".to_string(),
+        created_at: dt,
+        updated_at: dt,
+        user: user.clone(),
+        commit_id,
+        original_commit_id: None,
+        diff_hunk: None,
+        position: None,
+        original_position: None,
+        path: None,
+        pull_request_review_id: None,
+        line: None,
+    }
+}
+
+impl EventOld {
+    // Converts old format event into the current one, IDs missing in old format are hashed from names
+    pub fn into_event(self, raw: &str) -> Event {
+        let login = self.actor.unwrap_or_default();
+        let actor = old_actor(&login);
+        let (repo, org, forkee) = match self.repository {
+            Some(r) => {
+                let full_name = format!("{}/{}", r.owner, r.name);
+                let org = r.organization.as_ref().map(|o| Org {
+                    id: hash_strings(&[o]),
+                    login: o.clone(),
+                });
+                let forkee = Forkee {
+                    id: r.id,
+                    name: r.name,
+                    full_name: full_name.clone(),
+                    owner: Some(old_actor(&r.owner)),
+                    description: r.description,
+                    fork: r.fork,
+                    created_at: r.created_at,
+                    updated_at: r.pushed_at.or(r.created_at),
+                    pushed_at: r.pushed_at,
+                    homepage: r.homepage,
+                    size: r.size,
+                    language: r.language,
+                    stargazers_count: r.stargazers,
+                    has_issues: r.has_issues,
+                    has_projects: None,
+                    has_downloads: r.has_downloads,
+                    has_wiki: r.has_wiki,
+                    has_pages: None,
+                    forks: r.forks,
+                    default_branch: r.master_branch.unwrap_or_else(|| "master".to_string()),
+                    open_issues: r.open_issues,
+                    watchers: r.watchers,
+                    public: Some(!r.private),
+                };
+                let repo = Repo {
+                    id: r.id,
+                    name: full_name,
+                };
+                (repo, org, Some(forkee))
+            }
+            None => (
+                Repo {
+                    id: 0,
+                    name: "".to_string(),
+                },
+                None,
+                None,
+            ),
+        };
+        let p = self.payload;
+        let commits = p.shas.map(|shas| {
+            shas.iter()
+                .map(|sha| {
+                    let field = |i: usize| sha.get(i).and_then(|v| v.as_str()).unwrap_or("").to_string();
+                    Commit {
+                        sha: field(0),
+                        author: CommitAuthor {
+                            email: field(1),
+                            name: field(3),
+                        },
+                        message: field(2),
+                        distinct: sha.get(4).and_then(|v| v.as_bool()).unwrap_or(true),
+                    }
+                })
+                .collect()
+        });
+        let member = p.member.and_then(|m| match m {
+            serde_json::Value::String(login) => Some(old_actor(&login)),
+            serde_json::Value::Object(ref o) => o
+                .get("login")
+                .and_then(|l| l.as_str())
+                .map(old_actor),
+            _ => None,
+        });
+        // Old format has only IDs of issues and comments, other fields are taken from the event
+        let (number, action, created_at) = (p.number, p.action.as_deref(), self.created_at);
+        let issue_from_id = |id: i64| old_issue(id, number, action, &actor, created_at);
+        let issue = match p.issue {
+            Some(v @ serde_json::Value::Object(_)) => serde_json::from_value::<Issue>(v).ok(),
+            Some(v) => v.as_i64().or(p.issue_id).map(issue_from_id),
+            None => p.issue_id.map(issue_from_id),
+        };
+        let comment = p.comment.or_else(|| {
+            p.comment_id
+                .map(|id| old_comment(id, p.commit, &actor, created_at))
+        });
+        let payload_forkee = match (self.event_type.as_str(), p.forkee) {
+            ("ForkEvent", Some(v @ serde_json::Value::Object(_))) => {
+                serde_json::from_value::<Forkee>(v).ok()
+            }
+            ("ForkEvent", _) => forkee,
+            _ => None,
+        };
+        let id = hash_strings(&[
+            &self.created_at.to_rfc3339(),
+            &self.event_type,
+            &login,
+            &repo.name,
+            raw,
+        ]);
+        Event {
+            id: id.to_string(),
+            event_type: self.event_type,
+            public: self.public,
+            created_at: self.created_at,
+            actor,
+            repo,
+            org,
+            payload: Payload {
+                size: p.size,
+                git_ref: p.git_ref,
+                head: p.head,
+                commits,
+                action: p.action,
+                ref_type: p.ref_type,
+                master_branch: p.master_branch,
+                description: p.description,
+                number: p.number,
+                issue,
+                pull_request: p.pull_request,
+                comment,
+                release: p.release,
+                member,
+                pages: p.pages,
+                team: p.team,
+                forkee: payload_forkee,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+// Parses a single GHA JSON, in pre 2015 format when GHA2DB_OLDFMT is set
+pub fn parse_event(ctx: &Ctx, json: &str) -> Result<Event, serde_json::Error> {
+    if ctx.old_format {
+        let ev: EventOld = serde_json::from_str(json)?;
+        return Ok(ev.into_event(json));
+    }
+    serde_json::from_str(json)
+}
+
+impl Event {
+    // Numeric event ID, GHA stores it as a string
+    pub fn id_num(&self) -> i64 {
+        string_to_num::<i64>(&self.id).unwrap_or_else(|_| hash_strings(&[&self.id]))
+    }
+}
+
+#[cfg(test)]
+mod gha_tests {
+    use super::*;
+
+    fn fixture(format: &str, event_type: &str) -> String {
+        let path = format!("{}/tests/fixtures/gha/{format}/{event_type}.json", env!("CARGO_MANIFEST_DIR"));
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{path}: {e}"))
+    }
+
+    fn parse(old_format: bool, json: &str) -> Event {
+        let ctx = Ctx {
+            old_format,
+            ..Default::default()
+        };
+        parse_event(&ctx, json).unwrap_or_else(|e| panic!("{e}: {json}"))
+    }
+
+    // Payload objects each event type carries, the same in both formats (old format only has IDs of issues and comments)
+    fn check_payload(event_type: &str, p: &Payload, old: bool) {
+        match event_type {
+            "CommitCommentEvent" => {
+                let c = p.comment.as_ref().expect("comment");
+                assert_eq!((c.id, c.commit_id.as_deref()), (9001, Some("a1b2c3d4")));
+            }
+            "CreateEvent" => assert_eq!(
+                (p.git_ref.as_deref(), p.ref_type.as_deref(), p.master_branch.as_deref()),
+                (Some("v1.0.0"), Some("tag"), Some("master"))
+            ),
+            "DeleteEvent" => assert_eq!(
+                (p.git_ref.as_deref(), p.ref_type.as_deref()),
+                (Some("fix-resize"), Some("branch"))
+            ),
+            "ForkEvent" => {
+                let f = p.forkee.as_ref().expect("forkee");
+                let expected = if old { (2001, "acme/widget") } else { (2002, "bob/widget") };
+                assert_eq!((f.id, f.full_name.as_str()), expected);
+            }
+            "GollumEvent" => {
+                let pages = p.pages.as_ref().expect("pages");
+                assert_eq!(
+                    (pages.len(), pages[0].page_name.as_str(), pages[0].action.as_str()),
+                    (1, "Home", "edited")
+                );
+            }
+            "IssueCommentEvent" => {
+                let i = p.issue.as_ref().expect("issue");
+                assert_eq!((i.id, i.number), (5001, if old { 0 } else { 42 }));
+                assert_eq!(p.comment.as_ref().expect("comment").id, 9002);
+            }
+            "IssuesEvent" => {
+                let i = p.issue.as_ref().expect("issue");
+                let state = if old { "closed" } else { "open" };
+                assert_eq!((i.id, i.number, i.state.as_str()), (5001, 42, state));
+            }
+            "MemberEvent" => {
+                let m = p.member.as_ref().expect("member");
+                let id = if old { hash_strings(&["bob"]) } else { 1002 };
+                assert_eq!((m.id, m.login.as_str()), (id, "bob"));
+            }
+            "PublicEvent" => assert_eq!(p, &Payload::default()),
+            "PullRequestEvent" => {
+                let pr = p.pull_request.as_ref().expect("pull_request");
+                assert_eq!((pr.id, pr.number, pr.head.sha.as_str()), (6001, 43, "a1b2c3d4"));
+                assert_eq!(p.number, Some(43));
+            }
+            "PullRequestReviewEvent" => {
+                assert_eq!(p.review.as_ref().expect("review").id, 9101);
+                assert_eq!(p.pull_request.as_ref().expect("pull_request").id, 6001);
+            }
+            "PullRequestReviewCommentEvent" => {
+                let c = p.comment.as_ref().expect("comment");
+                assert_eq!(
+                    (c.id, c.path.as_deref(), c.position),
+                    (9003, Some("widget.go"), Some(4))
+                );
+            }
+            "PushEvent" => {
+                let commits = p.commits.as_ref().expect("commits");
+                let c = &commits[0];
+                assert_eq!(
+                    (commits.len(), c.sha.as_str(), c.message.as_str()),
+                    (1, "a1b2c3d4", "Fix resize")
+                );
+                assert_eq!(
+                    (c.author.email.as_str(), c.author.name.as_str(), c.distinct),
+                    ("alice@example.com", "Alice", true)
+                );
+                assert_eq!(
+                    (p.size, p.git_ref.as_deref(), p.head.as_deref()),
+                    (Some(1), Some("refs/heads/master"), Some("a1b2c3d4"))
+                );
+            }
+            "ReleaseEvent" => {
+                let r = p.release.as_ref().expect("release");
+                assert_eq!(
+                    (r.id, r.tag_name.as_str(), r.author.login.as_str()),
+                    (7001, "v1.0.0", "alice")
+                );
+                assert_eq!(r.assets.len(), if old { 0 } else { 1 });
+            }
+            "TeamAddEvent" => {
+                let t = p.team.as_ref().expect("team");
+                assert_eq!((t.id, t.slug.as_str()), (9201, "maintainers"));
+            }
+            "WatchEvent" => assert_eq!(p.action.as_deref(), Some("started")),
+            _ => panic!("no payload check for {event_type}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_events() {
+        for (i, event_type) in GHA_EVENT_TYPES.iter().enumerate() {
+            let ev = parse(false, &fixture("new", event_type));
+            assert_eq!(ev.event_type, *event_type);
+            assert_eq!(ev.id_num(), 2489651045 + i as i64, "{event_type}");
+            assert_eq!((ev.actor.id, ev.actor.login.as_str()), (1001, "alice"));
+            assert_eq!((ev.repo.id, ev.repo.name.as_str()), (2001, "acme/widget"));
+            assert_eq!(ev.org.as_ref().map(|o| o.id), Some(3001));
+            assert_eq!(ev.created_at, parse_gha_time("2015-01-02T10:11:12Z").unwrap());
+            check_payload(event_type, &ev.payload, false);
+            // Normalised JSON output parses back to the same event
+            let json = serde_json::to_string(&ev).unwrap();
+            assert_eq!(parse(false, &json), ev, "{event_type}");
+        }
+    }
+
+    #[test]
+    fn test_parse_old_events() {
+        // PullRequestReviewEvent only exists since 2016, there is no pre 2015 JSON for it
+        for event_type in GHA_EVENT_TYPES.iter().filter(|t| **t != "PullRequestReviewEvent") {
+            let json = fixture("old", event_type);
+            let ev = parse(true, &json);
+            assert_eq!(ev.event_type, *event_type);
+            assert_eq!((ev.actor.id, ev.actor.login.as_str()), (hash_strings(&["alice"]), "alice"));
+            assert_eq!((ev.repo.id, ev.repo.name.as_str()), (2001, "acme/widget"));
+            assert_eq!(
+                ev.org.as_ref().map(|o| (o.id, o.login.as_str())),
+                Some((hash_strings(&["acme"]), "acme"))
+            );
+            assert_eq!(ev.created_at, parse_gha_time("2012-03-10T15:28:52Z").unwrap());
+            check_payload(event_type, &ev.payload, true);
+            // Hashed event ID is stable for the same JSON
+            assert_eq!(parse(true, &json).id, ev.id);
+        }
+    }
+
+    #[test]
+    fn test_into_event() {
+        let json = fixture("old", "IssueCommentEvent");
+        let old: EventOld = serde_json::from_str(&json).unwrap();
+        let ev = old.clone().into_event(&json);
+        // Missing issue and comment fields are taken from the event
+        let issue = ev.payload.issue.as_ref().unwrap();
+        assert_eq!((&issue.user, issue.created_at, issue.state.as_str()), (&ev.actor, ev.created_at, "open"));
+        let comment = ev.payload.comment.as_ref().unwrap();
+        assert_eq!((&comment.user, comment.created_at, comment.body.as_str()), (&ev.actor, ev.created_at, ""));
+        let id = hash_strings(&[&ev.created_at.to_rfc3339(), &ev.event_type, "alice", "acme/widget", &json]);
+        assert_eq!(ev.id, id.to_string());
+        // Different JSONs of the same actor, repo, type and time get different IDs
+        assert_ne!(old.into_event(&json.replace("9002", "9003")).id, ev.id);
+
+        // Repository becomes the forkee of ForkEvent when payload has only its ID
+        let json = fixture("old", "ForkEvent");
+        let ev = serde_json::from_str::<EventOld>(&json).unwrap().into_event(&json);
+        let f = ev.payload.forkee.unwrap();
+        assert_eq!(f.owner.map(|o| o.login), Some("acme".to_string()));
+        assert_eq!((f.default_branch.as_str(), f.public, f.stargazers_count), ("master", Some(true), 10));
+        assert_eq!(f.created_at, parse_gha_time("2011-05-01T17:00:00Z").ok());
+
+        // Issue given as an object, event without repository
+        let json = r#"{"type": "IssuesEvent", "created_at": "2013/05/01 10:00:00 -0700", "actor": "bob",
+            "payload": {"action": "closed", "issue": {"id": 7, "number": 3, "user": {"id": 1, "login": "x"},
+            "created_at": "2013-04-01T00:00:00Z", "updated_at": "2013-05-01T17:00:00Z"}}}"#;
+        let ev = serde_json::from_str::<EventOld>(json).unwrap().into_event(json);
+        assert_eq!((ev.repo.id, ev.repo.name.as_str()), (0, ""));
+        let issue = ev.payload.issue.unwrap();
+        assert_eq!((issue.id, issue.number, issue.user.login.as_str()), (7, 3, "x"));
+
+        // Closed issue given by ID only
+        let json = r#"{"type": "IssuesEvent", "created_at": "2013-05-01T10:00:00Z",
+            "payload": {"action": "closed", "issue": 7}}"#;
+        let ev = serde_json::from_str::<EventOld>(json).unwrap().into_event(json);
+        let issue = ev.payload.issue.unwrap();
+        assert_eq!((issue.number, issue.state.as_str()), (0, "closed"));
+        assert_eq!(issue.closed_at, Some(ev.created_at));
+    }
+}
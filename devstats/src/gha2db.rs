@@ -0,0 +1,2503 @@
+// gha2db statistics
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GhaStats {
+    pub files: u64,         // Processed hourly files
+    pub files_missing: u64, // Hourly files not found (not fatal)
+    pub skipped: u64,       // Hours listed in skip_dates.yaml
+    pub imported: u64,      // Hours already imported according to gha_import_hours checkpoints
+    pub events: u64,        // All events in processed files
+    pub filtered_repo: u64, // Events dropped by org/repo filter
+    pub filtered_actor: u64, // Events dropped by GHA2DB_ACTORS_ALLOW/GHA2DB_ACTORS_FORBID
+    pub written: u64,       // Events written to DB (or JSON output)
+    pub existing: u64,      // Events already present in DB
+    pub broken_json: u64,   // Unparsable JSONs skipped with GHA2DB_ALLOW_BROKEN_JSON
+}
+
+impl GhaStats {
+    pub fn add(&mut self, other: &GhaStats) {
+        self.files += other.files;
+        self.files_missing += other.files_missing;
+        self.skipped += other.skipped;
+        self.imported += other.imported;
+        self.events += other.events;
+        self.filtered_repo += other.filtered_repo;
+        self.filtered_actor += other.filtered_actor;
+        self.written += other.written;
+        self.existing += other.existing;
+        self.broken_json += other.broken_json;
+    }
+}
+
+// Org/repo filter from gha2db command line: comma separated lists of orgs and repos, empty means all
+// Orgs list entries containing "/" are exact repository full names, with GHA2DB_EXACT all of them are
+// Repositories from GHA2DB_EXCLUDE_REPOS are always dropped
+// IDs of matched (and excluded) repositories are resolved from gha_repos and from every imported hour before its events
+// are filtered (learn_hour), so renamed repositories are matched by their other names from that hour on
+// Events from before the first hour a matching name appears in are imported by catching up (gha_catch_up), excluded
+// names only apply from the first hour they appear in (or to IDs from gha_repos)
+pub struct GhaRepoFilter {
+    exact: bool,
+    orgs: HashSet<String>,
+    repos: HashSet<String>,
+    full_names: HashSet<String>,
+    exclude: HashSet<String>,
+    ids: RwLock<HashMap<i64, GhaRepoSeen>>,
+    exclude_ids: RwLock<HashMap<i64, DateTime<Utc>>>, // Excluded IDs with the first hour their name was excluded in
+    catch_up: bool, // Catching up filter: only `ids` match, their hours were imported already
+}
+
+// First hour a matching repository name was seen in (MIN_UTC for names from gha_repos),
+// and whether the repository was created in that hour
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GhaRepoSeen {
+    dt: DateTime<Utc>,
+    created: bool,
+}
+
+impl GhaRepoFilter {
+    pub fn new(ctx: &Ctx, orgs: &str, repos: &str) -> Self {
+        let set = |s: &str| -> HashSet<String> {
+            s.split(',')
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect()
+        };
+        let (full_names, orgs): (HashSet<String>, HashSet<String>) = set(orgs)
+            .into_iter()
+            .partition(|org| ctx.exact || org.contains('/'));
+        GhaRepoFilter {
+            exact: ctx.exact,
+            orgs,
+            repos: if ctx.exact { HashSet::new() } else { set(repos) },
+            full_names,
+            exclude: ctx.exclude_repos.keys().cloned().collect(),
+            ids: RwLock::new(HashMap::new()),
+            exclude_ids: RwLock::new(HashMap::new()),
+            catch_up: false,
+        }
+    }
+
+    // True when no org/repo restrictions were given (excludes still apply)
+    pub fn all(&self) -> bool {
+        !self.catch_up
+            && self.orgs.is_empty()
+            && self.repos.is_empty()
+            && self.full_names.is_empty()
+    }
+
+    // Checks repository full name "org/repo" against orgs/repos/full names lists
+    fn name_hit(&self, full_name: &str) -> bool {
+        if self.full_names.contains(full_name) {
+            return true;
+        }
+        if self.exact || (self.orgs.is_empty() && self.repos.is_empty()) {
+            return false;
+        }
+        let (org, repo) = match full_name.split_once('/') {
+            Some((org, repo)) => (org, repo),
+            None => ("", full_name),
+        };
+        if !self.orgs.is_empty() && !self.orgs.contains(org) {
+            return false;
+        }
+        if !self.repos.is_empty() && !self.repos.contains(repo) {
+            return false;
+        }
+        true
+    }
+
+    // Filter definition for import checkpoints (import_key): sorted orgs, repos, full names and excluded names,
+    // empty when nothing is filtered
+    fn definition(&self) -> Vec<String> {
+        let mut parts = vec![];
+        for (name, set) in [
+            ("orgs", &self.orgs),
+            ("repos", &self.repos),
+            ("full_names", &self.full_names),
+            ("exclude", &self.exclude),
+        ] {
+            if !set.is_empty() {
+                let mut values: Vec<&str> = set.iter().map(|v| v.as_str()).collect();
+                values.sort();
+                parts.push(format!("{name}:{}", values.join(",")));
+            }
+        }
+        parts
+    }
+
+    // True when repository IDs have to be resolved: some repositories are selected or excluded by name
+    fn needs_ids(&self) -> bool {
+        !self.catch_up && (!self.all() || !self.exclude.is_empty())
+    }
+
+    // Remembers repository ID first seen with a matching or excluded name in hour `dt`
+    // IDs are matched (or excluded) from that hour on: the result doesn't depend on the order hours are learned in
+    fn learn(&self, id: i64, name: &str, dt: DateTime<Utc>, created: bool) {
+        if self.exclude.contains(name) {
+            let mut exclude_ids = self.exclude_ids.write().unwrap();
+            let first = exclude_ids.entry(id).or_insert(dt);
+            *first = dt.min(*first);
+        } else if !self.all() && self.name_hit(name) {
+            let mut ids = self.ids.write().unwrap();
+            let first = ids.entry(id).or_insert(GhaRepoSeen { dt, created });
+            if dt < first.dt {
+                *first = GhaRepoSeen { dt, created };
+            } else if dt == first.dt {
+                first.created |= created;
+            }
+        }
+    }
+
+    fn report_ids(&self, ctx: &Ctx, source: &str) {
+        if ctx.debug > 0 {
+            printf(&format!(
+                "repository filter: {} matching and {} excluded repository IDs known after {source}",
+                self.ids.read().unwrap().len(),
+                self.exclude_ids.read().unwrap().len()
+            ));
+        }
+    }
+
+    // Resolves IDs of matching and excluded repositories from names they had in the past (gha_repos)
+    pub fn load_repo_ids(&self, client: &mut Client, ctx: &Ctx) -> Result<(), postgres::Error> {
+        if !self.needs_ids() {
+            return Ok(());
+        }
+        let rows = query_sql_with_err(client, ctx, "select distinct id, name from gha_repos", &[])?;
+        for row in rows {
+            self.learn(row.get(0), &row.get::<_, String>(1), DateTime::<Utc>::MIN_UTC, false);
+        }
+        self.report_ids(ctx, "reading gha_repos");
+        Ok(())
+    }
+
+    // Learns repository IDs from all events of an hourly file, called before the hour's events are filtered
+    // Broken JSONs are left for the import to report
+    fn learn_hour(
+        &self,
+        ctx: &Ctx,
+        dt: DateTime<Utc>,
+        reader: Box<dyn BufRead>,
+    ) -> Result<(), String> {
+        let mut repos = HashSet::new();
+        let mut created = HashSet::new();
+        gha_read_lines(dt, reader, |_, line| {
+            if let Some(repo) = gha_line_repo(ctx, &line) {
+                if repo.created {
+                    created.insert(repo.id);
+                }
+                repos.insert((repo.id, repo.name));
+            }
+            Ok(())
+        })?;
+        for (id, name) in repos {
+            self.learn(id, &name, dt, created.contains(&id));
+        }
+        Ok(())
+    }
+
+    // Matching repositories that may have events under other names before `from` hour: IDs first matched after it,
+    // not created in that hour, with the first hour they were matched in
+    fn renamed(&self, from: DateTime<Utc>) -> HashMap<i64, DateTime<Utc>> {
+        self.ids
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, seen)| seen.dt > from && !seen.created)
+            .map(|(id, seen)| (*id, seen.dt))
+            .collect()
+    }
+
+    // Filter matching only given IDs (and not excluded), used to import events of renamed repositories into
+    // already imported hours
+    fn catch_up_filter(&self, ids: &[i64]) -> GhaRepoFilter {
+        let seen = GhaRepoSeen {
+            dt: DateTime::<Utc>::MIN_UTC,
+            created: false,
+        };
+        GhaRepoFilter {
+            exact: true,
+            orgs: HashSet::new(),
+            repos: HashSet::new(),
+            full_names: HashSet::new(),
+            exclude: self.exclude.clone(),
+            ids: RwLock::new(ids.iter().map(|id| (*id, seen)).collect()),
+            exclude_ids: RwLock::new(self.exclude_ids.read().unwrap().clone()),
+            catch_up: true,
+        }
+    }
+
+    // Checks if repository of an event from hour `dt` passes the filter
+    pub fn hit(&self, id: i64, full_name: &str, dt: DateTime<Utc>) -> bool {
+        if full_name.is_empty() || self.exclude.contains(full_name) {
+            return false;
+        }
+        if self.exclude_ids.read().unwrap().get(&id).is_some_and(|first| *first <= dt) {
+            return false;
+        }
+        if self.all() || self.name_hit(full_name) {
+            return true;
+        }
+        self.ids.read().unwrap().get(&id).is_some_and(|seen| seen.dt <= dt)
+    }
+}
+
+// Repository of a GHA JSON line
+struct GhaLineRepo {
+    id: i64,
+    name: String,
+    created: bool, // CreateEvent of the repository itself
+}
+
+// Repository of a GHA JSON line without parsing the whole event: "repo" object, or "repository" in pre 2015 format
+fn gha_line_repo(ctx: &Ctx, line: &[u8]) -> Option<GhaLineRepo> {
+    #[derive(Deserialize)]
+    struct RepoOld {
+        id: i64,
+        #[serde(default)]
+        name: String,
+        #[serde(default)]
+        owner: String,
+    }
+    #[derive(Deserialize)]
+    struct Payload {
+        ref_type: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct Line {
+        #[serde(rename = "type", default)]
+        event_type: String,
+        payload: Option<Payload>,
+        repo: Option<Repo>,
+        repository: Option<RepoOld>,
+    }
+    let line: Line = serde_json::from_slice(line).ok()?;
+    let created = line.event_type == "CreateEvent"
+        && line.payload.and_then(|p| p.ref_type).as_deref() == Some("repository");
+    let (id, name) = if ctx.old_format {
+        line.repository.map(|r| (r.id, format!("{}/{}", r.owner, r.name)))
+    } else {
+        line.repo.map(|r| (r.id, r.name))
+    }?;
+    Some(GhaLineRepo { id, name, created })
+}
+
+// Checks actor login against GHA2DB_ACTORS_ALLOW and GHA2DB_ACTORS_FORBID (when GHA2DB_ACTORS_FILTER is set)
+pub fn actor_hit(ctx: &Ctx, login: &str) -> bool {
+    if !ctx.actors_filter {
+        return true;
+    }
+    if let Some(re) = &ctx.actors_allow {
+        if !re.is_match(login) {
+            return false;
+        }
+    }
+    if let Some(re) = &ctx.actors_forbid {
+        if re.is_match(login) {
+            return false;
+        }
+    }
+    true
+}
+
+// Hourly GHA file name: YYYY-MM-DD-H.json.gz, hour has no leading zero
+pub fn gha_file_name(dt: DateTime<Utc>) -> String {
+    format!("{}-{}.json.gz", dt.format("%Y-%m-%d"), dt.hour())
+}
+
+// Truncates date-time to the beginning of its hour
+pub fn hour_start(dt: DateTime<Utc>) -> DateTime<Utc> {
+    dt.with_minute(0)
+        .and_then(|dt| dt.with_second(0))
+        .and_then(|dt| dt.with_nanosecond(0))
+        .expect("cannot truncate date to hour")
+}
+
+// Parses gha2db date-hour arguments: "YYYY-MM-DD" and "HH"
+pub fn parse_gha_hour(date: &str, hour: &str) -> Result<DateTime<Utc>, String> {
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|e| format!("invalid date {date:?}: {e}"))?;
+    let h = string_to_num::<u32>(hour).map_err(|e| format!("invalid hour {hour:?}: {e}"))?;
+    day.and_hms_opt(h, 0, 0)
+        .map(|dt| dt.and_utc())
+        .ok_or_else(|| format!("invalid hour {hour:?}"))
+}
+
+// Normalised JSON output of a single hour (GHA2DB_JSON), written to GHA2DB_JSON_DIR:
+// one <unix time>_<event id>.json file per event or, with GHA2DB_JSON_NDJSON, one YYYY-MM-DD-H.ndjson file
+// NDJSON file is written under a temporary name and renamed when the hour is complete, when catching up on an
+// already imported hour (`append`) events are appended to its NDJSON file
+struct GhaJsonOut<'a> {
+    ctx: &'a Ctx,
+    dt: DateTime<Utc>,
+    ndjson: Option<(String, std::io::BufWriter<File>)>,
+    append: bool,
+}
+
+impl<'a> GhaJsonOut<'a> {
+    fn new(ctx: &'a Ctx, dt: DateTime<Utc>, append: bool) -> Result<Option<Self>, String> {
+        if !ctx.json_out {
+            return Ok(None);
+        }
+        let mut ndjson = None;
+        if ctx.json_ndjson {
+            let path = gha_ndjson_path(ctx, dt);
+            let (path, f) = if append {
+                let f = File::options().append(true).create(true).open(&path);
+                (path, f)
+            } else {
+                let tmp = format!("{path}.tmp");
+                let f = File::create(&tmp);
+                (tmp, f)
+            };
+            let f = f.map_err(|e| format!("{path}: {e}"))?;
+            ndjson = Some((path, std::io::BufWriter::new(f)));
+        }
+        Ok(Some(GhaJsonOut {
+            ctx,
+            dt,
+            ndjson,
+            append,
+        }))
+    }
+
+    // Writes normalised JSONs (event ID, JSON) in the given order
+    fn write(&mut self, jsons: &[(String, String)]) -> Result<(), String> {
+        for (id, json) in jsons {
+            match &mut self.ndjson {
+                Some((tmp, w)) => writeln!(w, "{json}").map_err(|e| format!("{tmp}: {e}"))?,
+                None => {
+                    let path = format!("{}{}_{id}.json", self.ctx.json_dir, self.dt.timestamp());
+                    std::fs::write(&path, json).map_err(|e| format!("{path}: {e}"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), String> {
+        if let Some((tmp, mut w)) = self.ndjson {
+            w.flush().map_err(|e| format!("{tmp}: {e}"))?;
+            if self.append {
+                return Ok(());
+            }
+            let path = gha_ndjson_path(self.ctx, self.dt);
+            std::fs::rename(&tmp, &path).map_err(|e| format!("{path}: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+fn gha_ndjson_path(ctx: &Ctx, dt: DateTime<Utc>) -> String {
+    format!("{}{}-{}.ndjson", ctx.json_dir, dt.format("%Y-%m-%d"), dt.hour())
+}
+
+// Results of processing JSON lines
+#[derive(Default)]
+struct GhaOutput {
+    stats: GhaStats,
+    broken: GhaLines,            // Broken lines to quarantine
+    jsons: Vec<(String, String)>, // Normalised JSONs of events passing filters: event ID, JSON
+    events: Vec<GhaEventRows>,    // Rows of events passing filters, not written to DB yet
+}
+
+// JSON lines with their numbers in the hourly file
+type GhaLines = Vec<(usize, Vec<u8>)>;
+
+// Processes a single JSON line (n-th in the hourly file), broken lines are collected when GHA2DB_ALLOW_BROKEN_JSON is set
+// Rows of events passing filters are collected in `out.events` when writing to DB, see gha_write_output
+fn gha_process_line(
+    ctx: &Ctx,
+    dt: DateTime<Utc>,
+    n: usize,
+    line: Vec<u8>,
+    filter: &GhaRepoFilter,
+    out: &mut GhaOutput,
+) -> Result<(), String> {
+    out.stats.events += 1;
+    // Catching up needs events of a few repositories, other lines are not parsed (nor reported as broken again)
+    if filter.catch_up
+        && !gha_line_repo(ctx, &line).is_some_and(|r| filter.hit(r.id, &r.name, dt))
+    {
+        out.stats.filtered_repo += 1;
+        return Ok(());
+    }
+    let parsed = String::from_utf8(line)
+        .map_err(|e| (e.to_string(), e.into_bytes()))
+        .and_then(|line| match parse_event(ctx, &line) {
+            Ok(ev) => Ok((ev, ())),
+            Err(e) => Err((e.to_string(), line.into_bytes())),
+        });
+    let ev = match parsed {
+        Ok((ev, _)) => ev,
+        Err((e, line)) => {
+            if !ctx.allow_broken_json {
+                return Err(format!("{}: JSON #{n}: {e}", gha_file_name(dt)));
+            }
+            printf(&format!("{}: broken JSON #{n}, skipping: {e}", gha_file_name(dt)));
+            out.stats.broken_json += 1;
+            out.broken.push((n, line));
+            return Ok(());
+        }
+    };
+    if !filter.hit(ev.repo.id, &ev.repo.name, dt) {
+        out.stats.filtered_repo += 1;
+        return Ok(());
+    }
+    if !actor_hit(ctx, &ev.actor.login) {
+        out.stats.filtered_actor += 1;
+        return Ok(());
+    }
+    // JSON is written for every event passing the filters, including ones already present in the database
+    if ctx.db_out {
+        out.events.push(gha_event_rows(ctx, &ev));
+    } else {
+        out.stats.written += 1;
+    }
+    if ctx.json_out {
+        let json = if ctx.json_ndjson {
+            serde_json::to_string(&ev)
+        } else {
+            serde_json::to_string_pretty(&ev)
+        };
+        let json = json.map_err(|e| format!("{}: event {}: {e}", gha_file_name(dt), ev.id))?;
+        out.jsons.push((ev.id, json));
+    }
+    Ok(())
+}
+
+// Reads non-empty lines of an hourly file, calls `f` with line number (1-based) and line, returns number of lines
+fn gha_read_lines<F>(dt: DateTime<Utc>, reader: Box<dyn BufRead>, mut f: F) -> Result<usize, String>
+where
+    F: FnMut(usize, Vec<u8>) -> Result<(), String>,
+{
+    let mut n = 0;
+    for line in reader.split(b'\n') {
+        let line = line.map_err(|e| format!("{}: {e}", gha_file_name(dt)))?;
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        n += 1;
+        f(n, line)?;
+    }
+    Ok(n)
+}
+
+// Writes collected event rows in a single transaction, counts written and already present events
+fn gha_write_output(
+    ctx: &Ctx,
+    client: Option<&mut Client>,
+    dt: DateTime<Utc>,
+    out: &mut GhaOutput,
+) -> Result<(), String> {
+    let Some(client) = client else {
+        return Ok(());
+    };
+    let n = out.events.len() as u64;
+    let written = gha_write_events(client, ctx, &out.events)
+        .map_err(|e| format!("{}: writing {n} events: {e}", gha_file_name(dt)))?;
+    out.stats.written += written;
+    out.stats.existing += n - written;
+    out.events.clear();
+    Ok(())
+}
+
+// Processes all JSONs from a single hourly file stream, events are written in batches of GHA_EVENTS_BATCH
+pub fn gha_process_stream(
+    ctx: &Ctx,
+    mut client: Option<&mut Client>,
+    reader: Box<dyn BufRead>,
+    dt: DateTime<Utc>,
+    filter: &GhaRepoFilter,
+) -> Result<GhaStats, String> {
+    let mut out = GhaOutput::default();
+    out.stats.files = 1;
+    let mut json_out = GhaJsonOut::new(ctx, dt, filter.catch_up)?;
+    let n = gha_read_lines(dt, reader, |n, line| {
+        gha_process_line(ctx, dt, n, line, filter, &mut out)?;
+        if let Some(json_out) = json_out.as_mut() {
+            json_out.write(&out.jsons)?;
+        }
+        out.jsons.clear();
+        if out.events.len() >= GHA_EVENTS_BATCH {
+            gha_write_output(ctx, client.as_deref_mut(), dt, &mut out)?;
+        }
+        Ok(())
+    })?;
+    gha_write_output(ctx, client, dt, &mut out)?;
+    if let Some(json_out) = json_out {
+        json_out.finish()?;
+    }
+    for (i, line) in &out.broken {
+        gha_write_broken_json(ctx, dt, *i, n, line)?;
+    }
+    Ok(out.stats)
+}
+
+// Saves broken JSON as <GHA2DB_JSON_DIR>error_YYYY-MM-DD-h-n-m.json, n is the JSON line number (1-m) of m lines in the hourly file
+fn gha_write_broken_json(
+    ctx: &Ctx,
+    dt: DateTime<Utc>,
+    n: usize,
+    m: usize,
+    line: &[u8],
+) -> Result<(), String> {
+    let path = format!(
+        "{}error_{}-{}-{n}-{m}.json",
+        ctx.json_dir,
+        dt.format("%Y-%m-%d"),
+        dt.hour()
+    );
+    std::fs::write(&path, line).map_err(|e| format!("{path}: {e}"))
+}
+
+// Fails the hour when its broken JSONs ratio exceeds GHA2DB_MAX_BROKEN_JSON_RATIO, called before the hour is marked
+// as imported, so a failed hour is imported again on the next run
+fn check_broken_json(ctx: &Ctx, dt: DateTime<Utc>, stats: &GhaStats) -> Result<(), String> {
+    if stats.broken_json == 0 || stats.events == 0 {
+        return Ok(());
+    }
+    let ratio = stats.broken_json as f64 / stats.events as f64;
+    if ratio > ctx.max_broken_json_ratio {
+        return Err(format!(
+            "{}: {} of {} JSONs broken ({:.4}), exceeds allowed ratio {}",
+            gha_file_name(dt),
+            stats.broken_json,
+            stats.events,
+            ratio,
+            ctx.max_broken_json_ratio
+        ));
+    }
+    Ok(())
+}
+
+// Opens hourly file, missing hourly files are logged and reported as None
+// When the filter resolves repository IDs, they are learned from the whole file first, the file is read twice
+// (it is on disk, so it is never downloaded twice)
+fn gha_open_hour(
+    ctx: &Ctx,
+    dt: DateTime<Utc>,
+    filter: &GhaRepoFilter,
+) -> Result<Option<Box<dyn BufRead>>, String> {
+    let Some(mut f) = gha_open_file(ctx, dt)? else {
+        printf(&format!("{}: missing, skipping", gha_file_name(dt)));
+        return Ok(None);
+    };
+    if filter.needs_ids() {
+        let err = |e: std::io::Error| format!("{}: {e}", gha_file_name(dt));
+        filter.learn_hour(ctx, dt, gha_reader(f.try_clone().map_err(err)?))?;
+        f.rewind().map_err(err)?;
+    }
+    Ok(Some(gha_reader(f)))
+}
+
+fn gha_missing_hour() -> GhaStats {
+    GhaStats {
+        files_missing: 1,
+        ..Default::default()
+    }
+}
+
+fn gha_report_hour(ctx: &Ctx, dt: DateTime<Utc>, stats: &GhaStats, took: Duration) {
+    // Per-file report is always shown when actors filter dropped something
+    if ctx.debug > 0 || (ctx.debug >= 0 && stats.filtered_actor > 0) {
+        printf(&format!(
+            "{}: {} events, {} written, {} already present, {} filtered out by repo, {} filtered out by actor, {} broken JSONs, took {:?}",
+            gha_file_name(dt),
+            stats.events,
+            stats.written,
+            stats.existing,
+            stats.filtered_repo,
+            stats.filtered_actor,
+            stats.broken_json,
+            took
+        ));
+    }
+}
+
+// Processes single hour of GHA data, missing hourly files are logged and skipped
+pub fn gha_process_hour(
+    ctx: &Ctx,
+    client: Option<&mut Client>,
+    dt: DateTime<Utc>,
+    filter: &GhaRepoFilter,
+) -> Result<GhaStats, String> {
+    let start = Instant::now();
+    let Some(reader) = gha_open_hour(ctx, dt, filter)? else {
+        return Ok(gha_missing_hour());
+    };
+    let stats = gha_process_stream(ctx, client, reader, dt, filter)?;
+    gha_report_hour(ctx, dt, &stats, start.elapsed());
+    Ok(stats)
+}
+
+// Event data duplicated into most gha_* tables to avoid joins
+struct GhaDup {
+    actor_id: i64,
+    actor_login: String,
+    repo_id: i64,
+    repo_name: String,
+    event_type: String,
+    created_at: NaiveDateTime,
+}
+
+impl GhaDup {
+    fn new(ev: &Event) -> Self {
+        GhaDup {
+            actor_id: ev.actor.id,
+            actor_login: trunc_to_bytes(&ev.actor.login, 120),
+            repo_id: ev.repo.id,
+            repo_name: trunc_to_bytes(&ev.repo.name, 160),
+            event_type: ev.event_type.clone(),
+            created_at: ev.created_at.naive_utc(),
+        }
+    }
+
+    // Values of gha_dup_cols!() columns
+    fn values(&self) -> [GhaValue; 6] {
+        [
+            self.actor_id.into(),
+            self.actor_login.clone().into(),
+            self.repo_id.into(),
+            self.repo_name.clone().into(),
+            self.event_type.clone().into(),
+            self.created_at.into(),
+        ]
+    }
+}
+
+macro_rules! gha_dup_cols {
+    () => {
+        "dup_actor_id, dup_actor_login, dup_repo_id, dup_repo_name, dup_type, dup_created_at"
+    };
+}
+
+// Single column value of a gha_* row, NULLs keep their type so rows can be bound in multi-row statements
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GhaValue {
+    Bool(Option<bool>),
+    Int(Option<i32>),
+    BigInt(Option<i64>),
+    Text(Option<String>),
+    Time(Option<NaiveDateTime>),
+}
+
+macro_rules! gha_value_from {
+    ($t:ty, $variant:ident) => {
+        impl From<$t> for GhaValue {
+            fn from(v: $t) -> Self {
+                GhaValue::$variant(Some(v))
+            }
+        }
+
+        impl From<Option<$t>> for GhaValue {
+            fn from(v: Option<$t>) -> Self {
+                GhaValue::$variant(v)
+            }
+        }
+    };
+}
+
+gha_value_from!(bool, Bool);
+gha_value_from!(i32, Int);
+gha_value_from!(i64, BigInt);
+gha_value_from!(String, Text);
+gha_value_from!(NaiveDateTime, Time);
+
+impl GhaValue {
+    fn param(&self) -> SqlParam {
+        match self.clone() {
+            GhaValue::Bool(v) => Box::new(v),
+            GhaValue::Int(v) => Box::new(v),
+            GhaValue::BigInt(v) => Box::new(v),
+            GhaValue::Text(v) => Box::new(v),
+            GhaValue::Time(v) => Box::new(v),
+        }
+    }
+}
+
+// Row values from expressions convertible to GhaValue
+macro_rules! gha_values {
+    ($($v:expr),* $(,)?) => {
+        vec![$(GhaValue::from($v)),*]
+    };
+}
+
+// gha_* table written by the importer: comma separated columns, number of leading columns forming the primary key
+// (0: no primary key) and whether its rows are shared between events (actors, repos, ...), shared rows keep values
+// from the event with the lowest ID, so the result doesn't depend on the order events are written in
+pub struct GhaTable {
+    pub name: &'static str,
+    pub cols: &'static str,
+    pub key: usize,
+    pub shared: bool,
+}
+
+const GHA_EVENTS: GhaTable = GhaTable {
+    name: "gha_events",
+    cols: "id, type, actor_id, repo_id, public, created_at, org_id, forkee_id, dup_actor_login, dup_repo_name",
+    key: 1,
+    shared: false,
+};
+const GHA_ACTORS: GhaTable = GhaTable {
+    name: "gha_actors",
+    cols: "id, login, is_bot, event_id",
+    key: 1,
+    shared: true,
+};
+const GHA_ORGS: GhaTable = GhaTable {
+    name: "gha_orgs",
+    cols: "id, login, event_id",
+    key: 1,
+    shared: true,
+};
+const GHA_REPOS: GhaTable = GhaTable {
+    name: "gha_repos",
+    cols: "id, name, org_id, org_login, event_id",
+    key: 2,
+    shared: true,
+};
+const GHA_LABELS: GhaTable = GhaTable {
+    name: "gha_labels",
+    cols: "id, name, color, is_default, event_id",
+    key: 1,
+    shared: true,
+};
+const GHA_COMMENTS: GhaTable = GhaTable {
+    name: "gha_comments",
+    cols: concat!(
+        "id, body, created_at, updated_at, user_id, commit_id, original_commit_id, diff_hunk, position, ",
+        "original_position, path, pull_request_review_id, line, ",
+        gha_dup_cols!(),
+        ", dup_user_login, event_id"
+    ),
+    key: 1,
+    shared: true,
+};
+const GHA_PAYLOADS: GhaTable = GhaTable {
+    name: "gha_payloads",
+    cols: concat!(
+        "event_id, push_id, size, ref, head, befor, action, issue_id, pull_request_id, comment_id, ",
+        "ref_type, master_branch, description, number, forkee_id, release_id, member_id, commit, ",
+        gha_dup_cols!()
+    ),
+    key: 1,
+    shared: false,
+};
+const GHA_COMMITS: GhaTable = GhaTable {
+    name: "gha_commits",
+    cols: concat!(
+        "sha, event_id, author_name, encrypted_email, message, is_distinct, ",
+        gha_dup_cols!()
+    ),
+    key: 2,
+    shared: false,
+};
+const GHA_PAGES: GhaTable = GhaTable {
+    name: "gha_pages",
+    cols: concat!("sha, event_id, action, title, ", gha_dup_cols!()),
+    key: 4,
+    shared: false,
+};
+const GHA_ISSUES: GhaTable = GhaTable {
+    name: "gha_issues",
+    cols: concat!(
+        "id, event_id, assignee_id, body, closed_at, comments, created_at, locked, milestone_id, number, ",
+        "state, title, updated_at, user_id, is_pull_request, ",
+        gha_dup_cols!(),
+        ", dup_user_login, dupn_assignee_login"
+    ),
+    key: 2,
+    shared: false,
+};
+const GHA_ISSUES_ASSIGNEES: GhaTable = GhaTable {
+    name: "gha_issues_assignees",
+    cols: "issue_id, event_id, assignee_id",
+    key: 3,
+    shared: false,
+};
+const GHA_ISSUES_LABELS: GhaTable = GhaTable {
+    name: "gha_issues_labels",
+    cols: concat!(
+        "issue_id, event_id, label_id, ",
+        gha_dup_cols!(),
+        ", dup_issue_number, dup_label_name"
+    ),
+    key: 3,
+    shared: false,
+};
+const GHA_MILESTONES: GhaTable = GhaTable {
+    name: "gha_milestones",
+    cols: concat!(
+        "id, event_id, closed_at, closed_issues, created_at, creator_id, description, due_on, number, ",
+        "open_issues, state, title, updated_at, ",
+        gha_dup_cols!(),
+        ", dupn_creator_login"
+    ),
+    key: 2,
+    shared: false,
+};
+const GHA_PULL_REQUESTS: GhaTable = GhaTable {
+    name: "gha_pull_requests",
+    cols: concat!(
+        "id, event_id, user_id, base_sha, head_sha, merged_by_id, assignee_id, milestone_id, number, state, ",
+        "locked, title, body, created_at, updated_at, closed_at, merged_at, merge_commit_sha, merged, ",
+        "mergeable, rebaseable, mergeable_state, comments, review_comments, maintainer_can_modify, commits, ",
+        "additions, deletions, changed_files, ",
+        gha_dup_cols!(),
+        ", dup_user_login, dupn_assignee_login, dupn_merged_by_login"
+    ),
+    key: 2,
+    shared: false,
+};
+const GHA_BRANCHES: GhaTable = GhaTable {
+    name: "gha_branches",
+    cols: "sha, event_id, user_id, repo_id, label, ref, dupn_forkee_name, dupn_user_login",
+    key: 2,
+    shared: false,
+};
+const GHA_PULL_REQUESTS_ASSIGNEES: GhaTable = GhaTable {
+    name: "gha_pull_requests_assignees",
+    cols: "pull_request_id, event_id, assignee_id",
+    key: 3,
+    shared: false,
+};
+const GHA_PULL_REQUESTS_REQUESTED_REVIEWERS: GhaTable = GhaTable {
+    name: "gha_pull_requests_requested_reviewers",
+    cols: "pull_request_id, event_id, requested_reviewer_id",
+    key: 3,
+    shared: false,
+};
+const GHA_FORKEES: GhaTable = GhaTable {
+    name: "gha_forkees",
+    cols: concat!(
+        "id, event_id, name, full_name, owner_id, description, fork, created_at, updated_at, pushed_at, ",
+        "homepage, size, language, stargazers_count, has_issues, has_projects, has_downloads, has_wiki, ",
+        "has_pages, forks, default_branch, open_issues, watchers, public, org_id, ",
+        gha_dup_cols!(),
+        ", dup_owner_login"
+    ),
+    key: 2,
+    shared: false,
+};
+const GHA_RELEASES: GhaTable = GhaTable {
+    name: "gha_releases",
+    cols: concat!(
+        "id, event_id, tag_name, target_commitish, name, draft, author_id, prerelease, created_at, ",
+        "published_at, body, ",
+        gha_dup_cols!(),
+        ", dup_author_login"
+    ),
+    key: 2,
+    shared: false,
+};
+const GHA_ASSETS: GhaTable = GhaTable {
+    name: "gha_assets",
+    cols: concat!(
+        "id, event_id, name, label, uploader_id, content_type, state, size, download_count, ",
+        "created_at, updated_at, ",
+        gha_dup_cols!(),
+        ", dup_uploader_login"
+    ),
+    key: 2,
+    shared: false,
+};
+const GHA_RELEASES_ASSETS: GhaTable = GhaTable {
+    name: "gha_releases_assets",
+    cols: "release_id, event_id, asset_id",
+    key: 3,
+    shared: false,
+};
+const GHA_TEAMS: GhaTable = GhaTable {
+    name: "gha_teams",
+    cols: concat!("id, event_id, name, slug, permission, ", gha_dup_cols!()),
+    key: 2,
+    shared: false,
+};
+const GHA_TEAMS_REPOSITORIES: GhaTable = GhaTable {
+    name: "gha_teams_repositories",
+    cols: "team_id, event_id, repository_id",
+    key: 3,
+    shared: false,
+};
+// Texts have no primary key, they are written with binary COPY
+const GHA_TEXTS: GhaTable = GhaTable {
+    name: "gha_texts",
+    cols: "event_id, body, created_at, actor_id, actor_login, repo_id, repo_name, type",
+    key: 0,
+    shared: false,
+};
+
+// Postgres types of GHA_TEXTS columns, for binary COPY
+fn gha_texts_types() -> Vec<Type> {
+    vec![
+        Type::INT8,
+        Type::TEXT,
+        Type::TIMESTAMP,
+        Type::INT8,
+        Type::VARCHAR,
+        Type::INT8,
+        Type::VARCHAR,
+        Type::VARCHAR,
+    ]
+}
+
+// Tables in the order they are written in, shared tables first: concurrent batches lock shared rows in the same order
+const GHA_TABLES: [&GhaTable; 24] = [
+    &GHA_EVENTS,
+    &GHA_ACTORS,
+    &GHA_ORGS,
+    &GHA_REPOS,
+    &GHA_LABELS,
+    &GHA_COMMENTS,
+    &GHA_PAYLOADS,
+    &GHA_COMMITS,
+    &GHA_PAGES,
+    &GHA_ISSUES,
+    &GHA_ISSUES_ASSIGNEES,
+    &GHA_ISSUES_LABELS,
+    &GHA_MILESTONES,
+    &GHA_PULL_REQUESTS,
+    &GHA_BRANCHES,
+    &GHA_PULL_REQUESTS_ASSIGNEES,
+    &GHA_PULL_REQUESTS_REQUESTED_REVIEWERS,
+    &GHA_FORKEES,
+    &GHA_RELEASES,
+    &GHA_ASSETS,
+    &GHA_RELEASES_ASSETS,
+    &GHA_TEAMS,
+    &GHA_TEAMS_REPOSITORIES,
+    &GHA_TEXTS,
+];
+
+// Rows of a single event and all its payload objects, built without touching the database
+// The gha_events row goes first, other rows are only written when it was not present yet
+pub struct GhaEventRows {
+    pub eid: i64,
+    pub rows: Vec<(&'static GhaTable, Vec<GhaValue>)>,
+}
+
+impl GhaEventRows {
+    fn add(&mut self, table: &'static GhaTable, values: Vec<GhaValue>) {
+        debug_assert_eq!(values.len(), table.cols.split(", ").count(), "{}", table.name);
+        self.rows.push((table, values));
+    }
+
+    fn add_text(&mut self, dup: &GhaDup, body: &str) {
+        let values = gha_values![
+            self.eid,
+            trunc_to_bytes(body, usize::MAX),
+            dup.created_at,
+            dup.actor_id,
+            dup.actor_login.clone(),
+            dup.repo_id,
+            dup.repo_name.clone(),
+            dup.event_type.clone(),
+        ];
+        self.add(&GHA_TEXTS, values);
+    }
+}
+
+fn gha_naive(dt: &Option<DateTime<Utc>>) -> Option<NaiveDateTime> {
+    dt.map(|dt| dt.naive_utc())
+}
+
+fn gha_actor_id(actor: &Option<Actor>) -> Option<i64> {
+    actor.as_ref().map(|a| a.id)
+}
+
+fn gha_actor_login(actor: &Option<Actor>) -> Option<String> {
+    actor.as_ref().map(|a| trunc_to_bytes(&a.login, 120))
+}
+
+fn gha_opt_text(s: &Option<String>, n: usize) -> Option<String> {
+    s.as_ref().map(|s| trunc_to_bytes(s, n))
+}
+
+// All actors referenced by the event
+fn gha_event_actors(ev: &Event) -> Vec<&Actor> {
+    let p = &ev.payload;
+    let mut actors = vec![&ev.actor];
+    actors.extend(p.member.iter());
+    actors.extend(p.assignee.iter());
+    if let Some(issue) = &p.issue {
+        actors.push(&issue.user);
+        actors.extend(issue.assignee.iter());
+        actors.extend(issue.assignees.iter());
+        actors.extend(issue.milestone.iter().filter_map(|m| m.creator.as_ref()));
+    }
+    if let Some(pr) = &p.pull_request {
+        actors.push(&pr.user);
+        actors.extend(pr.assignee.iter());
+        actors.extend(pr.assignees.iter());
+        actors.extend(pr.requested_reviewers.iter());
+        actors.extend(pr.merged_by.iter());
+        actors.extend(pr.milestone.iter().filter_map(|m| m.creator.as_ref()));
+        actors.extend(pr.base.user.iter());
+        actors.extend(pr.head.user.iter());
+    }
+    if let Some(comment) = &p.comment {
+        actors.push(&comment.user);
+    }
+    if let Some(release) = &p.release {
+        actors.push(&release.author);
+        actors.extend(release.assets.iter().map(|a| &a.uploader));
+    }
+    if let Some(forkee) = &p.forkee {
+        actors.extend(forkee.owner.iter());
+    }
+    actors
+}
+
+// Converts event and all its payload objects into gha_* rows
+pub fn gha_event_rows(ctx: &Ctx, ev: &Event) -> GhaEventRows {
+    let eid = ev.id_num();
+    let mut rows = GhaEventRows { eid, rows: vec![] };
+    let dup = GhaDup::new(ev);
+    let p = &ev.payload;
+    let org_id = ev.org.as_ref().map(|o| o.id);
+    let org_login = ev.org.as_ref().map(|o| trunc_to_bytes(&o.login, 100));
+
+    // Event
+    let forkee_id = p.forkee.as_ref().map(|f| f.id);
+    rows.add(
+        &GHA_EVENTS,
+        gha_values![
+            eid,
+            dup.event_type.clone(),
+            dup.actor_id,
+            dup.repo_id,
+            ev.public,
+            dup.created_at,
+            org_id,
+            forkee_id,
+            dup.actor_login.clone(),
+            dup.repo_name.clone(),
+        ],
+    );
+
+    // Actors, orgs, repos
+    let mut actors = gha_event_actors(ev);
+    actors.sort_by_key(|a| a.id);
+    actors.dedup_by_key(|a| a.id);
+    for actor in actors {
+        let values = gha_values![
+            actor.id,
+            trunc_to_bytes(&actor.login, 120),
+            ctx.bots().is_bot(&actor.login),
+            eid,
+        ];
+        rows.add(&GHA_ACTORS, values);
+    }
+    if let (Some(id), Some(login)) = (org_id, &org_login) {
+        rows.add(&GHA_ORGS, gha_values![id, login.clone(), eid]);
+    }
+    rows.add(
+        &GHA_REPOS,
+        gha_values![dup.repo_id, dup.repo_name.clone(), org_id, org_login, eid],
+    );
+
+    // Payload
+    let action = gha_opt_text(&p.action, 40);
+    let mut values = gha_values![
+        eid,
+        p.push_id,
+        p.size,
+        gha_opt_text(&p.git_ref, 200),
+        p.head.clone(),
+        p.before.clone(),
+        action.clone(),
+        p.issue.as_ref().map(|i| i.id),
+        p.pull_request.as_ref().map(|pr| pr.id),
+        p.comment.as_ref().map(|c| c.id),
+        p.ref_type.clone(),
+        gha_opt_text(&p.master_branch, 200),
+        gha_opt_text(&p.description, usize::MAX),
+        p.number,
+        forkee_id,
+        p.release.as_ref().map(|r| r.id),
+        p.member.as_ref().map(|m| m.id),
+        p.comment.as_ref().and_then(|c| c.commit_id.clone()),
+    ];
+    values.extend(dup.values());
+    rows.add(&GHA_PAYLOADS, values);
+
+    // Commits
+    for c in p.commits.iter().flatten() {
+        let mut values = gha_values![
+            c.sha.clone(),
+            eid,
+            trunc_to_bytes(&c.author.name, 160),
+            trunc_to_bytes(&c.author.email, 160),
+            trunc_to_bytes(&c.message, usize::MAX),
+            c.distinct,
+        ];
+        values.extend(dup.values());
+        rows.add(&GHA_COMMITS, values);
+        rows.add_text(&dup, &c.message);
+    }
+
+    // Pages
+    for page in p.pages.iter().flatten() {
+        let mut values = gha_values![
+            page.sha.clone(),
+            eid,
+            page.action.clone(),
+            trunc_to_bytes(&page.title, 300),
+        ];
+        values.extend(dup.values());
+        rows.add(&GHA_PAGES, values);
+    }
+
+    if let Some(comment) = &p.comment {
+        gha_comment_rows(&mut rows, &dup, comment);
+        rows.add_text(&dup, &comment.body);
+    }
+    if let Some(issue) = &p.issue {
+        gha_issue_rows(&mut rows, &dup, issue);
+        if action.as_deref() == Some("opened") {
+            rows.add_text(&dup, &issue.title);
+            if let Some(body) = &issue.body {
+                rows.add_text(&dup, body);
+            }
+        }
+    }
+    if let Some(pr) = &p.pull_request {
+        gha_pull_request_rows(&mut rows, &dup, pr);
+        if action.as_deref() == Some("opened") {
+            rows.add_text(&dup, &pr.title);
+            if let Some(body) = &pr.body {
+                rows.add_text(&dup, body);
+            }
+        }
+    }
+    if let Some(review) = &p.review {
+        if let Some(body) = &review.body {
+            rows.add_text(&dup, body);
+        }
+    }
+    if let Some(forkee) = &p.forkee {
+        gha_forkee_rows(&mut rows, &dup, forkee, ev.created_at);
+    }
+    if let Some(release) = &p.release {
+        gha_release_rows(&mut rows, &dup, release);
+        if let Some(body) = &release.body {
+            rows.add_text(&dup, body);
+        }
+    }
+    if let Some(team) = &p.team {
+        let mut values = gha_values![
+            team.id,
+            eid,
+            trunc_to_bytes(&team.name, 120),
+            trunc_to_bytes(&team.slug, 100),
+            team.permission.clone(),
+        ];
+        values.extend(dup.values());
+        rows.add(&GHA_TEAMS, values);
+        if let Some(repo) = &p.repository {
+            rows.add(&GHA_TEAMS_REPOSITORIES, gha_values![team.id, eid, repo.id]);
+        }
+    }
+    rows
+}
+
+fn gha_comment_rows(rows: &mut GhaEventRows, dup: &GhaDup, c: &Comment) {
+    let mut values = gha_values![
+        c.id,
+        trunc_to_bytes(&c.body, usize::MAX),
+        c.created_at.naive_utc(),
+        c.updated_at.naive_utc(),
+        c.user.id,
+        c.commit_id.clone(),
+        c.original_commit_id.clone(),
+        gha_opt_text(&c.diff_hunk, usize::MAX),
+        c.position,
+        c.original_position,
+        gha_opt_text(&c.path, usize::MAX),
+        c.pull_request_review_id,
+        c.line,
+    ];
+    values.extend(dup.values());
+    values.extend(gha_values![trunc_to_bytes(&c.user.login, 120), rows.eid]);
+    rows.add(&GHA_COMMENTS, values);
+}
+
+fn gha_milestone_rows(rows: &mut GhaEventRows, dup: &GhaDup, m: &Milestone) {
+    let mut values = gha_values![
+        m.id,
+        rows.eid,
+        gha_naive(&m.closed_at),
+        m.closed_issues,
+        m.created_at.naive_utc(),
+        gha_actor_id(&m.creator),
+        gha_opt_text(&m.description, usize::MAX),
+        gha_naive(&m.due_on),
+        m.number,
+        m.open_issues,
+        m.state.clone(),
+        trunc_to_bytes(&m.title, 200),
+        m.updated_at.naive_utc(),
+    ];
+    values.extend(dup.values());
+    values.push(gha_actor_login(&m.creator).into());
+    rows.add(&GHA_MILESTONES, values);
+}
+
+// Label ID is missing in some old payloads, it is then hashed from the label name
+fn gha_label_id(l: &Label) -> i64 {
+    l.id.unwrap_or_else(|| hash_strings(&[&l.name]))
+}
+
+fn gha_issue_rows(rows: &mut GhaEventRows, dup: &GhaDup, i: &Issue) {
+    let eid = rows.eid;
+    let mut values = gha_values![
+        i.id,
+        eid,
+        gha_actor_id(&i.assignee),
+        gha_opt_text(&i.body, usize::MAX),
+        gha_naive(&i.closed_at),
+        i.comments,
+        i.created_at.naive_utc(),
+        i.locked,
+        i.milestone.as_ref().map(|m| m.id),
+        i.number,
+        i.state.clone(),
+        trunc_to_bytes(&i.title, usize::MAX),
+        i.updated_at.naive_utc(),
+        i.user.id,
+        i.pull_request.is_some(),
+    ];
+    values.extend(dup.values());
+    values.extend(gha_values![
+        trunc_to_bytes(&i.user.login, 120),
+        gha_actor_login(&i.assignee),
+    ]);
+    rows.add(&GHA_ISSUES, values);
+    for a in &i.assignees {
+        rows.add(&GHA_ISSUES_ASSIGNEES, gha_values![i.id, eid, a.id]);
+    }
+    for l in &i.labels {
+        let label_id = gha_label_id(l);
+        let label_name = trunc_to_bytes(&l.name, 160);
+        let values = gha_values![
+            label_id,
+            label_name.clone(),
+            trunc_to_bytes(&l.color, 8),
+            l.default,
+            eid,
+        ];
+        rows.add(&GHA_LABELS, values);
+        let mut values = gha_values![i.id, eid, label_id];
+        values.extend(dup.values());
+        values.extend(gha_values![i.number, label_name]);
+        rows.add(&GHA_ISSUES_LABELS, values);
+    }
+    if let Some(m) = &i.milestone {
+        gha_milestone_rows(rows, dup, m);
+    }
+}
+
+fn gha_branch_rows(rows: &mut GhaEventRows, repo_id: i64, b: &Branch) {
+    let values = gha_values![
+        b.sha.clone(),
+        rows.eid,
+        gha_actor_id(&b.user),
+        b.repo.as_ref().map_or(repo_id, |r| r.id),
+        trunc_to_bytes(&b.label, 200),
+        trunc_to_bytes(&b.git_ref, 200),
+        b.repo.as_ref().map(|r| trunc_to_bytes(&r.full_name, 160)),
+        gha_actor_login(&b.user),
+    ];
+    rows.add(&GHA_BRANCHES, values);
+}
+
+fn gha_pull_request_rows(rows: &mut GhaEventRows, dup: &GhaDup, pr: &PullRequest) {
+    let eid = rows.eid;
+    let mut values = gha_values![
+        pr.id,
+        eid,
+        pr.user.id,
+        pr.base.sha.clone(),
+        pr.head.sha.clone(),
+        gha_actor_id(&pr.merged_by),
+        gha_actor_id(&pr.assignee),
+        pr.milestone.as_ref().map(|m| m.id),
+        pr.number,
+        pr.state.clone(),
+        pr.locked,
+        trunc_to_bytes(&pr.title, usize::MAX),
+        gha_opt_text(&pr.body, usize::MAX),
+        pr.created_at.naive_utc(),
+        pr.updated_at.naive_utc(),
+        gha_naive(&pr.closed_at),
+        gha_naive(&pr.merged_at),
+        pr.merge_commit_sha.clone(),
+        pr.merged,
+        pr.mergeable,
+        pr.rebaseable,
+        gha_opt_text(&pr.mergeable_state, 20),
+        pr.comments,
+        pr.review_comments,
+        pr.maintainer_can_modify,
+        pr.commits,
+        pr.additions,
+        pr.deletions,
+        pr.changed_files,
+    ];
+    values.extend(dup.values());
+    values.extend(gha_values![
+        trunc_to_bytes(&pr.user.login, 120),
+        gha_actor_login(&pr.assignee),
+        gha_actor_login(&pr.merged_by),
+    ]);
+    rows.add(&GHA_PULL_REQUESTS, values);
+    gha_branch_rows(rows, dup.repo_id, &pr.base);
+    gha_branch_rows(rows, dup.repo_id, &pr.head);
+    for a in &pr.assignees {
+        rows.add(&GHA_PULL_REQUESTS_ASSIGNEES, gha_values![pr.id, eid, a.id]);
+    }
+    for r in &pr.requested_reviewers {
+        rows.add(&GHA_PULL_REQUESTS_REQUESTED_REVIEWERS, gha_values![pr.id, eid, r.id]);
+    }
+    if let Some(m) = &pr.milestone {
+        gha_milestone_rows(rows, dup, m);
+    }
+}
+
+fn gha_forkee_rows(rows: &mut GhaEventRows, dup: &GhaDup, f: &Forkee, dt: DateTime<Utc>) {
+    let mut values = gha_values![
+        f.id,
+        rows.eid,
+        trunc_to_bytes(&f.name, 100),
+        trunc_to_bytes(&f.full_name, 200),
+        f.owner.as_ref().map_or(0, |o| o.id),
+        gha_opt_text(&f.description, usize::MAX),
+        f.fork,
+        f.created_at.unwrap_or(dt).naive_utc(),
+        f.updated_at.or(f.created_at).unwrap_or(dt).naive_utc(),
+        gha_naive(&f.pushed_at),
+        gha_opt_text(&f.homepage, usize::MAX),
+        f.size,
+        gha_opt_text(&f.language, 80),
+        f.stargazers_count,
+        f.has_issues,
+        f.has_projects,
+        f.has_downloads,
+        f.has_wiki,
+        f.has_pages,
+        f.forks,
+        trunc_to_bytes(&f.default_branch, 200),
+        f.open_issues,
+        f.watchers,
+        f.public,
+        None::<i64>,
+    ];
+    values.extend(dup.values());
+    let owner_login = f.owner.as_ref().map_or("".to_string(), |o| trunc_to_bytes(&o.login, 120));
+    values.push(owner_login.into());
+    rows.add(&GHA_FORKEES, values);
+}
+
+fn gha_release_rows(rows: &mut GhaEventRows, dup: &GhaDup, r: &Release) {
+    let eid = rows.eid;
+    let mut values = gha_values![
+        r.id,
+        eid,
+        trunc_to_bytes(&r.tag_name, 200),
+        trunc_to_bytes(&r.target_commitish, 200),
+        gha_opt_text(&r.name, 200),
+        r.draft,
+        r.author.id,
+        r.prerelease,
+        r.created_at.naive_utc(),
+        gha_naive(&r.published_at),
+        gha_opt_text(&r.body, usize::MAX),
+    ];
+    values.extend(dup.values());
+    values.push(trunc_to_bytes(&r.author.login, 120).into());
+    rows.add(&GHA_RELEASES, values);
+    for a in &r.assets {
+        let mut values = gha_values![
+            a.id,
+            eid,
+            trunc_to_bytes(&a.name, 200),
+            gha_opt_text(&a.label, 120),
+            a.uploader.id,
+            trunc_to_bytes(&a.content_type, 80),
+            a.state.clone(),
+            i32::try_from(a.size).unwrap_or(i32::MAX),
+            a.download_count,
+            a.created_at.naive_utc(),
+            a.updated_at.naive_utc(),
+        ];
+        values.extend(dup.values());
+        values.push(trunc_to_bytes(&a.uploader.login, 120).into());
+        rows.add(&GHA_ASSETS, values);
+        rows.add(&GHA_RELEASES_ASSETS, gha_values![r.id, eid, a.id]);
+    }
+}
+
+// Rows of new events grouped by table in GHA_TABLES order, as they are written by gha_write_events:
+// rows of each table are sorted by primary key (keeping events order for equal keys), shared rows are deduplicated
+// keeping the row from the event with the lowest ID (an upsert cannot update the same row twice in one statement)
+fn gha_batch_rows<'a>(
+    events: &'a [GhaEventRows],
+    new: &HashSet<i64>,
+) -> Vec<(&'static GhaTable, Vec<&'a [GhaValue]>)> {
+    let mut tables: HashMap<&str, Vec<&[GhaValue]>> = HashMap::new();
+    let mut seen = HashSet::new();
+    for ev in events {
+        // An event present twice in the batch is written once
+        if !new.contains(&ev.eid) || !seen.insert(ev.eid) {
+            continue;
+        }
+        for (table, values) in ev.rows.iter().skip(1) {
+            tables.entry(table.name).or_default().push(values);
+        }
+    }
+    let mut batch = vec![];
+    for table in GHA_TABLES.iter().skip(1) {
+        let Some(mut rows) = tables.remove(table.name) else {
+            continue;
+        };
+        if table.shared {
+            // event_id is the last column of shared tables
+            rows.sort_by(|a, b| a[..table.key].cmp(&b[..table.key]).then(a.last().cmp(&b.last())));
+            rows.dedup_by(|a, b| a[..table.key] == b[..table.key]);
+        } else {
+            rows.sort_by(|a, b| a[..table.key].cmp(&b[..table.key]));
+        }
+        batch.push((*table, rows));
+    }
+    batch
+}
+
+// Writes rows of a batch of events in a single transaction, returns number of events written (not present before)
+// The transaction is retried when Postgres aborts it because of a deadlock
+pub fn gha_write_events(
+    client: &mut Client,
+    ctx: &Ctx,
+    events: &[GhaEventRows],
+) -> Result<u64, postgres::Error> {
+    if events.is_empty() {
+        return Ok(0);
+    }
+    let mut tries = 1;
+    loop {
+        match gha_write_events_tx(client, ctx, events) {
+            Err(e) if tries < 3 && gha_retryable(&e) => {
+                if ctx.debug > 0 {
+                    printf(&format!("{} events: retrying after: {e}", events.len()));
+                }
+                tries += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+fn gha_retryable(e: &postgres::Error) -> bool {
+    matches!(
+        e.code(),
+        Some(&SqlState::T_R_DEADLOCK_DETECTED) | Some(&SqlState::T_R_SERIALIZATION_FAILURE)
+    )
+}
+
+fn gha_write_events_tx(
+    client: &mut Client,
+    ctx: &Ctx,
+    events: &[GhaEventRows],
+) -> Result<u64, postgres::Error> {
+    batch_sql_with_err(client, ctx, "begin")?;
+    match gha_write_events_rows(client, ctx, events) {
+        Ok(n) => {
+            batch_sql_with_err(client, ctx, "commit")?;
+            Ok(n)
+        }
+        Err(e) => {
+            let _ = batch_sql_with_err(client, ctx, "rollback");
+            Err(e)
+        }
+    }
+}
+
+// Event rows go first: concurrent writers of the same event wait for each other here and only one of them proceeds
+// Shared rows are then written in table and primary key order, so concurrent transactions cannot deadlock
+fn gha_write_events_rows(
+    client: &mut Client,
+    ctx: &Ctx,
+    events: &[GhaEventRows],
+) -> Result<u64, postgres::Error> {
+    let mut order: Vec<&GhaEventRows> = events.iter().collect();
+    order.sort_by_key(|ev| ev.eid);
+    let cols: Vec<&str> = GHA_EVENTS.cols.split(", ").collect();
+    let mut bulk = BulkInsert::new(ctx, GHA_EVENTS.name, &cols);
+    bulk.set_suffix("on conflict do nothing");
+    bulk.set_returning("id");
+    for ev in order {
+        bulk.add_with_err(client, ev.rows[0].1.iter().map(|v| v.param()).collect())?;
+    }
+    bulk.flush_with_err(client)?;
+    // Dry run mode doesn't insert anything, all events are written as new
+    let new: HashSet<i64> = if ctx.dry_run {
+        events.iter().map(|ev| ev.eid).collect()
+    } else {
+        bulk.take_returned().iter().map(|row| row.get(0)).collect()
+    };
+    for (table, rows) in gha_batch_rows(events, &new) {
+        let cols: Vec<&str> = table.cols.split(", ").collect();
+        if table.name == GHA_TEXTS.name {
+            let format = CopyFormat::Binary(gha_texts_types());
+            let mut copy = BulkCopy::new(ctx, table.name, &cols, format);
+            for values in rows {
+                copy.add_with_err(client, values.iter().map(|v| v.param()).collect())?;
+            }
+            copy.flush_with_err(client)?;
+            continue;
+        }
+        let mut bulk = BulkInsert::new(ctx, table.name, &cols);
+        if table.shared {
+            let set: Vec<String> = cols[table.key..]
+                .iter()
+                .map(|c| format!("{c} = excluded.{c}"))
+                .collect();
+            bulk.set_suffix(&format!(
+                "on conflict({}) do update set {} where excluded.event_id < {}.event_id",
+                cols[..table.key].join(", "),
+                set.join(", "),
+                table.name
+            ));
+        } else {
+            bulk.set_suffix("on conflict do nothing");
+        }
+        for values in rows {
+            bulk.add_with_err(client, values.iter().map(|v| v.param()).collect())?;
+        }
+        bulk.flush_with_err(client)?;
+    }
+    Ok(new.len() as u64)
+}
+
+// Imports all hours from `from` to `to` (inclusive), writes to DB and/or JSON files depending on db_out/json_out
+// Uses threads_num() workers, single threaded mode (GHA2DB_ST) processes hours and events sequentially
+pub fn gha2db(
+    ctx: &Ctx,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    filter: &GhaRepoFilter,
+) -> Result<GhaStats, String> {
+    let skip = skip_dates(ctx)?;
+    if ctx.db_out {
+        ctx.load_bots()?;
+    }
+    if ctx.json_out || ctx.allow_broken_json {
+        std::fs::create_dir_all(&ctx.json_dir).map_err(|e| format!("{}: {e}", ctx.json_dir))?;
+    }
+    let mut stats = GhaStats::default();
+    let mut hours = Vec::new();
+    let mut dt = hour_start(from);
+    let to = hour_start(to);
+    while dt <= to {
+        if skip.contains(&dt) {
+            printf(&format!("{}: listed in {}, skipping", gha_file_name(dt), ctx.skip_dates_yaml));
+            stats.skipped += 1;
+        } else {
+            hours.push(dt);
+        }
+        dt += chrono::Duration::hours(1);
+    }
+    let key = import_key(ctx, filter);
+    let mut client = if ctx.db_out { Some(pg_conn(ctx)) } else { None };
+    if let Some(client) = client.as_mut() {
+        if !ctx.force_start_date && !hours.is_empty() {
+            hours = gha_resume_hours(client, ctx, &key, hours, &mut stats)?;
+        }
+    }
+    let thrn = ctx.threads_num();
+    if thrn > 1 {
+        drop(client);
+        stats.add(&gha2db_threads(ctx, &key, &hours, filter, thrn)?);
+    } else {
+        if let Some(client) = client.as_mut() {
+            filter
+                .load_repo_ids(client, ctx)
+                .map_err(|e| format!("cannot load repositories: {e}"))?;
+        }
+        for &dt in &hours {
+            if let Some(client) = client.as_mut() {
+                import_hour_started(client, ctx, &key, dt)
+                    .map_err(|e| gha_checkpoint_error(dt, e))?;
+            }
+            let hour = gha_process_hour(ctx, client.as_mut(), dt, filter)?;
+            check_broken_json(ctx, dt, &hour)?;
+            if let Some(client) = client.as_mut() {
+                import_hour_finished(client, ctx, &key, dt, &hour)
+                    .map_err(|e| gha_checkpoint_error(dt, e))?;
+            }
+            stats.add(&hour);
+        }
+    }
+    gha_catch_up(ctx, &key, &hours, filter, &mut stats)?;
+    Ok(stats)
+}
+
+// Imports events of renamed repositories from hours before the first hour one of their matching names appears in
+// (e.g. events of "org/old" when importing "org/new"), only hours before that are read again and only when there are
+// such repositories
+fn gha_catch_up(
+    ctx: &Ctx,
+    key: &str,
+    hours: &[DateTime<Utc>],
+    filter: &GhaRepoFilter,
+    stats: &mut GhaStats,
+) -> Result<(), String> {
+    let Some(&from) = hours.first() else {
+        return Ok(());
+    };
+    let renamed = filter.renamed(from);
+    let Some(&last) = renamed.values().max() else {
+        return Ok(());
+    };
+    if ctx.debug >= 0 {
+        printf(&format!(
+            "{} renamed repositories first matched after {}, catching up until {}",
+            renamed.len(),
+            gha_file_name(from),
+            gha_file_name(last)
+        ));
+    }
+    let mut client = if ctx.db_out { Some(pg_conn(ctx)) } else { None };
+    for &dt in hours.iter().take_while(|dt| **dt < last) {
+        let ids: Vec<i64> = renamed
+            .iter()
+            .filter(|(_, first)| **first > dt)
+            .map(|(id, _)| *id)
+            .collect();
+        let Some(reader) = gha_open(ctx, dt)? else {
+            continue;
+        };
+        if let Some(client) = client.as_mut() {
+            import_hour_reopened(client, ctx, key, dt).map_err(|e| gha_checkpoint_error(dt, e))?;
+        }
+        let filter = filter.catch_up_filter(&ids);
+        let hour = gha_process_stream(ctx, client.as_mut(), reader, dt, &filter)?;
+        if let Some(client) = client.as_mut() {
+            import_hour_caught_up(client, ctx, key, dt, &hour)
+                .map_err(|e| gha_checkpoint_error(dt, e))?;
+        }
+        // Events written now were counted as filtered out by repository
+        stats.filtered_repo -= hour.events - hour.filtered_repo - hour.broken_json;
+        stats.filtered_actor += hour.filtered_actor;
+        stats.written += hour.written;
+        stats.existing += hour.existing;
+    }
+    Ok(())
+}
+
+// Drops hours already imported according to gha_import_hours, unless GHA2DB_STARTDT_FORCE is set
+fn gha_resume_hours(
+    client: &mut Client,
+    ctx: &Ctx,
+    key: &str,
+    hours: Vec<DateTime<Utc>>,
+    stats: &mut GhaStats,
+) -> Result<Vec<DateTime<Utc>>, String> {
+    let (from, to) = (hours[0], hours[hours.len() - 1]);
+    let statuses = import_hours_status(client, ctx, key, from, to)
+        .map_err(|e| format!("cannot read import checkpoints (run migrate?): {e}"))?;
+    let n = hours.len();
+    let hours: Vec<DateTime<Utc>> = hours
+        .into_iter()
+        .filter(|dt| statuses.get(dt).map(|s| s.as_str()) != Some("done"))
+        .collect();
+    stats.imported += (n - hours.len()) as u64;
+    if ctx.debug >= 0 && hours.len() < n {
+        printf(&format!(
+            "{key}: {} hours already imported, resuming with {} hours",
+            n - hours.len(),
+            hours.len()
+        ));
+    }
+    Ok(hours)
+}
+
+fn gha_checkpoint_error(dt: DateTime<Utc>, e: postgres::Error) -> String {
+    format!("{}: cannot write import checkpoint: {e}", gha_file_name(dt))
+}
+
+// Updates import checkpoint using a pooled connection, released right away so events workers are not starved
+fn gha_checkpoint<F>(pool: Option<&PgPool>, dt: DateTime<Utc>, f: F) -> Result<(), String>
+where
+    F: FnOnce(&mut Client) -> Result<(), postgres::Error>,
+{
+    if let Some(pool) = pool {
+        let mut conn = pool.get_with_err().map_err(|e| e.to_string())?;
+        f(&mut conn).map_err(|e| gha_checkpoint_error(dt, e))?;
+    }
+    Ok(())
+}
+
+// Number of JSON lines sent to a worker at once, and of events written in a single transaction
+const GHA_EVENTS_BATCH: usize = 500;
+
+// Limits total size of JSON lines read but not yet processed (GHA2DB_IMPORT_MEMORY)
+struct MemoryBudget {
+    limit: usize,
+    used: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl MemoryBudget {
+    fn new(limit: usize) -> Self {
+        MemoryBudget {
+            limit: limit.max(1),
+            used: Mutex::new(0),
+            freed: Condvar::new(),
+        }
+    }
+
+    // Waits until `n` bytes fit in the budget, returns number of bytes taken (a single batch larger than the limit takes all of it)
+    fn acquire(&self, n: usize) -> usize {
+        let n = n.min(self.limit);
+        let mut used = self.used.lock().expect("memory budget mutex poisoned");
+        while *used + n > self.limit {
+            used = self.freed.wait(used).expect("memory budget mutex poisoned");
+        }
+        *used += n;
+        n
+    }
+
+    fn release(&self, n: usize) {
+        *self.used.lock().expect("memory budget mutex poisoned") -= n;
+        self.freed.notify_all();
+    }
+}
+
+// Hour is filtered only once repository IDs from all hours up to it are learned (GhaRepoFilter::learn_hour),
+// so events are filtered the same way as when hours are imported one by one
+// Hours are taken in order, so all hours before a waiting one are already being learned
+struct GhaLearnGate {
+    learned: Mutex<(Vec<bool>, usize)>, // Learned hours, number of leading learned hours
+    cond: Condvar,
+}
+
+impl GhaLearnGate {
+    fn new(hours: usize) -> Self {
+        GhaLearnGate {
+            learned: Mutex::new((vec![false; hours], 0)),
+            cond: Condvar::new(),
+        }
+    }
+
+    // Marks i-th hour as learned, also when it failed, so no hour waits forever
+    fn learned(&self, i: usize) {
+        let mut learned = self.learned.lock().expect("learn gate mutex poisoned");
+        let (hours, upto) = &mut *learned;
+        hours[i] = true;
+        while *upto < hours.len() && hours[*upto] {
+            *upto += 1;
+        }
+        self.cond.notify_all();
+    }
+
+    fn wait(&self, i: usize) {
+        let mut learned = self.learned.lock().expect("learn gate mutex poisoned");
+        while learned.1 <= i {
+            learned = self.cond.wait(learned).expect("learn gate mutex poisoned");
+        }
+    }
+}
+
+// Batch of JSON lines from one hourly file, processed by any events worker
+// Result is sent back with the batch index, so the hour can output JSONs in the input order
+struct GhaBatch {
+    dt: DateTime<Utc>,
+    index: usize,
+    lines: GhaLines,
+    bytes: usize,
+    result: mpsc::Sender<(usize, Result<GhaOutput, String>)>,
+}
+
+// Multi threaded import: `thrn` readers decompress hourly files and split them into batches of events,
+// `thrn` workers write batches using pooled connections
+// Every batch is written in its own transaction, rows shared between events keep values from the earliest event (GhaTable)
+// and hours are filtered in order of learning repository IDs (GhaLearnGate), so the result is the same as with 1 thread
+fn gha2db_threads(
+    ctx: &Ctx,
+    key: &str,
+    hours: &[DateTime<Utc>],
+    filter: &GhaRepoFilter,
+    thrn: usize,
+) -> Result<GhaStats, String> {
+    let pool = if ctx.db_out {
+        let pool = PgPool::with_size(ctx, thrn);
+        {
+            let mut conn = pool.get_with_err().map_err(|e| e.to_string())?;
+            filter
+                .load_repo_ids(&mut conn, ctx)
+                .map_err(|e| format!("cannot load repositories: {e}"))?;
+        }
+        Some(pool)
+    } else {
+        None
+    };
+    if ctx.debug > 0 {
+        printf(&format!(
+            "importing {} hours using {thrn} threads, memory budget {} MB",
+            hours.len(),
+            ctx.import_memory >> 20
+        ));
+    }
+    let budget = MemoryBudget::new(ctx.import_memory);
+    let gate = GhaLearnGate::new(hours.len());
+    let (batch_tx, batch_rx) = mpsc::channel::<GhaBatch>();
+    let batch_rx = Mutex::new(batch_rx);
+    let next = AtomicUsize::new(0);
+    let failed: Mutex<Option<String>> = Mutex::new(None);
+    let stats = Mutex::new(GhaStats::default());
+    let is_failed = || failed.lock().expect("failed mutex poisoned").is_some();
+    let fail = |e: String| {
+        let mut failed = failed.lock().expect("failed mutex poisoned");
+        if failed.is_none() {
+            *failed = Some(e);
+        }
+    };
+    thread::scope(|s| {
+        for _ in 0..thrn {
+            s.spawn(|| loop {
+                let batch = match batch_rx.lock().expect("batch queue mutex poisoned").recv() {
+                    Ok(batch) => batch,
+                    Err(_) => break,
+                };
+                let GhaBatch {
+                    dt,
+                    index,
+                    lines,
+                    bytes,
+                    result,
+                } = batch;
+                let res = if is_failed() {
+                    Err("import aborted".to_string())
+                } else {
+                    gha_process_batch(ctx, pool.as_ref(), dt, lines, filter)
+                };
+                budget.release(bytes);
+                let _ = result.send((index, res));
+            });
+        }
+        for _ in 0..thrn {
+            let batch_tx = batch_tx.clone();
+            let (budget, next, stats, pool, gate) =
+                (&budget, &next, &stats, pool.as_ref(), &gate);
+            s.spawn(move || loop {
+                if is_failed() {
+                    break;
+                }
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= hours.len() {
+                    break;
+                }
+                let dt = hours[i];
+                let reader = gha_checkpoint(pool, dt, |c| import_hour_started(c, ctx, key, dt))
+                    .and_then(|_| gha_open_hour(ctx, dt, filter));
+                gate.learned(i);
+                let res = reader
+                    .and_then(|reader| {
+                        gate.wait(i);
+                        match reader {
+                            Some(reader) => {
+                                gha_process_hour_threads(ctx, dt, reader, &batch_tx, budget)
+                            }
+                            None => Ok(gha_missing_hour()),
+                        }
+                    })
+                    .and_then(|hour| {
+                        check_broken_json(ctx, dt, &hour)?;
+                        gha_checkpoint(pool, dt, |c| {
+                            import_hour_finished(c, ctx, key, dt, &hour)
+                        })?;
+                        Ok(hour)
+                    });
+                match res {
+                    Ok(hour) => stats.lock().expect("stats mutex poisoned").add(&hour),
+                    Err(e) => fail(e),
+                }
+            });
+        }
+        drop(batch_tx);
+    });
+    match failed.into_inner().expect("failed mutex poisoned") {
+        Some(e) => Err(e),
+        None => Ok(stats.into_inner().expect("stats mutex poisoned")),
+    }
+}
+
+fn gha_process_batch(
+    ctx: &Ctx,
+    pool: Option<&PgPool>,
+    dt: DateTime<Utc>,
+    lines: GhaLines,
+    filter: &GhaRepoFilter,
+) -> Result<GhaOutput, String> {
+    let mut out = GhaOutput::default();
+    for (n, line) in lines {
+        gha_process_line(ctx, dt, n, line, filter, &mut out)?;
+    }
+    if let (Some(pool), false) = (pool, out.events.is_empty()) {
+        let mut conn = pool.get_with_err().map_err(|e| e.to_string())?;
+        gha_write_output(ctx, Some(&mut conn), dt, &mut out)?;
+    }
+    Ok(out)
+}
+
+// Sends events of one hourly file in batches to workers and collects their results
+fn gha_process_hour_threads(
+    ctx: &Ctx,
+    dt: DateTime<Utc>,
+    reader: Box<dyn BufRead>,
+    batch_tx: &mpsc::Sender<GhaBatch>,
+    budget: &MemoryBudget,
+) -> Result<GhaStats, String> {
+    let start = Instant::now();
+    let (result_tx, result_rx) = mpsc::channel();
+    let mut out = GhaOutput::default();
+    out.stats.files = 1;
+    let mut json_out = GhaJsonOut::new(ctx, dt, false)?;
+    // Batches results waiting for all previous batches, JSONs are written in batches order
+    let mut pending: BTreeMap<usize, GhaOutput> = BTreeMap::new();
+    let mut next_out = 0;
+    let mut collect = |index: usize, res: Result<GhaOutput, String>| -> Result<(), String> {
+        pending.insert(index, res?);
+        while let Some(mut batch_out) = pending.remove(&next_out) {
+            if let Some(json_out) = json_out.as_mut() {
+                json_out.write(&batch_out.jsons)?;
+            }
+            out.stats.add(&batch_out.stats);
+            out.broken.append(&mut batch_out.broken);
+            next_out += 1;
+        }
+        Ok(())
+    };
+    let mut lines = Vec::with_capacity(GHA_EVENTS_BATCH);
+    let mut bytes = 0;
+    let mut batches = 0;
+    let mut send = |lines: GhaLines, bytes: usize| -> Result<(), String> {
+        let batch = GhaBatch {
+            dt,
+            index: batches,
+            lines,
+            bytes: budget.acquire(bytes),
+            result: result_tx.clone(),
+        };
+        batches += 1;
+        batch_tx
+            .send(batch)
+            .map_err(|_| format!("{}: events workers finished", gha_file_name(dt)))
+    };
+    let n = gha_read_lines(dt, reader, |n, line| {
+        bytes += line.len();
+        lines.push((n, line));
+        if lines.len() >= GHA_EVENTS_BATCH {
+            send(std::mem::take(&mut lines), std::mem::take(&mut bytes))?;
+            while let Ok((index, res)) = result_rx.try_recv() {
+                collect(index, res)?;
+            }
+        }
+        Ok(())
+    })?;
+    if !lines.is_empty() {
+        send(lines, bytes)?;
+    }
+    drop(result_tx);
+    for (index, res) in result_rx {
+        collect(index, res)?;
+    }
+    if let Some(json_out) = json_out {
+        json_out.finish()?;
+    }
+    let stats = out.stats;
+    for (i, line) in &out.broken {
+        gha_write_broken_json(ctx, dt, *i, n, line)?;
+    }
+    gha_report_hour(ctx, dt, &stats, start.elapsed());
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod gha2db_tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+
+    // Writes hourly GHA files with given JSON lines into a fresh temporary directory, returns the directory
+    fn write_hours(name: &str, hours: &[(DateTime<Utc>, Vec<String>)]) -> String {
+        let dir = format!("{}/{name}/", std::env::temp_dir().display());
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (dt, lines) in hours {
+            let mut enc = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            for line in lines {
+                writeln!(enc, "{line}").unwrap();
+            }
+            std::fs::write(format!("{dir}{}", gha_file_name(*dt)), enc.finish().unwrap()).unwrap();
+        }
+        dir
+    }
+
+    fn event(id: i64, dt: DateTime<Utc>, repo_id: i64, repo: &str) -> String {
+        serde_json::json!({
+            "id": id.to_string(),
+            "type": "WatchEvent",
+            "public": true,
+            "created_at": dt,
+            "actor": {"id": 1, "login": "alice"},
+            "repo": {"id": repo_id, "name": repo},
+            "payload": {"action": "started"},
+        })
+        .to_string()
+    }
+
+    fn dt(h: i64) -> DateTime<Utc> {
+        parse_gha_time("2015-01-02T00:00:00Z").unwrap() + chrono::Duration::hours(h)
+    }
+
+    fn test_ctx(gha_dir: &str, ncpus: u16) -> Ctx {
+        Ctx {
+            gha_dir: gha_dir.to_string(),
+            db_out: false,
+            json_out: false,
+            ncpus,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_renamed_repos() {
+        // Repository 5 is renamed from acme/old to acme/new, 8 from acme/public to acme/secret,
+        // 11 from acme/beta to acme/gamma within an hour, 10 is created as acme/fresh
+        let created = serde_json::json!({
+            "id": "6",
+            "type": "CreateEvent",
+            "public": true,
+            "created_at": dt(1),
+            "actor": {"id": 1, "login": "alice"},
+            "repo": {"id": 10, "name": "acme/fresh"},
+            "payload": {"ref": null, "ref_type": "repository", "master_branch": "main"},
+        });
+        let dir = write_hours(
+            "gha2db_tests_renamed",
+            &[
+                (
+                    dt(0),
+                    vec![
+                        event(1, dt(0), 5, "acme/old"),
+                        event(2, dt(0), 6, "acme/other"),
+                        event(3, dt(0), 8, "acme/public"),
+                    ],
+                ),
+                (
+                    dt(1),
+                    vec![
+                        event(4, dt(1), 5, "acme/new"),
+                        event(5, dt(1), 8, "acme/secret"),
+                        created.to_string(),
+                        event(7, dt(1), 11, "acme/beta"),
+                        event(8, dt(1), 11, "acme/gamma"),
+                    ],
+                ),
+            ],
+        );
+        for ncpus in [1, 4] {
+            let run = |ctx: &Ctx, orgs: &str, repos: &str| {
+                let filter = GhaRepoFilter::new(ctx, orgs, repos);
+                let stats = gha2db(ctx, dt(0), dt(1), &filter).unwrap();
+                ((stats.written, stats.filtered_repo), filter.renamed(dt(0)))
+            };
+            let ctx = test_ctx(&dir, ncpus);
+
+            // New name selects events from before the rename, they are imported by catching up
+            let (stats, renamed) = run(&ctx, "acme/new", "");
+            assert_eq!(stats, (2, 6), "ncpus {ncpus}");
+            assert_eq!(renamed, HashMap::from([(5, dt(1))]), "ncpus {ncpus}");
+
+            // Old name selects the repository in later hours too
+            let (stats, renamed) = run(&ctx, "acme", "old");
+            assert_eq!(stats, (2, 6), "ncpus {ncpus}");
+            assert!(renamed.is_empty(), "ncpus {ncpus}");
+
+            // Hour is learned before it is filtered
+            let (stats, renamed) = run(&ctx, "acme/gamma", "");
+            assert_eq!(stats, (2, 6), "ncpus {ncpus}");
+            assert_eq!(renamed, HashMap::from([(11, dt(1))]), "ncpus {ncpus}");
+
+            // Repository created when it is first matched has no earlier events to catch up on
+            let (stats, renamed) = run(&ctx, "acme/fresh", "");
+            assert_eq!(stats, (1, 7), "ncpus {ncpus}");
+            assert!(renamed.is_empty(), "ncpus {ncpus}");
+
+            // Excluded name applies to later hours, but not to hours before it appears
+            let exclude = |name: &str| Ctx {
+                exclude_repos: HashMap::from([(name.to_string(), true)]),
+                ..test_ctx(&dir, ncpus)
+            };
+            let (stats, _) = run(&exclude("acme/public"), "", "");
+            assert_eq!(stats, (6, 2), "ncpus {ncpus}");
+            let (stats, _) = run(&exclude("acme/secret"), "", "");
+            assert_eq!(stats, (7, 1), "ncpus {ncpus}");
+
+            // Caught up events are appended to the hour's NDJSON file
+            let ctx = Ctx {
+                json_out: true,
+                json_ndjson: true,
+                json_dir: dir.clone(),
+                ..test_ctx(&dir, ncpus)
+            };
+            let (stats, _) = run(&ctx, "acme/new", "");
+            assert_eq!(stats, (2, 6), "ncpus {ncpus}");
+            for (h, id) in [(0, "1"), (1, "4")] {
+                let ndjson = std::fs::read_to_string(gha_ndjson_path(&ctx, dt(h))).unwrap();
+                let ids: Vec<String> = ndjson
+                    .lines()
+                    .map(|l| serde_json::from_str::<Event>(l).unwrap().id)
+                    .collect();
+                assert_eq!(ids, vec![id], "ncpus {ncpus}");
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_broken_json_ratio() {
+        let dir = write_hours(
+            "gha2db_tests_broken",
+            &[
+                (dt(0), vec![event(1, dt(0), 5, "acme/a"), event(2, dt(0), 5, "acme/a")]),
+                (dt(1), vec![event(3, dt(1), 5, "acme/a"), "{broken".to_string()]),
+            ],
+        );
+        for ncpus in [1, 4] {
+            let ctx = Ctx {
+                allow_broken_json: true,
+                max_broken_json_ratio: 0.5,
+                json_dir: dir.clone(),
+                ..test_ctx(&dir, ncpus)
+            };
+            let filter = GhaRepoFilter::new(&ctx, "", "");
+            let stats = gha2db(&ctx, dt(0), dt(1), &filter).unwrap();
+            assert_eq!((stats.written, stats.broken_json), (3, 1), "ncpus {ncpus}");
+
+            // Ratio is checked per hour: 1 of 4 JSONs is broken, but 1 of 2 in the second hour
+            let ctx = Ctx {
+                max_broken_json_ratio: 0.3,
+                ..ctx
+            };
+            let err = gha2db(&ctx, dt(0), dt(1), &filter).unwrap_err();
+            assert!(err.starts_with(&gha_file_name(dt(1))), "ncpus {ncpus}: {err}");
+            let err = gha2db(&ctx, dt(0), dt(0), &filter);
+            assert!(err.is_ok(), "ncpus {ncpus}");
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Events racing for shared rows: actors, org, repository, label and comments get different values from each event
+    fn racing_event(eid: i64, dt: DateTime<Utc>, repo: &str) -> String {
+        let path = format!(
+            "{}/tests/fixtures/gha/new/IssueCommentEvent.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut ev: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let user = |id: i64| serde_json::json!({"id": id, "login": format!("user{id}-{eid}")});
+        ev["id"] = eid.to_string().into();
+        ev["created_at"] = serde_json::json!(dt);
+        ev["actor"] = user(1001 + eid % 4);
+        ev["repo"]["name"] = repo.into();
+        ev["org"]["login"] = format!("acme-{eid}").into();
+        let issue = &mut ev["payload"]["issue"];
+        issue["user"] = user(1002);
+        let label = serde_json::json!({"id": 7001, "name": format!("bug-{eid}"), "color": "fc2929"});
+        issue["labels"] = serde_json::json!([label]);
+        let comment = &mut ev["payload"]["comment"];
+        comment["id"] = (9001 + eid % 3).into();
+        comment["user"] = user(1003 + eid % 2);
+        comment["body"] = format!("comment {eid}").into();
+        ev.to_string()
+    }
+
+    // Rows of gha_* tables after writing batches of events with gha_write_events semantics: an event is written once,
+    // shared rows keep values from the lowest event ID, other rows the first written ones, texts are appended
+    #[derive(Debug, Default, PartialEq)]
+    struct GhaTablesModel {
+        rows: BTreeMap<&'static str, BTreeMap<Vec<GhaValue>, Vec<GhaValue>>>,
+        texts: Vec<Vec<GhaValue>>,
+    }
+
+    impl GhaTablesModel {
+        fn write(&mut self, events: &[GhaEventRows]) {
+            let mut new = HashSet::new();
+            for ev in events {
+                let (table, values) = &ev.rows[0];
+                let rows = self.rows.entry(table.name).or_default();
+                if !rows.contains_key(&values[..table.key]) {
+                    rows.insert(values[..table.key].to_vec(), values.clone());
+                    new.insert(ev.eid);
+                }
+            }
+            for (table, rows) in gha_batch_rows(events, &new) {
+                for values in rows {
+                    if table.key == 0 {
+                        self.texts.push(values.to_vec());
+                        continue;
+                    }
+                    let key = values[..table.key].to_vec();
+                    match self.rows.entry(table.name).or_default().entry(key) {
+                        std::collections::btree_map::Entry::Vacant(row) => {
+                            row.insert(values.to_vec());
+                        }
+                        std::collections::btree_map::Entry::Occupied(mut row) => {
+                            if table.shared && values.last() < row.get().last() {
+                                row.insert(values.to_vec());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        fn sorted(mut self) -> Self {
+            self.texts.sort();
+            self
+        }
+    }
+
+    #[test]
+    fn test_threads_same_rows() {
+        let ctx = Ctx {
+            db_out: true,
+            ..test_ctx("", 4)
+        };
+        let filter = GhaRepoFilter::new(&ctx, "acme/gadget,acme/widget", "");
+        // Batches of JSON lines as readers send them to workers, hour 2 repeats an event from hour 0
+        let mut batches = vec![];
+        for h in 0..3 {
+            let mut lines: Vec<String> = (0..1200)
+                .map(|e| racing_event(100000 + h * 10000 + e, dt(h), "acme/widget"))
+                .collect();
+            if h == 2 {
+                lines.push(racing_event(100000, dt(0), "acme/widget"));
+            }
+            let lines: GhaLines = lines
+                .into_iter()
+                .enumerate()
+                .map(|(n, line)| (n + 1, line.into_bytes()))
+                .collect();
+            for chunk in lines.chunks(GHA_EVENTS_BATCH) {
+                batches.push((dt(h), chunk.to_vec()));
+            }
+        }
+        let rows = |i: usize| {
+            let (dt, lines) = &batches[i];
+            gha_process_batch(&ctx, None, *dt, lines.clone(), &filter).unwrap().events
+        };
+
+        let mut single = GhaTablesModel::default();
+        for i in 0..batches.len() {
+            single.write(&rows(i));
+        }
+        let single = single.sorted();
+        assert_eq!(single.rows["gha_events"].len(), 3600);
+        // Shared rows come from the earliest event
+        let label = &single.rows["gha_labels"][&gha_values![7001i64]];
+        assert!(label.contains(&"bug-100000".to_string().into()), "{label:?}");
+
+        // Workers take batches in any order and write them concurrently
+        for seed in 1..=4 {
+            let mut order: Vec<usize> = (0..batches.len()).collect();
+            rand_shuffle(&mut TimeRand::with_seed(seed), &mut order);
+            let next = AtomicUsize::new(0);
+            let multi = Mutex::new(GhaTablesModel::default());
+            thread::scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        if i >= order.len() {
+                            break;
+                        }
+                        let events = rows(order[i]);
+                        multi.lock().unwrap().write(&events);
+                    });
+                }
+            });
+            let multi = multi.into_inner().unwrap().sorted();
+            assert!(multi == single, "seed {seed}: order {order:?}");
+        }
+    }
+
+    // Creates a fresh migrated database ctx.pg_db, the server is given by PG_* variables
+    fn test_db(ctx: &Ctx) -> Client {
+        drop_test_db(&ctx.pg_db);
+        pg_conn(&Ctx {
+            pg_db: "postgres".to_string(),
+            ..Default::default()
+        })
+        .batch_execute(&format!("create database {}", ctx.pg_db))
+        .unwrap();
+        let mut client = pg_conn(ctx);
+        migrate(&mut client, ctx).unwrap();
+        client
+    }
+
+    fn drop_test_db(db: &str) {
+        pg_conn(&Ctx {
+            pg_db: "postgres".to_string(),
+            ..Default::default()
+        })
+        .batch_execute(&format!("drop database if exists {db}"))
+        .unwrap();
+    }
+
+    // All rows of all gha_* tables except import checkpoints, as sorted text
+    fn gha_tables_dump(client: &mut Client) -> BTreeMap<String, Vec<String>> {
+        let tables = client
+            .query(
+                "select table_name from information_schema.tables where table_schema = 'public' \
+                 and table_name like 'gha_%' and table_name not in ('gha_import_hours', 'gha_schema_migrations')",
+                &[],
+            )
+            .unwrap();
+        let mut dump = BTreeMap::new();
+        for row in tables {
+            let table: String = row.get(0);
+            let sql = format!("select t::text from {table} t order by 1");
+            let rows = client.query(&sql, &[]).unwrap();
+            dump.insert(table, rows.iter().map(|r| r.get(0)).collect());
+        }
+        dump
+    }
+
+    #[test]
+    #[ignore = "needs Postgres (PG_* variables), creates and drops test databases"]
+    fn test_threads_same_result() {
+        let hours: Vec<_> = (0..3)
+            .map(|h| {
+                let repo = if h < 2 { "acme/widget" } else { "acme/gadget" };
+                let lines = (0..1200)
+                    .map(|e| racing_event(100000 + h * 10000 + e, dt(h), repo))
+                    .collect();
+                (dt(h), lines)
+            })
+            .collect();
+        let dir = write_hours("gha2db_tests_threads", &hours);
+        let mut dumps = vec![];
+        for ncpus in [1, 4] {
+            let db = format!("devstats_test_threads_{ncpus}");
+            let ctx = Ctx {
+                pg_db: db.clone(),
+                db_out: true,
+                ..test_ctx(&dir, ncpus)
+            };
+            let mut conn = test_db(&ctx);
+            let filter = GhaRepoFilter::new(&ctx, "acme/gadget", "");
+            let stats = gha2db(&ctx, dt(0), dt(2), &filter).unwrap();
+            assert_eq!(stats.written, 3600, "ncpus {ncpus}");
+            dumps.push(gha_tables_dump(&mut conn));
+            drop(conn);
+            drop_test_db(&db);
+        }
+        assert_eq!(dumps[0].len(), dumps[1].len());
+        for (table, rows) in &dumps[0] {
+            assert!(&dumps[1][table] == rows, "{table} differs");
+        }
+        // Shared rows come from the earliest event
+        assert!(dumps[0]["gha_labels"][0].contains("bug-100000"), "{:?}", dumps[0]["gha_labels"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs Postgres (PG_* variables), creates and drops a test database"]
+    fn test_json_out_existing() {
+        let lines = (0..3).map(|e| event(e, dt(0), 5, "acme/a")).collect();
+        let dir = write_hours("gha2db_tests_json_existing", &[(dt(0), lines)]);
+        for ncpus in [1, 4] {
+            let ctx = Ctx {
+                pg_db: "devstats_test_json_existing".to_string(),
+                db_out: true,
+                json_out: true,
+                json_ndjson: true,
+                json_dir: dir.clone(),
+                ..test_ctx(&dir, ncpus)
+            };
+            let client = test_db(&ctx);
+            let filter = GhaRepoFilter::new(&ctx, "", "");
+            let stats = gha2db(&ctx, dt(0), dt(0), &filter).unwrap();
+            assert_eq!((stats.written, stats.existing), (3, 0), "ncpus {ncpus}");
+            std::fs::remove_file(gha_ndjson_path(&ctx, dt(0))).unwrap();
+
+            // Events already in the database are still written as JSONs
+            let ctx = Ctx {
+                force_start_date: true,
+                ..ctx
+            };
+            let stats = gha2db(&ctx, dt(0), dt(0), &filter).unwrap();
+            assert_eq!((stats.written, stats.existing), (0, 3), "ncpus {ncpus}");
+            let ndjson = std::fs::read_to_string(gha_ndjson_path(&ctx, dt(0))).unwrap();
+            assert_eq!(ndjson.lines().count(), 3, "ncpus {ncpus}");
+            drop(client);
+            drop_test_db(&ctx.pg_db);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs Postgres (PG_* variables), creates and drops a test database"]
+    fn test_renamed_catch_up_checkpoints() {
+        let dir = write_hours(
+            "gha2db_tests_catch_up",
+            &[
+                (dt(0), vec![event(1, dt(0), 5, "acme/old"), event(2, dt(0), 6, "acme/other")]),
+                (dt(1), vec![event(3, dt(1), 6, "acme/other")]),
+                (dt(2), vec![event(4, dt(2), 5, "acme/new")]),
+            ],
+        );
+        let ctx = Ctx {
+            pg_db: "devstats_test_catch_up".to_string(),
+            db_out: true,
+            ..test_ctx(&dir, 1)
+        };
+        let mut client = test_db(&ctx);
+        let filter = GhaRepoFilter::new(&ctx, "acme/new", "");
+        let stats = gha2db(&ctx, dt(0), dt(2), &filter).unwrap();
+        assert_eq!((stats.written, stats.filtered_repo), (2, 2));
+        let hours: Vec<(String, i64, i64)> = client
+            .query("select status, events, written from gha_import_hours order by dt", &[])
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect();
+        let done = |events, written| ("done".to_string(), events, written);
+        assert_eq!(hours, vec![done(2, 1), done(1, 0), done(1, 1)]);
+
+        // Old name is known from gha_repos now, nothing to catch up on
+        let ctx = Ctx {
+            force_start_date: true,
+            ..ctx
+        };
+        let filter = GhaRepoFilter::new(&ctx, "acme/new", "");
+        let stats = gha2db(&ctx, dt(0), dt(2), &filter).unwrap();
+        assert_eq!((stats.written, stats.existing, stats.filtered_repo), (0, 2, 2));
+        assert!(filter.renamed(dt(0)).is_empty());
+
+        // Hours imported with another filter are not skipped
+        let ctx = Ctx {
+            force_start_date: false,
+            ..ctx
+        };
+        let stats = gha2db(&ctx, dt(0), dt(2), &GhaRepoFilter::new(&ctx, "acme/new", "")).unwrap();
+        assert_eq!((stats.imported, stats.files), (3, 0));
+        let stats = gha2db(&ctx, dt(0), dt(2), &GhaRepoFilter::new(&ctx, "", "")).unwrap();
+        assert_eq!((stats.imported, stats.files, stats.written), (0, 3, 2));
+        drop(client);
+        drop_test_db(&ctx.pg_db);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+// Opens hourly file from GHA2DB_GHA_DIR, None if there is no such file
+fn gha_file_local(ctx: &Ctx, dt: DateTime<Utc>) -> Result<Option<File>, String> {
+    let path = format!("{}{}", ctx.gha_dir, gha_file_name(dt));
+    match File::open(&path) {
+        Ok(f) => Ok(Some(f)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{path}: {e}")),
+    }
+}
+
+// Stream of decompressed lines of a gzipped hourly file
+pub fn gha_reader(f: File) -> Box<dyn BufRead> {
+    Box::new(BufReader::new(MultiGzDecoder::new(BufReader::new(f))))
+}
+
+// Opens hourly file as a stream of decompressed lines, see gha_open_file
+pub fn gha_open(ctx: &Ctx, dt: DateTime<Utc>) -> Result<Option<Box<dyn BufRead>>, String> {
+    Ok(gha_open_file(ctx, dt)?.map(gha_reader))
+}
+
+// Opens gzipped hourly file: from GHA2DB_GHA_DIR if set, otherwise from cache or by downloading it
+// None means that there is no data for this hour
+// Downloads are spooled to disk (GHA2DB_GHA_CACHE or a temporary file), so archives are never held in memory
+// and the file can be read again (rewound) without downloading it twice
+pub fn gha_open_file(ctx: &Ctx, dt: DateTime<Utc>) -> Result<Option<File>, String> {
+    if !ctx.gha_dir.is_empty() {
+        return gha_file_local(ctx, dt);
+    }
+    let name = gha_file_name(dt);
+    let path = if ctx.gha_cache.is_empty() {
+        format!("{}/devstats-{}-{name}", std::env::temp_dir().display(), std::process::id())
+    } else {
+        let path = format!("{}{name}", ctx.gha_cache);
+        if let Some(f) = gha_cache_open(ctx, &path)? {
+            if ctx.debug > 0 {
+                printf(&format!("{name}: using cached {path}"));
+            }
+            return Ok(Some(f));
+        }
+        std::fs::create_dir_all(&ctx.gha_cache).map_err(|e| format!("{}: {e}", ctx.gha_cache))?;
+        path
+    };
+    // Cache never has partial files: download goes to a temporary file renamed once verified
+    let tmp = format!("{path}.tmp");
+    match gha_fetch(ctx, dt, &tmp) {
+        Ok(true) => {}
+        res => {
+            let _ = std::fs::remove_file(&tmp);
+            return res.map(|_| None);
+        }
+    }
+    std::fs::rename(&tmp, &path).map_err(|e| format!("{path}: {e}"))?;
+    let f = File::open(&path).map_err(|e| format!("{path}: {e}"))?;
+    if ctx.gha_cache.is_empty() {
+        // Already opened, data stays readable until the file is closed
+        let _ = std::fs::remove_file(&path);
+    }
+    Ok(Some(f))
+}
+
+// Opens cached file if it is a complete gzip stream, a broken one is removed so the hour is downloaded again
+fn gha_cache_open(ctx: &Ctx, path: &str) -> Result<Option<File>, String> {
+    let f = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{path}: {e}")),
+    };
+    if let Err(e) = gzip_verify(BufReader::new(f)) {
+        printf(&format!("{path}: removing broken cached file: {e}"));
+        std::fs::remove_file(path).map_err(|e| format!("{path}: {e}"))?;
+        return Ok(None);
+    }
+    if ctx.debug > 1 {
+        printf(&format!("{path}: cached file verified"));
+    }
+    File::open(path).map(Some).map_err(|e| format!("{path}: {e}"))
+}
+
+// Checks that data is a complete gzip stream (truncated downloads fail here)
+pub fn gzip_verify<R: Read>(data: R) -> Result<(), String> {
+    let mut decoder = MultiGzDecoder::new(data);
+    std::io::copy(&mut decoder, &mut std::io::sink())
+        .map(|_| ())
+        .map_err(|e| format!("gzip integrity check failed: {e}"))
+}
+
+// Downloads hourly file from GHA2DB_GHA_URL into `path`, returns false when the hour is missing (HTTP 404)
+// Retries GHA2DB_HTTP_RETRY times, N-th try uses N*GHA2DB_HTTP_TIMEOUT minutes timeout
+pub fn gha_fetch(ctx: &Ctx, dt: DateTime<Utc>, path: &str) -> Result<bool, String> {
+    let url = format!("{}{}", ctx.gha_url, gha_file_name(dt));
+    let timeout = Duration::from_secs(60 * ctx.http_timeout.max(1) as u64);
+    gha_fetch_url(&url, path, ctx.http_retry.max(1) as u32, timeout)
+}
+
+// Downloads url into `path` making up to `tries` tries, N-th try uses N*timeout
+fn gha_fetch_url(url: &str, path: &str, tries: u32, timeout: Duration) -> Result<bool, String> {
+    let mut last_err = String::new();
+    for n in 1..=tries {
+        let timeout = timeout * n;
+        match gha_fetch_once(url, path, timeout) {
+            Ok(found) => return Ok(found),
+            Err(e) => {
+                printf(&format!(
+                    "{url}: try {n}/{tries} (timeout {timeout:?}) failed: {e}"
+                ));
+                last_err = e;
+            }
+        }
+    }
+    Err(format!("{url}: giving up after {tries} tries: {last_err}"))
+}
+
+// Streams response body to `path` and verifies it, the body is never held in memory
+fn gha_fetch_once(url: &str, path: &str, timeout: Duration) -> Result<bool, String> {
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    let response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(false),
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut f = File::create(path).map_err(|e| format!("{path}: {e}"))?;
+    std::io::copy(&mut response.into_reader(), &mut f).map_err(|e| e.to_string())?;
+    let f = File::open(path).map_err(|e| format!("{path}: {e}"))?;
+    gzip_verify(BufReader::new(f))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod gharchive_tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::net::TcpListener;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut enc = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    // Minimal HTTP server answering `responses` requests in order: (delay, status, body), returns base URL
+    fn http_stand_in(responses: Vec<(Duration, u16, Vec<u8>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for (delay, status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 0) && line != "\r\n" {
+                    line.clear();
+                }
+                thread::sleep(delay);
+                let head = format!(
+                    "HTTP/1.1 {status} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(&body));
+            }
+        });
+        url
+    }
+
+    fn test_ctx(url: &str, retry: i8) -> Ctx {
+        Ctx {
+            gha_url: url.to_string(),
+            gha_dir: "".to_string(),
+            gha_cache: "".to_string(),
+            http_retry: retry,
+            http_timeout: 1,
+            ..Default::default()
+        }
+    }
+
+    fn read_all(mut r: Box<dyn BufRead>) -> String {
+        let mut s = String::new();
+        r.read_to_string(&mut s).unwrap();
+        s
+    }
+
+    fn dt() -> DateTime<Utc> {
+        parse_gha_time("2015-01-02T03:00:00Z").unwrap()
+    }
+
+    // Downloads into a temporary file and returns its contents
+    fn fetch(
+        name: &str,
+        f: impl FnOnce(&str) -> Result<bool, String>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let path = format!("{}/gharchive_tests_{name}", std::env::temp_dir().display());
+        let res = f(&path).map(|found| found.then(|| std::fs::read(&path).unwrap()));
+        let _ = std::fs::remove_file(&path);
+        res
+    }
+
+    #[test]
+    fn test_fetch_missing_hour() {
+        let url = http_stand_in(vec![(Duration::ZERO, 404, b"not found".to_vec())]);
+        let res = fetch("missing", |p| gha_fetch(&test_ctx(&url, 3), dt(), p));
+        assert_eq!(res, Ok(None));
+    }
+
+    #[test]
+    fn test_fetch_retries() {
+        let data = gzip(b"{}\n");
+        let url = http_stand_in(vec![
+            (Duration::ZERO, 500, vec![]),
+            (Duration::ZERO, 503, vec![]),
+            (Duration::ZERO, 200, data.clone()),
+        ]);
+        let res = fetch("retries", |p| gha_fetch(&test_ctx(&url, 3), dt(), p));
+        assert_eq!(res, Ok(Some(data)));
+        let url = http_stand_in(vec![(Duration::ZERO, 500, vec![]), (Duration::ZERO, 500, vec![])]);
+        let err = fetch("retries", |p| gha_fetch(&test_ctx(&url, 2), dt(), p)).unwrap_err();
+        assert!(err.contains("giving up after 2 tries"), "{err}");
+    }
+
+    #[test]
+    fn test_fetch_growing_timeout() {
+        // First try times out after 300ms, the second one waits 600ms which is enough
+        let data = gzip(b"{}\n");
+        let url = http_stand_in(vec![
+            (Duration::from_millis(450), 200, data.clone()),
+            (Duration::from_millis(450), 200, data.clone()),
+        ]);
+        let url = format!("{url}{}", gha_file_name(dt()));
+        let timeout = Duration::from_millis(300);
+        let res = fetch("timeout", |p| gha_fetch_url(&url, p, 2, timeout));
+        assert_eq!(res, Ok(Some(data.clone())));
+        let url = http_stand_in(vec![(Duration::from_millis(450), 200, data)]);
+        assert!(fetch("timeout", |p| gha_fetch_url(&url, p, 1, timeout)).is_err());
+    }
+
+    #[test]
+    fn test_fetch_truncated() {
+        let data = gzip("{\"id\": \"1\"}\n".repeat(100).as_bytes());
+        let truncated = data[..data.len() / 2].to_vec();
+        assert!(gzip_verify(truncated.as_slice()).is_err());
+        let url = http_stand_in(vec![(Duration::ZERO, 200, truncated)]);
+        let err = fetch("truncated", |p| gha_fetch(&test_ctx(&url, 1), dt(), p)).unwrap_err();
+        assert!(err.contains("gzip integrity check failed"), "{err}");
+    }
+
+    #[test]
+    fn test_broken_cache_refetched() {
+        let cache = format!("{}/gharchive_tests_cache/", std::env::temp_dir().display());
+        let _ = std::fs::remove_dir_all(&cache);
+        std::fs::create_dir_all(&cache).unwrap();
+        let path = format!("{cache}{}", gha_file_name(dt()));
+        let data = gzip(b"{\"id\": \"2\"}\n");
+        std::fs::write(&path, &data[..data.len() - 4]).unwrap();
+        let url = http_stand_in(vec![(Duration::ZERO, 200, data.clone())]);
+        let ctx = Ctx {
+            gha_cache: cache.clone(),
+            ..test_ctx(&url, 1)
+        };
+        assert_eq!(read_all(gha_open(&ctx, dt()).unwrap().unwrap()), "{\"id\": \"2\"}\n");
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        // Verified cached file is used without downloading (stand-in server has no more responses)
+        assert_eq!(read_all(gha_open(&ctx, dt()).unwrap().unwrap()), "{\"id\": \"2\"}\n");
+        std::fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn test_open_without_cache() {
+        let data = gzip(b"{\"id\": \"3\"}\n");
+        let url = http_stand_in(vec![(Duration::ZERO, 200, data), (Duration::ZERO, 404, vec![])]);
+        let ctx = test_ctx(&url, 1);
+        assert_eq!(read_all(gha_open(&ctx, dt()).unwrap().unwrap()), "{\"id\": \"3\"}\n");
+        assert!(gha_open(&ctx, dt()).unwrap().is_none());
+        // Temporary download is removed
+        let tmp = format!("{}/devstats-{}-", std::env::temp_dir().display(), std::process::id());
+        assert!(!std::path::Path::new(&format!("{tmp}{}", gha_file_name(dt()))).exists());
+        assert!(!std::path::Path::new(&format!("{tmp}{}.tmp", gha_file_name(dt()))).exists());
+    }
+}
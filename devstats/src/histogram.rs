@@ -0,0 +1,329 @@
+// Histogram metrics are calculated for quick ranges instead of periods, each range replaces all its previous points
+// Rows are written in SQL order with times starting at HIST_TIME, one hour apart, period column holds the range suffix
+
+const HIST_TIME: &str = "2014-01-01 00:00:00";
+
+// Quick range: "last <period>" ending now or a range between annotations (releases)
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuickRange {
+    pub suffix: String, // Written as period: d, w, m, q, y, y10, anno_<i>_<i+1> or anno_<i>_now
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub past: bool, // Range between two annotations never changes, it is calculated once (unless GHA2DB_RESETRANGES or GHA2DB_RESETTSDB)
+}
+
+// "Last <period>" range ending at the end of the hour containing now
+pub fn period_quick_range(period: &str, now: DateTime<Utc>) -> Result<QuickRange, String> {
+    let (base, n) = parse_period(period)?;
+    let to = hour_start(now) + chrono::Duration::hours(1);
+    Ok(QuickRange {
+        suffix: period.to_string(),
+        from: period_add(to, base, -(n as i32)),
+        to,
+        past: false,
+    })
+}
+
+// Ranges between consecutive annotations ("sannotations" series) and from each annotation to now
+pub fn annotations_quick_ranges(client: &mut Client, ctx: &Ctx, now: DateTime<Utc>) -> Result<Vec<QuickRange>, String> {
+    let rows = query_sql_with_err(client, ctx, "select to_regclass('sannotations') is not null", &[])
+        .map_err(|e| e.to_string())?;
+    if !rows.first().is_some_and(|row| row.get::<_, bool>(0)) {
+        return Ok(vec![]);
+    }
+    let rows = query_sql_with_err(client, ctx, "select distinct time from sannotations order by time", &[])
+        .map_err(|e| format!("sannotations: {e}"))?;
+    let times: Vec<DateTime<Utc>> = rows.iter().map(|row| row.get::<_, NaiveDateTime>(0).and_utc()).collect();
+    let now = hour_start(now) + chrono::Duration::hours(1);
+    let mut ranges = Vec::new();
+    for (i, from) in times.iter().enumerate() {
+        if let Some(to) = times.get(i + 1) {
+            ranges.push(QuickRange {
+                suffix: format!("anno_{i}_{}", i + 1),
+                from: *from,
+                to: *to,
+                past: true,
+            });
+        }
+        ranges.push(QuickRange {
+            suffix: format!("anno_{i}_now"),
+            from: *from,
+            to: now,
+            past: false,
+        });
+    }
+    Ok(ranges)
+}
+
+// Quick ranges for histogram metric: comma separated periods ("d,w,m") and/or "anno" for annotations ranges
+pub fn histogram_quick_ranges(
+    client: &mut Client,
+    ctx: &Ctx,
+    periods: &str,
+    now: DateTime<Utc>,
+) -> Result<Vec<QuickRange>, String> {
+    let mut ranges = Vec::new();
+    for period in periods.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        if period == "anno" {
+            ranges.extend(annotations_quick_ranges(client, ctx, now)?);
+        } else {
+            ranges.push(period_quick_range(period, now)?);
+        }
+    }
+    Ok(ranges)
+}
+
+// Converts histogram SQL result into points: first column is the name, second the value (or values named as columns)
+pub fn histogram_points(metric: &Metric, range: &QuickRange, res: &TextRows) -> Result<Vec<TsPoint>, String> {
+    if res.columns.len() < 2 {
+        return Err(format!("{}: histogram SQL must return name and value columns", metric.sql));
+    }
+    let name = histogram_name(metric);
+    let t0 = time_parse_any(HIST_TIME)?;
+    let mut points = Vec::new();
+    for (i, row) in res.rows.iter().enumerate() {
+        let mut p = TsPoint::new(t0 + chrono::Duration::hours(i as i64), &name, &range.suffix);
+        let label = row[0].clone().unwrap_or_default();
+        p.fields.insert("name".to_string(), TsValue::Text(label));
+        for (j, col) in res.columns.iter().enumerate().skip(1) {
+            if let Some(v) = &row[j] {
+                let field = if res.columns.len() == 2 {
+                    "value".to_string()
+                } else {
+                    metric_field_name(metric, col)
+                };
+                p.fields.insert(field, TsValue::parse(v));
+            }
+        }
+        if !metric.desc.is_empty() {
+            p.fields.insert("descr".to_string(), TsValue::Text(metric.desc.clone()));
+        }
+        points.push(p);
+    }
+    Ok(points)
+}
+
+// Series (table) name of a histogram metric
+fn histogram_name(metric: &Metric) -> String {
+    normalize_name(&metric.series_name_or_func)
+}
+
+// gha_computed key of a past range
+fn histogram_computed_key(metric: &Metric, range: &QuickRange) -> String {
+    format!("hist:{}:{}", histogram_name(metric), range.suffix)
+}
+
+// Calculates a single quick range and replaces its points, past ranges already calculated are skipped
+// Queries run concurrently, writes are serialized by the writer lock: all ranges share one table which may not exist yet
+fn calc_histogram_range(
+    client: &mut Client,
+    ctx: &Ctx,
+    metric: &Metric,
+    sql: &str,
+    range: &QuickRange,
+    writer: &Mutex<TsWriter>,
+) -> Result<MetricStats, String> {
+    let mut stats = MetricStats::default();
+    let key = histogram_computed_key(metric, range);
+    let dt = range.to.naive_utc();
+    if range.past && !ctx.reset_ranges && !ctx.reset_tsdb {
+        let rows = query_sql_with_err(
+            client,
+            ctx,
+            "select 1 from gha_computed where metric = $1 and dt = $2",
+            &[&key, &dt],
+        )
+        .map_err(|e| e.to_string())?;
+        if !rows.is_empty() {
+            return Ok(stats);
+        }
+    }
+    let rendered = render_metric_sql(ctx, metric, sql, range.from, range.to, 1);
+    let res = text_query_sql_with_err(client, ctx, &rendered)
+        .map_err(|e| format!("{} {}: {e}", metric.sql, range.suffix))?;
+    let points = histogram_points(metric, range, &res)?;
+    stats.intervals = 1;
+    stats.rows = res.rows.len() as u64;
+    stats.points = points.len() as u64;
+    if ctx.skip_tsdb {
+        return Ok(stats);
+    }
+    let mut writer = writer.lock().expect("writer mutex poisoned");
+    batch_sql_with_err(client, ctx, "begin").map_err(|e| e.to_string())?;
+    let res = delete_ts_period(client, ctx, &histogram_name(metric), &range.suffix)
+        .and_then(|_| writer.write(client, ctx, &points))
+        .and_then(|written| {
+            if range.past {
+                exec_sql_with_err(
+                    client,
+                    ctx,
+                    "insert into gha_computed(metric, dt) values($1, $2) on conflict do nothing",
+                    &[&key, &dt],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            Ok(written)
+        });
+    match res {
+        Ok(written) => {
+            stats.written = written;
+            batch_sql_with_err(client, ctx, "commit").map_err(|e| e.to_string())?;
+            Ok(stats)
+        }
+        Err(e) => {
+            let _ = batch_sql_with_err(client, ctx, "rollback");
+            Err(format!("{} {}: {e}", metric.sql, range.suffix))
+        }
+    }
+}
+
+// Calculates histogram metric for all given quick ranges, up to GHA2DB_MAX_HIST ranges at once (0 - all of them)
+pub fn calc_histogram(ctx: &Ctx, metric: &Metric, ranges: &[QuickRange]) -> Result<MetricStats, String> {
+    if ranges.is_empty() {
+        return Ok(MetricStats::default());
+    }
+    let path = metric_sql_file(ctx, metric);
+    let sql = std::fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+    let thrn = match ctx.max_histograms {
+        n if n > 0 => (n as usize).min(ranges.len()),
+        _ => ranges.len(),
+    };
+    let pool = PgPool::with_size(ctx, thrn);
+    let next = AtomicUsize::new(0);
+    let stats = Mutex::new(MetricStats::default());
+    let failed: Mutex<Option<String>> = Mutex::new(None);
+    let writer = Mutex::new(TsWriter::default());
+    thread::scope(|s| {
+        for _ in 0..thrn {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= ranges.len() || failed.lock().expect("failed mutex poisoned").is_some() {
+                    break;
+                }
+                let res = pool
+                    .get_with_err()
+                    .map_err(|e| e.to_string())
+                    .and_then(|mut conn| calc_histogram_range(&mut conn, ctx, metric, &sql, &ranges[i], &writer));
+                match res {
+                    Ok(range) => {
+                        let mut stats = stats.lock().expect("stats mutex poisoned");
+                        stats.intervals += range.intervals;
+                        stats.rows += range.rows;
+                        stats.points += range.points;
+                        stats.written += range.written;
+                    }
+                    Err(e) => {
+                        failed.lock().expect("failed mutex poisoned").get_or_insert(e);
+                    }
+                }
+            });
+        }
+    });
+    match failed.into_inner().expect("failed mutex poisoned") {
+        Some(e) => Err(e),
+        None => Ok(stats.into_inner().expect("stats mutex poisoned")),
+    }
+}
+
+#[cfg(test)]
+mod histogram_tests {
+    use super::*;
+
+    fn rows(columns: &[&str], rows: &[&[Option<&str>]]) -> TextRows {
+        TextRows {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: rows
+                .iter()
+                .map(|r| r.iter().map(|v| v.map(|v| v.to_string())).collect())
+                .collect(),
+        }
+    }
+
+    fn range(suffix: &str) -> QuickRange {
+        let now = time_parse_any("2021-03-17 10:30:00").unwrap();
+        QuickRange {
+            suffix: suffix.to_string(),
+            ..period_quick_range("d", now).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_histogram_points() {
+        let metric = Metric {
+            sql: "hist_langs".to_string(),
+            series_name_or_func: "Hist Languages".to_string(),
+            ..Default::default()
+        };
+        let res = rows(
+            &["name", "value"],
+            &[&[Some("Go"), Some("3")], &[Some("Rust"), None], &[None, Some("x")]],
+        );
+        let points = histogram_points(&metric, &range("w"), &res).unwrap();
+        // Rows keep SQL order as times one hour apart starting at HIST_TIME
+        let t0 = time_parse_any(HIST_TIME).unwrap();
+        let hours: Vec<i64> = points.iter().map(|p| (p.t - t0).num_hours()).collect();
+        assert_eq!(hours, [0, 1, 2]);
+        for p in &points {
+            assert_eq!((p.name.as_str(), p.series.as_str(), p.period.as_str()), ("hist_languages", "", "w"));
+        }
+        let text = |s: &str| TsValue::Text(s.to_string());
+        assert_eq!(points[0].fields["name"], text("Go"));
+        assert_eq!(points[0].fields["value"], TsValue::Float(3.0));
+        // Missing values are not written, missing names are empty
+        assert_eq!(points[1].fields.keys().collect::<Vec<_>>(), ["name"]);
+        assert_eq!(points[2].fields["name"], text(""));
+        assert_eq!(points[2].fields["value"], text("x"));
+        assert!(points.iter().all(|p| !p.fields.contains_key("descr")));
+
+        // More columns are named as result columns (normalized with escape_value_name), desc goes to descr
+        let metric = Metric {
+            desc: "Languages used".to_string(),
+            escape_value_name: true,
+            ..metric
+        };
+        let res = rows(&["name", "PRs Opened", "Issues"], &[&[Some("Go"), Some("3"), Some("4")]]);
+        let points = histogram_points(&metric, &range("anno_0_1"), &res).unwrap();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].period, "anno_0_1");
+        let fields = &points[0].fields;
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["descr", "issues", "name", "prs_opened"]);
+        assert_eq!(fields["prs_opened"], TsValue::Float(3.0));
+        assert_eq!(fields["issues"], TsValue::Float(4.0));
+        assert_eq!(fields["descr"], text("Languages used"));
+        assert!(histogram_points(&metric, &range("w"), &rows(&["name", "value"], &[])).unwrap().is_empty());
+
+        let err = histogram_points(&metric, &range("w"), &rows(&["name"], &[&[Some("Go")]])).unwrap_err();
+        assert_eq!(err, "hist_langs: histogram SQL must return name and value columns");
+    }
+
+    #[test]
+    fn test_period_quick_range() {
+        let now = time_parse_any("2021-03-17 10:30:00").unwrap();
+        let to = time_parse_any("2021-03-17 11:00:00").unwrap();
+        for (period, from) in [
+            ("h", "2021-03-17 10:00:00"),
+            ("d", "2021-03-16 11:00:00"),
+            ("w", "2021-03-10 11:00:00"),
+            ("d7", "2021-03-10 11:00:00"),
+            ("m", "2021-02-17 11:00:00"),
+            ("q", "2020-12-17 11:00:00"),
+            ("y", "2020-03-17 11:00:00"),
+            ("y10", "2011-03-17 11:00:00"),
+        ] {
+            let range = period_quick_range(period, now).unwrap();
+            let expected = QuickRange {
+                suffix: period.to_string(),
+                from: time_parse_any(from).unwrap(),
+                to,
+                past: false,
+            };
+            assert_eq!(range, expected, "{period}");
+        }
+        // Exactly at the hour start the range still ends at the end of that hour
+        let range = period_quick_range("d", time_parse_any("2021-03-17 11:00:00").unwrap()).unwrap();
+        assert_eq!(range.to, to + chrono::Duration::hours(1));
+        for period in ["", "x", "anno", "é"] {
+            assert!(period_quick_range(period, now).is_err(), "{period:?}");
+        }
+    }
+}
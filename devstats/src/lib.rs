@@ -5,4 +5,21 @@ pub mod lib {
     include!("log.rs");
     include!("string.rs");
     include!("env.rs");
+    include!("pgconn.rs");
+    include!("bulk.rs");
+    include!("structure.rs");
+    include!("migrations.rs");
+    include!("gha.rs");
+    include!("bots.rs");
+    include!("metrics.rs");
+    include!("periods.rs");
+    include!("tsdb.rs");
+    include!("calc_metric.rs");
+    include!("histogram.rs");
+    include!("skip_dates.rs");
+    include!("gharchive.rs");
+    include!("gha2db.rs");
+    include!("checkpoints.rs");
+    include!("exec.rs");
+    include!("sync.rs");
 }
@@ -5,9 +5,14 @@ pub fn fatal_no_log<T, E: std::fmt::Debug>(res: &Result<T, E>) {
     }
 }
 
-fn fatal_no_log_str(res: Result<(), String>) {
+pub fn fatal_no_log_str(res: Result<(), String>) {
     match res {
         Ok(_) => {}
         Err(_) => fatal_no_log(&res),
     }
 }
+
+// Prints message prefixed with the current UTC date-time
+pub fn printf(msg: &str) {
+    println!("{}: {}", Utc::now().format("%Y-%m-%d %H:%M:%S"), msg);
+}
@@ -0,0 +1,277 @@
+// metrics.yaml: metrics calculated by gha2db_sync/calc_metric
+// metrics:
+//   - name: Activity repo groups
+//     sql: activity_repo_groups
+//     series_name_or_func: multi_row_single_column
+//     periods: d,w,m,q,y
+//     aggregate: 1,7
+//     skip: w7,m7,q7,y7
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsYaml {
+    pub metrics: Vec<Metric>,
+}
+
+// Single metric definition, comma separated lists are split and validated when parsing
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Metric {
+    pub name: String, // Metric description used in logs
+    pub sql: String, // SQL file name without extension: metrics/<project>/<sql>.sql
+    pub series_name_or_func: String, // Series name, or prefix/function name for multi_value and histogram metrics
+    #[serde(default, deserialize_with = "de_metric_periods")]
+    pub periods: Vec<String>, // Periods to calculate: h, d, w, m, q, y, optionally followed by a number of units (y10)
+    #[serde(default, deserialize_with = "de_metric_aggregate")]
+    pub aggregate: Vec<u32>, // Moving average windows, empty means 1 (no aggregation)
+    #[serde(default, deserialize_with = "de_metric_list")]
+    pub skip: Vec<String>, // Period + aggregate combinations to skip, like "w7"
+    #[serde(default)]
+    pub add_period_to_name: bool, // Append "_<period>" to series name
+    #[serde(default)]
+    pub histogram: bool, // Histogram metric: calculated for quick ranges, series replaced on each run
+    #[serde(default)]
+    pub desc: String, // Description stored in the "descr" column of histogram and multi_value series
+    #[serde(default)]
+    pub multi_value: bool, // SQL (name, value) rows are written as columns of one series_name_or_func series
+    #[serde(default)]
+    pub escape_value_name: bool, // Normalize value names returned by SQL into column names
+    #[serde(default)]
+    pub annotations_ranges: bool, // Calculate for annotations (releases) ranges instead of periods
+    #[serde(default)]
+    pub merge_series: String, // Write all values into this single series instead of one series per name
+    #[serde(default)]
+    pub custom_data: bool, // SQL uses custom data, not gha_* tables events, always recalculate
+    #[serde(default, deserialize_with = "de_metric_time_opt")]
+    pub start_from: Option<DateTime<Utc>>, // Don't calculate before this date, instead of project start date
+    #[serde(default)]
+    pub last_hours: i32, // Only calculate last N hours on each sync
+    #[serde(default)]
+    pub series_name_map: HashMap<String, String>, // Rename series returned by SQL, keys are names as returned (before normalize_name)
+    #[serde(default)]
+    pub env: HashMap<String, String>, // Environment overrides used when calculating this metric
+    #[serde(default)]
+    pub disabled: bool, // Skip this metric
+    #[serde(default)]
+    pub drop: String, // Series (or series prefixes) to drop before calculating, with GHA2DB_ENABLE_METRICS_DROP
+    #[serde(default)]
+    pub project: String, // Only calculate for this project (shared metrics files)
+    #[serde(default)]
+    pub project_scale: Option<f64>, // Overrides GHA2DB_PROJECT_SCALE for this metric
+}
+
+impl Metric {
+    // Aggregate windows to calculate, at least one
+    pub fn aggregates(&self) -> Vec<u32> {
+        if self.aggregate.is_empty() {
+            return vec![1];
+        }
+        self.aggregate.clone()
+    }
+
+    // True if given period with aggregate window is listed in skip, aggregate 1 is written without a number ("w", not "w1")
+    pub fn skips(&self, period: &str, aggregate: u32) -> bool {
+        let key = if aggregate == 1 {
+            period.to_string()
+        } else {
+            format!("{period}{aggregate}")
+        };
+        self.skip.contains(&key)
+    }
+}
+
+fn metric_list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+fn de_metric_list<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    Ok(metric_list(&String::deserialize(d)?))
+}
+
+fn de_metric_periods<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    static PERIOD_RE: OnceLock<Regex> = OnceLock::new();
+    let re = PERIOD_RE.get_or_init(|| Regex::new(r"^[hdwmqy]([1-9][0-9]*)?$").expect("invalid period regexp"));
+    let periods = metric_list(&String::deserialize(d)?);
+    if let Some(p) = periods.iter().find(|p| !re.is_match(p)) {
+        return Err(serde::de::Error::custom(format!(
+            "invalid period {p:?}, expected one of h, d, w, m, q, y optionally followed by a number"
+        )));
+    }
+    Ok(periods)
+}
+
+fn de_metric_aggregate<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u32>, D::Error> {
+    // Single aggregate can be a YAML number
+    let s = match serde_yaml::Value::deserialize(d)? {
+        serde_yaml::Value::Number(n) => n.to_string(),
+        serde_yaml::Value::String(s) => s,
+        _ => {
+            return Err(serde::de::Error::custom(
+                "invalid aggregate, expected a number or comma separated numbers",
+            ))
+        }
+    };
+    metric_list(&s)
+        .iter()
+        .map(|a| match string_to_num::<u32>(a) {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(serde::de::Error::custom(format!(
+                "invalid aggregate {a:?}, expected positive number"
+            ))),
+        })
+        .collect()
+}
+
+fn de_metric_time_opt<'de, D: Deserializer<'de>>(d: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+    match Option::<String>::deserialize(d)? {
+        Some(s) => time_parse_any(&s).map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+// Parses metrics.yaml contents, errors include line and column of the offending entry
+pub fn parse_metrics_yaml(data: &str) -> Result<MetricsYaml, String> {
+    let yaml: MetricsYaml = serde_yaml::from_str(data).map_err(|e| e.to_string())?;
+    let mut seen = HashSet::new();
+    for m in &yaml.metrics {
+        let key = (m.sql.as_str(), m.series_name_or_func.as_str(), m.project.as_str());
+        if !seen.insert(key) {
+            return Err(format!(
+                "{}: duplicate metric sql: {}, series_name_or_func: {}",
+                metrics_yaml_line(data, &m.sql),
+                m.sql,
+                m.series_name_or_func
+            ));
+        }
+        if !m.histogram && !m.annotations_ranges && m.periods.is_empty() {
+            return Err(format!(
+                "{}: metric {:?} (sql: {}) has no periods",
+                metrics_yaml_line(data, &m.sql),
+                m.name,
+                m.sql
+            ));
+        }
+    }
+    Ok(yaml)
+}
+
+// Best effort location of a metric for errors found after parsing: last line defining given sql
+fn metrics_yaml_line(data: &str, sql: &str) -> String {
+    data.lines()
+        .enumerate()
+        .filter(|(_, l)| {
+            l.trim_start()
+                .trim_start_matches("- ")
+                .strip_prefix("sql:")
+                .is_some_and(|v| v.trim().trim_matches(|c| c == '\'' || c == '"') == sql)
+        })
+        .last()
+        .map_or("metrics".to_string(), |(i, _)| format!("line {}", i + 1))
+}
+
+// Reads metrics from GHA2DB_METRICS_YAML (resolved via GHA2DB_DATADIR), default metrics/<GHA2DB_PROJECT>/metrics.yaml
+pub fn metrics_yaml(ctx: &Ctx) -> Result<MetricsYaml, String> {
+    let path = ctx.data_file(&ctx.metrics_yaml);
+    let data = std::fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+    parse_metrics_yaml(&data).map_err(|e| format!("{path}: {e}"))
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_metrics_yaml() {
+        let yaml = parse_metrics_yaml(
+            "metrics:
+  - name: Activity
+    sql: activity
+    series_name_or_func: activity
+    periods: d, w,m
+    aggregate: 1,7
+    skip: w7,m7
+  - name: Weekly
+    sql: activity
+    series_name_or_func: activity_weekly
+    periods: w2
+    aggregate: 7
+  - name: Companies
+    sql: 'companies'
+    series_name_or_func: hcom
+    histogram: true
+  - name: Activity again
+    sql: activity
+    series_name_or_func: activity
+    periods: d
+    project: kubernetes
+    start_from: 2020-01-01
+",
+        )
+        .unwrap();
+        let m = &yaml.metrics;
+        assert_eq!(m.len(), 4);
+        assert_eq!(m[0].periods, vec!["d", "w", "m"]);
+        assert_eq!(m[0].aggregates(), vec![1, 7]);
+        assert!(m[0].skips("w", 7) && !m[0].skips("w", 1));
+        // Single aggregate can be a YAML number
+        assert_eq!((m[1].periods.clone(), m[1].aggregates()), (vec!["w2".to_string()], vec![7]));
+        assert_eq!((m[2].sql.as_str(), m[2].aggregates()), ("companies", vec![1]));
+        assert_eq!(m[3].start_from, Some(parse_gha_time("2020-01-01T00:00:00Z").unwrap()));
+    }
+
+    #[test]
+    fn test_parse_metrics_yaml_errors() {
+        let metric = |fields: &str| {
+            format!("metrics:\n  - name: A\n    sql: a\n    series_name_or_func: sa\n{fields}")
+        };
+        // Errors found when deserializing point at the metric
+        let at = " at line 2 column 5";
+        let unknown = "metrics[0]: unknown field `serie`, expected one of `name`, `sql`, \
+            `series_name_or_func`, `periods`, `aggregate`, `skip`, `add_period_to_name`, `histogram`, \
+            `desc`, `multi_value`, `escape_value_name`, `annotations_ranges`, `merge_series`, \
+            `custom_data`, `start_from`, `last_hours`, `series_name_map`, `env`, `disabled`, `drop`, \
+            `project`, `project_scale` at line 6 column 5";
+        for (data, error) in [
+            (metric("    periods: d\n    serie: x\n"), unknown.to_string()),
+            (
+                metric("    periods: d,x\n"),
+                format!("metrics[0]: invalid period \"x\", expected one of h, d, w, m, q, y optionally followed by a number{at}"),
+            ),
+            (
+                metric("    periods: d\n    aggregate: 0\n"),
+                format!("metrics[0]: invalid aggregate \"0\", expected positive number{at}"),
+            ),
+            (
+                metric("    periods: d\n    aggregate: 1,week\n"),
+                format!("metrics[0]: invalid aggregate \"week\", expected positive number{at}"),
+            ),
+            (
+                metric("    periods: d\n    aggregate: [1, 7]\n"),
+                format!("metrics[0]: invalid aggregate, expected a number or comma separated numbers{at}"),
+            ),
+            (
+                metric("    periods: d\n    start_from: yesterday\n"),
+                format!("metrics[0]: invalid date \"yesterday\", expected 'YYYY-MM-DD HH:MI:SS'{at}"),
+            ),
+            (
+                "metrics: []\nother: 1\n".to_string(),
+                "unknown field `other`, expected `metrics` at line 2 column 1".to_string(),
+            ),
+            // Errors found after parsing point at the last line with the metric's sql
+            (
+                metric("    periods: d\n  - name: B\n    sql: a\n    series_name_or_func: sa\n    periods: w\n"),
+                "line 7: duplicate metric sql: a, series_name_or_func: sa".to_string(),
+            ),
+            (metric(""), "line 3: metric \"A\" (sql: a) has no periods".to_string()),
+            (
+                metric("    periods: d\n  - {name: B, sql: b, series_name_or_func: sb}\n"),
+                "metrics: metric \"B\" (sql: b) has no periods".to_string(),
+            ),
+        ] {
+            assert_eq!(parse_metrics_yaml(&data), Err(error), "{data}");
+        }
+    }
+}
@@ -0,0 +1,239 @@
+// Schema migration body: SQL statements or Rust code
+pub enum MigrationKind {
+    Sql(&'static str),
+    Code(fn(&mut Client, &Ctx) -> Result<(), postgres::Error>),
+}
+
+// Numbered schema migration, versions must be unique and increasing
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub kind: MigrationKind,
+}
+
+// All migrations known to this binary, in order
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "gha_* tables structure",
+        kind: MigrationKind::Code(migration_structure_tables),
+    },
+    Migration {
+        version: 2,
+        name: "gha_import_hours checkpoints",
+        kind: MigrationKind::Sql(GHA_IMPORT_HOURS_TABLE),
+    },
+    Migration {
+        version: 3,
+        name: "gha_actors.is_bot",
+        kind: MigrationKind::Sql(GHA_ACTORS_IS_BOT),
+    },
+    Migration {
+        version: 4,
+        name: "event_id of shared gha_* rows",
+        kind: MigrationKind::Code(migration_shared_event_id),
+    },
+];
+
+// STRUCTURE_TABLES is frozen at schema version 1, later changes are separate migrations
+fn migration_structure_tables(client: &mut Client, ctx: &Ctx) -> Result<(), postgres::Error> {
+    for sql in STRUCTURE_TABLES {
+        exec_sql_with_err(client, ctx, sql, &[])?;
+    }
+    Ok(())
+}
+
+fn migration_shared_event_id(client: &mut Client, ctx: &Ctx) -> Result<(), postgres::Error> {
+    for sql in GHA_SHARED_EVENT_ID {
+        exec_sql_with_err(client, ctx, sql, &[])?;
+    }
+    Ok(())
+}
+
+// Latest schema version known to this binary
+pub fn migrations_version() -> i32 {
+    MIGRATIONS.iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+// Returns versions already applied to the connected database, empty if migrations table doesn't exist yet
+pub fn migrations_applied(client: &mut Client, ctx: &Ctx) -> Result<Vec<i32>, postgres::Error> {
+    let rows = query_sql_with_err(
+        client,
+        ctx,
+        "select to_regclass('gha_schema_migrations') is not null",
+        &[],
+    )?;
+    if !rows.first().is_some_and(|row| row.get::<_, bool>(0)) {
+        return Ok(vec![]);
+    }
+    let rows = query_sql_with_err(
+        client,
+        ctx,
+        "select version from gha_schema_migrations order by version",
+        &[],
+    )?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+// Returns migrations not yet applied, in order
+// Fails if the database has a version this binary doesn't know about (DB ahead of the binary)
+pub fn migrations_pending(
+    client: &mut Client,
+    ctx: &Ctx,
+) -> Result<Vec<&'static Migration>, String> {
+    let applied = migrations_applied(client, ctx).map_err(|e| e.to_string())?;
+    migrations_not_applied(MIGRATIONS, &applied).map_err(|e| format!("database {}: {e}", ctx.pg_db))
+}
+
+// Migrations from `migrations` missing in `applied` versions, in version order
+fn migrations_not_applied(
+    migrations: &'static [Migration],
+    applied: &[i32],
+) -> Result<Vec<&'static Migration>, String> {
+    if let Some(unknown) = applied.iter().find(|v| !migrations.iter().any(|m| m.version == **v)) {
+        let latest = migrations.iter().map(|m| m.version).max().unwrap_or(0);
+        return Err(format!(
+            "schema migration {unknown} is unknown to this binary (latest {latest}), refusing to run"
+        ));
+    }
+    let mut pending: Vec<&Migration> = migrations
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+        .collect();
+    pending.sort_by_key(|m| m.version);
+    Ok(pending)
+}
+
+const GHA_SCHEMA_MIGRATIONS_TABLE: &str = "create table if not exists gha_schema_migrations(
+        version int not null primary key,
+        name text not null,
+        applied_at timestamp not null default now()
+    )";
+
+// Records all migrations as applied, used when the structure tool created the latest schema
+// Fails if the database has a version this binary doesn't know about
+pub fn migrations_stamp(client: &mut Client, ctx: &Ctx) -> Result<(), String> {
+    let pending = migrations_pending(client, ctx)?;
+    exec_sql_with_err(client, ctx, GHA_SCHEMA_MIGRATIONS_TABLE, &[]).map_err(|e| e.to_string())?;
+    for m in pending {
+        exec_sql_with_err(
+            client,
+            ctx,
+            "insert into gha_schema_migrations(version, name) values($1, $2) on conflict do nothing",
+            &[&m.version, &m.name],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// Applies all pending migrations, each one in its own transaction, returns number of applied migrations
+// In dry run mode only lists pending migrations
+pub fn migrate(client: &mut Client, ctx: &Ctx) -> Result<usize, String> {
+    let pending = migrations_pending(client, ctx)?;
+    if ctx.dry_run {
+        for m in &pending {
+            printf(&format!("{}: would apply migration {}: {}", ctx.pg_db, m.version, m.name));
+        }
+        return Ok(0);
+    }
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    exec_sql_with_err(client, ctx, GHA_SCHEMA_MIGRATIONS_TABLE, &[]).map_err(|e| e.to_string())?;
+    for m in &pending {
+        let start = Instant::now();
+        batch_sql_with_err(client, ctx, "begin").map_err(|e| e.to_string())?;
+        let mut res = match m.kind {
+            MigrationKind::Sql(sql) => batch_sql_with_err(client, ctx, sql),
+            MigrationKind::Code(f) => f(client, ctx),
+        };
+        if res.is_ok() {
+            res = exec_sql_with_err(
+                client,
+                ctx,
+                "insert into gha_schema_migrations(version, name) values($1, $2)",
+                &[&m.version, &m.name],
+            )
+            .map(|_| ());
+        }
+        if let Err(e) = res {
+            let _ = batch_sql_with_err(client, ctx, "rollback");
+            return Err(format!(
+                "{}: migration {} ({}) failed: {e}",
+                ctx.pg_db, m.version, m.name
+            ));
+        }
+        batch_sql_with_err(client, ctx, "commit").map_err(|e| e.to_string())?;
+        if ctx.debug >= 0 {
+            printf(&format!(
+                "{}: applied migration {}: {} in {:?}",
+                ctx.pg_db,
+                m.version,
+                m.name,
+                start.elapsed()
+            ));
+        }
+    }
+    Ok(pending.len())
+}
+
+#[cfg(test)]
+mod migrations_tests {
+    use super::*;
+
+    fn versions(migrations: &[&Migration]) -> Vec<i32> {
+        migrations.iter().map(|m| m.version).collect()
+    }
+
+    #[test]
+    fn test_migrations_versions() {
+        let all: Vec<i32> = MIGRATIONS.iter().map(|m| m.version).collect();
+        let mut sorted = all.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(all, sorted, "versions must be unique and increasing");
+        assert_eq!(migrations_version(), *all.last().unwrap());
+    }
+
+    #[test]
+    fn test_migrations_pending() {
+        let pending = migrations_not_applied(MIGRATIONS, &[]).unwrap();
+        assert_eq!(versions(&pending), vec![1, 2, 3, 4]);
+        let pending = migrations_not_applied(MIGRATIONS, &[3, 1]).unwrap();
+        assert_eq!(versions(&pending), vec![2, 4]);
+        let pending = migrations_not_applied(MIGRATIONS, &[1, 2, 3, 4]).unwrap();
+        assert!(pending.is_empty());
+
+        // Pending migrations are returned in version order whatever order they are listed in
+        static UNORDERED: &[Migration] = &[
+            Migration {
+                version: 3,
+                name: "c",
+                kind: MigrationKind::Sql("select 3"),
+            },
+            Migration {
+                version: 1,
+                name: "a",
+                kind: MigrationKind::Sql("select 1"),
+            },
+            Migration {
+                version: 2,
+                name: "b",
+                kind: MigrationKind::Sql("select 2"),
+            },
+        ];
+        let pending = migrations_not_applied(UNORDERED, &[2]).unwrap();
+        assert_eq!(versions(&pending), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_migrations_unknown_version() {
+        let err = match migrations_not_applied(MIGRATIONS, &[1, 99]) {
+            Ok(_) => panic!("unknown version 99 accepted"),
+            Err(e) => e,
+        };
+        assert!(err.contains("migration 99 is unknown"), "{err}");
+        assert!(err.contains(&format!("latest {}", migrations_version())), "{err}");
+    }
+}
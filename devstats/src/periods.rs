@@ -0,0 +1,424 @@
+// Source of randomness for scheduling decisions, tests can inject a deterministic one
+pub trait RandSource {
+    // Random number in [0, n), n > 0
+    fn below(&mut self, n: u32) -> u32;
+}
+
+// Default random source: xorshift64* seeded from current time, good enough to spread recalculations
+pub struct TimeRand(u64);
+
+impl TimeRand {
+    pub fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self::with_seed(nanos)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        TimeRand(seed | 1)
+    }
+}
+
+impl Default for TimeRand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RandSource for TimeRand {
+    fn below(&mut self, n: u32) -> u32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as u32 % n.max(1)
+    }
+}
+
+// Shuffles items in place (Fisher-Yates)
+pub fn rand_shuffle<T>(rng: &mut dyn RandSource, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        let j = rng.below(i as u32 + 1) as usize;
+        items.swap(i, j);
+    }
+}
+
+// Parses GHA2DB_FORCE_PERIODS: "y10:t,m:f,..." - period and whether it is a histogram (t) or a time series (f) period
+// Entries with other formats are ignored
+pub fn parse_compute_periods(s: &str) -> HashMap<String, HashSet<bool>> {
+    let mut periods: HashMap<String, HashSet<bool>> = HashMap::new();
+    for data in s.split(',') {
+        let ary: Vec<&str> = data.split(':').collect();
+        if ary.len() != 2 {
+            continue;
+        }
+        let hist = match ary[1].trim() {
+            "t" => true,
+            "f" => false,
+            _ => continue,
+        };
+        periods.entry(ary[0].trim().to_string()).or_default().insert(hist);
+    }
+    periods
+}
+
+// How often (every N local hours) given period is recalculated, longer periods change less per hour of new data
+// Time series periods ("d", "w", ...) include the current period which changes with every hour of data,
+// histograms (quick ranges like "last week") are recalculated less often than time series of the same period
+// Decades ("y10") and annotations ranges ("anno") are recalculated daily
+fn period_every_hours(period: &str, hist: bool) -> Result<u32, String> {
+    if period == "anno" {
+        return Ok(24);
+    }
+    Ok(match (period.chars().next(), hist) {
+        (Some('h'), _) | (Some('d'), _) => 1,
+        (Some('w'), false) => 1,
+        (Some('w'), true) => 3,
+        (Some('m'), false) => 3,
+        (Some('m'), true) => 6,
+        (Some('q'), false) => 6,
+        (Some('q'), true) => 12,
+        (Some('y'), _) => 24,
+        _ => {
+            return Err(format!(
+                "invalid period {period:?}, expected h, d, w, m, q, y, y10 or anno"
+            ))
+        }
+    })
+}
+
+// Decides if given period of a metric must be calculated at given hour (GHA2DB_COMPUTE_ALL, GHA2DB_FORCE_PERIODS,
+// GHA2DB_TMOFFSET, GHA2DB_RECALC_RECIPROCAL), hist is true for histogram metrics
+// Periods recalculated every 24 hours are calculated at local (GHA2DB_TMOFFSET) midnight, shorter ones on local hours divisible by their frequency
+// When the schedule says no, 1/GHA2DB_RECALC_RECIPROCAL of decisions are random recalculations (if rand_compute_at_this_date)
+pub fn compute_period_at_this_date(
+    ctx: &Ctx,
+    rng: &mut dyn RandSource,
+    period: &str,
+    dt: DateTime<Utc>,
+    hist: bool,
+) -> Result<bool, String> {
+    if ctx.compute_all {
+        return Ok(true);
+    }
+    if !ctx.compute_periods.is_empty() {
+        return Ok(ctx
+            .compute_periods
+            .get(period)
+            .is_some_and(|h| h.contains(&hist)));
+    }
+    if period.is_empty() {
+        return Ok(false);
+    }
+    let local = hour_start(dt) + chrono::Duration::hours(ctx.mm_offset as i64);
+    if local.hour().is_multiple_of(period_every_hours(period, hist)?) {
+        return Ok(true);
+    }
+    Ok(ctx.rand_compute_at_this_date
+        && ctx.recalc_reciprocal > 0
+        && rng.below(ctx.recalc_reciprocal as u32) == 0)
+}
+
+// Decides if tags and columns must be calculated at given hour: only during local hours 0-5,
+// with allow_rand_tags_cols_compute at random hours of that window (about once per day), otherwise at each of them
+pub fn compute_tags_cols_at_this_date(ctx: &Ctx, rng: &mut dyn RandSource, dt: DateTime<Utc>) -> bool {
+    if ctx.compute_all {
+        return true;
+    }
+    let local = hour_start(dt) + chrono::Duration::hours(ctx.mm_offset as i64);
+    if local.hour() >= 6 {
+        return false;
+    }
+    !ctx.allow_rand_tags_cols_compute || rng.below(6) == 0
+}
+
+// Splits period with an optional moving aggregate window: "d" -> ("d", 1), "w7" -> ("w", 7), "y10" is a decade
+pub fn parse_period(period: &str) -> Result<(&str, u32), String> {
+    let split = if period.starts_with("y10") { 3 } else { 1 };
+    if !matches!(period.get(..1), Some("h" | "d" | "w" | "m" | "q" | "y")) {
+        return Err(format!("invalid period {period:?}, expected h, d, w, m, q, y or y10 optionally followed by a number"));
+    }
+    let (base, n) = period.split_at(split);
+    if n.is_empty() {
+        return Ok((base, 1));
+    }
+    match string_to_num::<u32>(n) {
+        Ok(n) if n > 0 => Ok((base, n)),
+        _ => Err(format!("invalid period {period:?}, aggregate must be a positive number")),
+    }
+}
+
+// Beginning of the period containing dt, weeks start on Monday, decades on years divisible by 10
+pub fn period_start(dt: DateTime<Utc>, base: &str) -> DateTime<Utc> {
+    let d = dt.date_naive();
+    let day = match base {
+        "h" => return hour_start(dt),
+        "d" => d,
+        "w" => d - chrono::Duration::days(d.weekday().num_days_from_monday() as i64),
+        "m" => d.with_day(1).expect("invalid month start"),
+        "q" => NaiveDate::from_ymd_opt(d.year(), (d.month0() / 3) * 3 + 1, 1).expect("invalid quarter start"),
+        "y" => NaiveDate::from_ymd_opt(d.year(), 1, 1).expect("invalid year start"),
+        _ => NaiveDate::from_ymd_opt(d.year() - d.year().rem_euclid(10), 1, 1).expect("invalid decade start"),
+    };
+    day.and_hms_opt(0, 0, 0).expect("invalid period start").and_utc()
+}
+
+// Moves period start by n periods (negative n moves back)
+pub fn period_add(dt: DateTime<Utc>, base: &str, n: i32) -> DateTime<Utc> {
+    let months = |m: i32| {
+        if m >= 0 {
+            dt.checked_add_months(chrono::Months::new(m as u32))
+        } else {
+            dt.checked_sub_months(chrono::Months::new(m.unsigned_abs()))
+        }
+        .expect("date out of range")
+    };
+    match base {
+        "h" => dt + chrono::Duration::hours(n as i64),
+        "d" => dt + chrono::Duration::days(n as i64),
+        "w" => dt + chrono::Duration::weeks(n as i64),
+        "m" => months(n),
+        "q" => months(3 * n),
+        "y" => months(12 * n),
+        _ => months(120 * n),
+    }
+}
+
+#[cfg(test)]
+mod periods_tests {
+    use super::*;
+
+    // Returns given numbers in a loop, counts calls
+    struct FixedRand {
+        values: Vec<u32>,
+        calls: usize,
+    }
+
+    impl FixedRand {
+        fn new(values: &[u32]) -> Self {
+            FixedRand {
+                values: values.to_vec(),
+                calls: 0,
+            }
+        }
+    }
+
+    impl RandSource for FixedRand {
+        fn below(&mut self, n: u32) -> u32 {
+            let v = self.values[self.calls % self.values.len()];
+            self.calls += 1;
+            v.min(n - 1)
+        }
+    }
+
+    fn sched_ctx(mm_offset: i8) -> Ctx {
+        Ctx {
+            compute_all: false,
+            compute_periods: HashMap::new(),
+            mm_offset,
+            rand_compute_at_this_date: false,
+            recalc_reciprocal: 24,
+            allow_rand_tags_cols_compute: false,
+            ..Default::default()
+        }
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        parse_gha_time(s).unwrap()
+    }
+
+    // UTC hours of 2021-03-17 at which the period is calculated
+    fn hours(ctx: &Ctx, rng: &mut dyn RandSource, period: &str, hist: bool) -> Vec<u32> {
+        let day = utc("2021-03-17T00:00:00Z");
+        (0..24)
+            .filter(|h| {
+                let dt = day + chrono::Duration::minutes(60 * *h as i64 + 37);
+                compute_period_at_this_date(ctx, rng, period, dt, hist).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compute_period_schedule() {
+        let ctx = sched_ctx(0);
+        let mut rng = FixedRand::new(&[0]);
+        let all: Vec<u32> = (0..24).collect();
+        let every = |n: u32| (0..24).filter(|h| h % n == 0).collect::<Vec<u32>>();
+        for (period, hist, expected) in [
+            ("h", false, all.clone()),
+            ("h", true, all.clone()),
+            ("d", false, all.clone()),
+            ("d", true, all.clone()),
+            ("d7", false, all.clone()),
+            ("w", false, all.clone()),
+            ("w", true, every(3)),
+            ("m", false, every(3)),
+            ("m", true, every(6)),
+            ("q", false, every(6)),
+            ("q", true, every(12)),
+            ("y", false, vec![0]),
+            ("y", true, vec![0]),
+            ("y10", false, vec![0]),
+            ("y10", true, vec![0]),
+        ] {
+            assert_eq!(hours(&ctx, &mut rng, period, hist), expected, "{period} hist {hist}");
+        }
+        assert_eq!(hours(&ctx, &mut rng, "anno", true), vec![0]);
+        assert!(hours(&ctx, &mut rng, "", false).is_empty());
+        // Random recalculation is off
+        assert_eq!(rng.calls, 0);
+        let dt = utc("2021-03-17T00:00:00Z");
+        for period in ["x", "é", "éw", "日"] {
+            let err = compute_period_at_this_date(&ctx, &mut rng, period, dt, false).unwrap_err();
+            assert_eq!(err, format!("invalid period {period:?}, expected h, d, w, m, q, y, y10 or anno"));
+        }
+    }
+
+    #[test]
+    fn test_compute_period_offset() {
+        // Local midnight is 05:00 UTC with -5 and 21:00 UTC (previous day) with +3
+        let mut rng = FixedRand::new(&[0]);
+        assert_eq!(hours(&sched_ctx(-5), &mut rng, "y", false), vec![5]);
+        assert_eq!(hours(&sched_ctx(-5), &mut rng, "q", true), vec![5, 17]);
+        assert_eq!(hours(&sched_ctx(-5), &mut rng, "m", false), vec![2, 5, 8, 11, 14, 17, 20, 23]);
+        assert_eq!(hours(&sched_ctx(3), &mut rng, "y10", true), vec![21]);
+        assert_eq!(hours(&sched_ctx(3), &mut rng, "m", true), vec![3, 9, 15, 21]);
+        assert_eq!(hours(&sched_ctx(3), &mut rng, "d", true).len(), 24);
+    }
+
+    #[test]
+    fn test_compute_period_forced() {
+        let mut rng = FixedRand::new(&[0]);
+        let ctx = Ctx {
+            compute_periods: parse_compute_periods("m:t, y10:f,d:x,w"),
+            ..sched_ctx(0)
+        };
+        assert_eq!(hours(&ctx, &mut rng, "m", true).len(), 24);
+        assert!(hours(&ctx, &mut rng, "m", false).is_empty());
+        assert_eq!(hours(&ctx, &mut rng, "y10", false).len(), 24);
+        assert!(hours(&ctx, &mut rng, "y10", true).is_empty());
+        assert!(hours(&ctx, &mut rng, "d", false).is_empty());
+        assert!(hours(&ctx, &mut rng, "w", false).is_empty());
+        // GHA2DB_COMPUTE_ALL wins over everything
+        let ctx = Ctx {
+            compute_all: true,
+            ..ctx
+        };
+        assert_eq!(hours(&ctx, &mut rng, "d", false).len(), 24);
+        assert_eq!(rng.calls, 0);
+    }
+
+    #[test]
+    fn test_compute_period_recalc() {
+        let ctx = Ctx {
+            rand_compute_at_this_date: true,
+            recalc_reciprocal: 4,
+            ..sched_ctx(0)
+        };
+        // Scheduled hours don't use the random source, others recalculate when it returns 0
+        let mut rng = FixedRand::new(&[0]);
+        assert_eq!(hours(&ctx, &mut rng, "y", false).len(), 24);
+        assert_eq!(rng.calls, 23);
+        let mut rng = FixedRand::new(&[1, 3, 2, 0]);
+        assert_eq!(hours(&ctx, &mut rng, "y", false), vec![0, 4, 8, 12, 16, 20]);
+        let mut rng = FixedRand::new(&[1, 3, 2, 0]);
+        assert_eq!(hours(&ctx, &mut rng, "q", true), vec![0, 4, 8, 12, 13, 17, 21]);
+        let ctx = Ctx {
+            recalc_reciprocal: 0,
+            ..ctx
+        };
+        let mut rng = FixedRand::new(&[0]);
+        assert_eq!(hours(&ctx, &mut rng, "y", false), vec![0]);
+        assert_eq!(rng.calls, 0);
+    }
+
+    #[test]
+    fn test_compute_tags_cols() {
+        let tags_hours = |ctx: &Ctx, rng: &mut FixedRand| {
+            let day = utc("2021-03-17T00:00:00Z");
+            (0..24)
+                .filter(|h| {
+                    let dt = day + chrono::Duration::hours(*h as i64);
+                    compute_tags_cols_at_this_date(ctx, rng, dt)
+                })
+                .collect::<Vec<u32>>()
+        };
+        let mut rng = FixedRand::new(&[0]);
+        assert_eq!(tags_hours(&sched_ctx(0), &mut rng), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(tags_hours(&sched_ctx(-6), &mut rng), vec![6, 7, 8, 9, 10, 11]);
+        assert_eq!(tags_hours(&sched_ctx(2), &mut rng), vec![0, 1, 2, 3, 22, 23]);
+        assert_eq!(rng.calls, 0);
+        let ctx = Ctx {
+            allow_rand_tags_cols_compute: true,
+            ..sched_ctx(0)
+        };
+        let mut rng = FixedRand::new(&[5, 0, 2]);
+        assert_eq!(tags_hours(&ctx, &mut rng), vec![1, 4]);
+        assert_eq!(rng.calls, 6);
+        let ctx = Ctx {
+            compute_all: true,
+            ..ctx
+        };
+        assert_eq!(tags_hours(&ctx, &mut rng).len(), 24);
+    }
+
+    #[test]
+    fn test_parse_period() {
+        for (period, expected) in [
+            ("h", Ok(("h", 1))),
+            ("d", Ok(("d", 1))),
+            ("d7", Ok(("d", 7))),
+            ("w", Ok(("w", 1))),
+            ("m12", Ok(("m", 12))),
+            ("q", Ok(("q", 1))),
+            ("y", Ok(("y", 1))),
+            ("y2", Ok(("y", 2))),
+            ("y10", Ok(("y10", 1))),
+            ("y103", Ok(("y10", 3))),
+        ] {
+            assert_eq!(parse_period(period), expected, "{period}");
+        }
+        for period in ["", "x", "d0", "dx", "w-1", "hour", "é", "éw", "日7", "y10é"] {
+            assert!(parse_period(period).is_err(), "{period}");
+        }
+    }
+
+    #[test]
+    fn test_period_start() {
+        let dt = utc("2021-03-17T13:45:10Z");
+        for (base, expected) in [
+            ("h", "2021-03-17T13:00:00Z"),
+            ("d", "2021-03-17T00:00:00Z"),
+            ("w", "2021-03-15T00:00:00Z"),
+            ("m", "2021-03-01T00:00:00Z"),
+            ("q", "2021-01-01T00:00:00Z"),
+            ("y", "2021-01-01T00:00:00Z"),
+            ("y10", "2020-01-01T00:00:00Z"),
+        ] {
+            assert_eq!(period_start(dt, base), utc(expected), "{base}");
+        }
+        assert_eq!(period_start(utc("2021-11-20T00:00:00Z"), "q"), utc("2021-10-01T00:00:00Z"));
+        assert_eq!(period_start(utc("2021-03-14T23:59:59Z"), "w"), utc("2021-03-08T00:00:00Z"));
+        assert_eq!(period_start(utc("2009-12-31T23:00:00Z"), "y10"), utc("2000-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_period_add() {
+        let dt = utc("2021-01-31T00:00:00Z");
+        for (base, n, expected) in [
+            ("h", 25, "2021-02-01T01:00:00Z"),
+            ("d", -31, "2020-12-31T00:00:00Z"),
+            ("w", 2, "2021-02-14T00:00:00Z"),
+            ("m", 1, "2021-02-28T00:00:00Z"),
+            ("m", -2, "2020-11-30T00:00:00Z"),
+            ("q", -1, "2020-10-31T00:00:00Z"),
+            ("y", 1, "2022-01-31T00:00:00Z"),
+            ("y10", -1, "2011-01-31T00:00:00Z"),
+        ] {
+            assert_eq!(period_add(dt, base, n), utc(expected), "{base} {n}");
+        }
+        // Period starts stay period starts
+        let start = period_start(utc("2021-08-09T10:00:00Z"), "q");
+        assert_eq!(period_add(start, "q", 2), utc("2022-01-01T00:00:00Z"));
+    }
+}
@@ -0,0 +1,625 @@
+// Postgres SSL mode from PG_SSL, connections are made without TLS so only modes allowing plain connections are accepted
+// ("allow" and "prefer" fall back to a plain connection, like libpq without SSL support)
+pub fn pg_ssl_mode(ssl: &str) -> Result<postgres::config::SslMode, String> {
+    match ssl {
+        "disable" => Ok(postgres::config::SslMode::Disable),
+        "allow" | "prefer" => Ok(postgres::config::SslMode::Prefer),
+        "require" | "verify-ca" | "verify-full" => Err(format!(
+            "PG_SSL={ssl} is not supported (no TLS support), use PG_SSL=disable or PG_SSL=prefer"
+        )),
+        _ => Err(format!("unknown PG_SSL={ssl:?}, expected disable, allow or prefer")),
+    }
+}
+
+// Postgres connection config from context, PG_SSL is validated by Ctx
+pub fn pg_config(ctx: &Ctx) -> postgres::Config {
+    let mut cfg = postgres::Config::new();
+    cfg.host(&ctx.pg_host)
+        .port(string_to_num_must::<u16>(&ctx.pg_port))
+        .dbname(&ctx.pg_db)
+        .user(&ctx.pg_user)
+        .password(&ctx.pg_pass)
+        .application_name(&ctx.tool);
+    cfg.ssl_mode(pg_ssl_mode(&ctx.pg_ssl).expect("invalid PG_SSL"));
+    cfg
+}
+
+// Connects to Postgres database, returns error
+pub fn pg_conn_with_err(ctx: &Ctx) -> Result<Client, postgres::Error> {
+    if ctx.debug > 0 {
+        printf(&format!(
+            "connecting to host={} port={} dbname={} user={}",
+            ctx.pg_host, ctx.pg_port, ctx.pg_db, ctx.pg_user
+        ));
+    }
+    pg_config(ctx).connect(NoTls)
+}
+
+// Connects to Postgres database, fatal on error
+pub fn pg_conn(ctx: &Ctx) -> Client {
+    let res = pg_conn_with_err(ctx);
+    fatal_no_log(&res);
+    res.expect("cannot connect to Postgres")
+}
+
+// Postgres connections pool statistics
+#[derive(Debug, Clone, Copy)]
+pub struct PgPoolStats {
+    pub size: usize,      // Maximum number of connections
+    pub open: usize,      // Currently open connections
+    pub idle: usize,      // Open connections not checked out
+    pub checkouts: u64,   // Number of successful connection checkouts
+    pub waits: u64,       // Number of checkouts that had to wait for a free connection
+    pub wait_time: Duration, // Total time spent waiting for a free connection
+}
+
+struct PgPoolState {
+    idle: Vec<Client>,
+    open: usize,
+}
+
+// Thread-safe Postgres connections pool, sized from GHA2DB_ST/GHA2DB_NCPUS
+// Connections are opened lazily, up to `size` of them
+pub struct PgPool {
+    config: postgres::Config,
+    size: usize,
+    debug: i8,
+    state: Mutex<PgPoolState>,
+    released: Condvar,
+    checkouts: AtomicU64,
+    waits: AtomicU64,
+    wait_us: AtomicU64,
+}
+
+// Pooled connection, returned to the pool when dropped
+pub struct PgPoolConn<'a> {
+    pool: &'a PgPool,
+    client: Option<Client>,
+}
+
+impl PgPool {
+    pub fn new(ctx: &Ctx) -> Self {
+        Self::with_size(ctx, ctx.threads_num())
+    }
+
+    pub fn with_size(ctx: &Ctx, size: usize) -> Self {
+        let size = size.max(1);
+        if ctx.debug > 0 {
+            printf(&format!("Postgres pool size: {size}"));
+        }
+        PgPool {
+            config: pg_config(ctx),
+            size,
+            debug: ctx.debug,
+            state: Mutex::new(PgPoolState {
+                idle: Vec::with_capacity(size),
+                open: 0,
+            }),
+            released: Condvar::new(),
+            checkouts: AtomicU64::new(0),
+            waits: AtomicU64::new(0),
+            wait_us: AtomicU64::new(0),
+        }
+    }
+
+    // Number of workers that can use the pool concurrently
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Gets a connection, waits if all of them are checked out, returns error
+    pub fn get_with_err(&self) -> Result<PgPoolConn<'_>, postgres::Error> {
+        let mut state = self.state.lock().expect("Postgres pool mutex poisoned");
+        let mut waited: Option<Instant> = None;
+        loop {
+            if let Some(client) = state.idle.pop() {
+                self.waited(waited);
+                self.checkouts.fetch_add(1, Ordering::Relaxed);
+                return Ok(PgPoolConn {
+                    pool: self,
+                    client: Some(client),
+                });
+            }
+            if state.open < self.size {
+                state.open += 1;
+                drop(state);
+                self.waited(waited);
+                return match self.config.connect(NoTls) {
+                    Ok(client) => {
+                        self.checkouts.fetch_add(1, Ordering::Relaxed);
+                        Ok(PgPoolConn {
+                            pool: self,
+                            client: Some(client),
+                        })
+                    }
+                    Err(e) => {
+                        self.discard();
+                        Err(e)
+                    }
+                };
+            }
+            if waited.is_none() {
+                self.waits.fetch_add(1, Ordering::Relaxed);
+                waited = Some(Instant::now());
+            }
+            state = self
+                .released
+                .wait(state)
+                .expect("Postgres pool mutex poisoned");
+        }
+    }
+
+    // Gets a connection, waits if all of them are checked out, fatal on error
+    pub fn get(&self) -> PgPoolConn<'_> {
+        let res = self.get_with_err();
+        fatal_no_log(&res);
+        res.expect("cannot get Postgres connection from pool")
+    }
+
+    pub fn stats(&self) -> PgPoolStats {
+        let state = self.state.lock().expect("Postgres pool mutex poisoned");
+        PgPoolStats {
+            size: self.size,
+            open: state.open,
+            idle: state.idle.len(),
+            checkouts: self.checkouts.load(Ordering::Relaxed),
+            waits: self.waits.load(Ordering::Relaxed),
+            wait_time: Duration::from_micros(self.wait_us.load(Ordering::Relaxed)),
+        }
+    }
+
+    // Outputs pool statistics when debug level is at least 2, called when the pool is dropped
+    pub fn print_stats(&self) {
+        if self.debug >= 2 {
+            printf(&format!("Postgres pool stats: {:?}", self.stats()));
+        }
+    }
+
+    fn waited(&self, since: Option<Instant>) {
+        if let Some(since) = since {
+            self.wait_us
+                .fetch_add(since.elapsed().as_micros() as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn release(&self, client: Client) {
+        if client.is_closed() {
+            self.discard();
+            return;
+        }
+        let mut state = self.state.lock().expect("Postgres pool mutex poisoned");
+        state.idle.push(client);
+        drop(state);
+        self.released.notify_one();
+    }
+
+    fn discard(&self) {
+        let mut state = self.state.lock().expect("Postgres pool mutex poisoned");
+        state.open -= 1;
+        drop(state);
+        self.released.notify_one();
+    }
+}
+
+impl Drop for PgPool {
+    fn drop(&mut self) {
+        self.print_stats();
+    }
+}
+
+impl Deref for PgPoolConn<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().expect("pooled connection already released")
+    }
+}
+
+impl DerefMut for PgPoolConn<'_> {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client.as_mut().expect("pooled connection already released")
+    }
+}
+
+impl Drop for PgPoolConn<'_> {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            self.pool.release(client);
+        }
+    }
+}
+
+// Returns $n
+pub fn n_value(index: usize) -> String {
+    format!("${index}")
+}
+
+// Returns values($1, $2, .., $n)
+pub fn n_values(n: usize) -> String {
+    format!("values{}", n_array(n, 0))
+}
+
+// Returns ($start+1, $start+2, .., $start+n)
+pub fn n_array(n: usize, start: usize) -> String {
+    let vals: Vec<String> = (start + 1..=start + n).map(n_value).collect();
+    format!("({})", vals.join(", "))
+}
+
+// Returns values($1, .., $cols), ($cols+1, .., $2*cols), .. for `rows` rows
+pub fn n_values_rows(rows: usize, cols: usize) -> String {
+    let arrays: Vec<String> = (0..rows).map(|r| n_array(cols, r * cols)).collect();
+    format!("values{}", arrays.join(", "))
+}
+
+// Quotes SQL identifier (table or column name)
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// Masks password literals in logged SQLs: "password 'x'", "password = 'x'" and "password=x" (connection strings)
+fn mask_secrets(s: &str) -> String {
+    static PASSWORD_RE: OnceLock<Regex> = OnceLock::new();
+    let re = PASSWORD_RE.get_or_init(|| {
+        Regex::new(r#"(?i)\b(password(?:\s*=\s*|\s+))'(?:[^']|'')*'|\b(password\s*=\s*)[^\s'",;)$][^\s'",;)]*"#)
+            .expect("invalid password regexp")
+    });
+    re.replace_all(s, |caps: &regex::Captures| match caps.get(1) {
+        Some(prefix) => format!("{}'***'", prefix.as_str()),
+        None => format!("{}***", &caps[2]),
+    })
+    .to_string()
+}
+
+// Formats bound parameters for logs, a parameter equal to the Postgres password is masked as a whole
+fn mask_params(ctx: &Ctx, args: &[&(dyn ToSql + Sync)]) -> String {
+    let pass = format!("{:?}", ctx.pg_pass);
+    let params: Vec<String> = args
+        .iter()
+        .map(|a| {
+            let v = format!("{a:?}");
+            if !ctx.pg_pass.is_empty() && v == pass {
+                "***".to_string()
+            } else {
+                mask_secrets(&v)
+            }
+        })
+        .collect();
+    format!("[{}]", params.join(", "))
+}
+
+// Logs executed SQL when GHA2DB_QOUT is set or debug level is at least 2
+// Queries slower than GHA2DB_SLOW_QUERY are logged at debug level 1
+fn log_sql(ctx: &Ctx, sql: &str, args: &[&(dyn ToSql + Sync)], took: Duration, rows: Option<u64>) {
+    let slow = ctx.slow_query > Duration::ZERO && took >= ctx.slow_query;
+    if !ctx.q_out && ctx.debug < 2 && !(slow && ctx.debug >= 1) {
+        return;
+    }
+    let rows = match rows {
+        Some(n) => n.to_string(),
+        None => "error".to_string(),
+    };
+    let mut msg = format!("{}: {}", ctx.tool, mask_secrets(sql.trim()));
+    if !args.is_empty() {
+        msg += &format!("\nparams: {}", mask_params(ctx, args));
+    }
+    msg += &format!("\ntook: {took:?}, rows: {rows}");
+    if slow {
+        msg = format!("slow query (>= {:?}) {msg}", ctx.slow_query);
+    }
+    printf(&msg);
+}
+
+// Returns end of the string literal, quoted identifier, dollar quoted body or comment starting at `i`
+// None if none starts there, unterminated ones extend to the end of SQL
+fn sql_skip(s: &[u8], i: usize) -> Option<usize> {
+    let ident = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+    let find = |from: usize, pat: &[u8]| {
+        s[from..]
+            .windows(pat.len())
+            .position(|w| w == pat)
+            .map_or(s.len(), |p| from + p + pat.len())
+    };
+    match s[i] {
+        q @ (b'\'' | b'"') => {
+            // E'..' strings use backslash escapes, quotes are escaped by doubling them
+            let escapes = q == b'\''
+                && i > 0
+                && s[i - 1].eq_ignore_ascii_case(&b'e')
+                && (i < 2 || !ident(s[i - 2]));
+            let mut j = i + 1;
+            while j < s.len() {
+                if (escapes && s[j] == b'\\') || (s[j] == q && s.get(j + 1) == Some(&q)) {
+                    j += 2;
+                } else if s[j] == q {
+                    return Some(j + 1);
+                } else {
+                    j += 1;
+                }
+            }
+            Some(s.len())
+        }
+        b'-' if s.get(i + 1) == Some(&b'-') => Some(find(i + 2, b"\n")),
+        b'/' if s.get(i + 1) == Some(&b'*') => Some(find(i + 2, b"*/")),
+        // $$ or $tag$, but not $1 parameters or identifiers containing $
+        b'$' if i == 0 || !ident(s[i - 1]) => {
+            let mut j = i + 1;
+            while j < s.len() && ident(s[j]) && !(j == i + 1 && s[j].is_ascii_digit()) {
+                j += 1;
+            }
+            if s.get(j) != Some(&b'$') {
+                return None;
+            }
+            Some(find(j + 1, &s[i..=j]))
+        }
+        _ => None,
+    }
+}
+
+// Splits SQL into statements on semicolons outside of string literals, quoted identifiers, dollar quoted bodies and comments
+pub fn split_sql(sql: &str) -> Vec<&str> {
+    let s = sql.as_bytes();
+    let mut stmts = Vec::new();
+    let (mut start, mut i) = (0, 0);
+    while i < s.len() {
+        if let Some(end) = sql_skip(s, i) {
+            i = end;
+            continue;
+        }
+        if s[i] == b';' {
+            stmts.push(&sql[start..i]);
+            start = i + 1;
+        }
+        i += 1;
+    }
+    stmts.push(&sql[start..]);
+    stmts.into_iter().filter(|stmt| !stmt.trim().is_empty()).collect()
+}
+
+// Replaces string literals, quoted identifiers, dollar quoted bodies and comments with spaces
+fn strip_sql_literals(sql: &str) -> String {
+    let s = sql.as_bytes();
+    let mut stripped = String::with_capacity(sql.len());
+    let (mut start, mut i) = (0, 0);
+    while i < s.len() {
+        match sql_skip(s, i) {
+            Some(end) => {
+                stripped += &sql[start..i];
+                stripped.push(' ');
+                start = end;
+                i = end;
+            }
+            None => i += 1,
+        }
+    }
+    stripped += &sql[start..];
+    stripped
+}
+
+// Checks if SQL can modify data or schema, everything except plain reads and session/transaction control
+// Keywords are only matched outside of string literals, quoted identifiers and comments
+pub fn is_write_sql(sql: &str) -> bool {
+    static WRITE_RE: OnceLock<Regex> = OnceLock::new();
+    static WORD_RE: OnceLock<Regex> = OnceLock::new();
+    let write_re = WRITE_RE.get_or_init(|| {
+        Regex::new(r"(?i)\b(insert|update|delete|merge|into|analyze)\b").expect("invalid SQL write regexp")
+    });
+    let word_re = WORD_RE
+        .get_or_init(|| Regex::new(r"^[\s(]*([A-Za-z]+)").expect("invalid SQL keyword regexp"));
+    let sql = strip_sql_literals(sql);
+    let first = match word_re.captures(&sql) {
+        Some(caps) => caps[1].to_lowercase(),
+        None => return false,
+    };
+    match first.as_str() {
+        "select" | "with" | "explain" => write_re.is_match(&sql),
+        "show" | "values" | "table" | "fetch" | "set" | "reset" | "begin" | "start" | "commit"
+        | "end" | "rollback" | "savepoint" | "release" | "discard" | "listen" => false,
+        _ => true,
+    }
+}
+
+// Records a statement that would be executed if not in dry run mode
+fn record_dry_run(ctx: &Ctx, sql: &str, args: &[&(dyn ToSql + Sync)]) {
+    static DRY_RUN_MTX: Mutex<()> = Mutex::new(());
+    let sql = sql.trim();
+    let term = if sql.ends_with(';') || sql.ends_with("\\.") { "" } else { ";" };
+    let mut rec = format!("-- {} dry run\n{}{term}\n", ctx.tool, mask_secrets(sql));
+    if !args.is_empty() {
+        rec += &format!("-- params: {}\n", mask_params(ctx, args));
+    }
+    let _lock = DRY_RUN_MTX.lock().expect("dry run mutex poisoned");
+    if ctx.dry_run_file.is_empty() {
+        print!("{rec}");
+        return;
+    }
+    let res = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&ctx.dry_run_file)
+        .and_then(|mut f| f.write_all(rec.as_bytes()));
+    fatal_no_log(&res);
+}
+
+// Executes SQL statement, returns number of affected rows or error
+// In dry run mode writes are only recorded and report 0 affected rows
+pub fn exec_sql_with_err(
+    client: &mut Client,
+    ctx: &Ctx,
+    sql: &str,
+    args: &[&(dyn ToSql + Sync)],
+) -> Result<u64, postgres::Error> {
+    if ctx.dry_run && is_write_sql(sql) {
+        record_dry_run(ctx, sql, args);
+        return Ok(0);
+    }
+    let start = Instant::now();
+    let res = client.execute(sql, args);
+    log_sql(ctx, sql, args, start.elapsed(), res.as_ref().ok().copied());
+    res
+}
+
+// Executes SQL statement, fatal on error
+pub fn exec_sql(client: &mut Client, ctx: &Ctx, sql: &str, args: &[&(dyn ToSql + Sync)]) -> u64 {
+    let res = exec_sql_with_err(client, ctx, sql, args);
+    fatal_no_log(&res);
+    res.expect("cannot execute SQL")
+}
+
+// Executes SQL query, returns rows or error
+// In dry run mode writes (like "insert .. returning") are only recorded and return no rows
+pub fn query_sql_with_err(
+    client: &mut Client,
+    ctx: &Ctx,
+    sql: &str,
+    args: &[&(dyn ToSql + Sync)],
+) -> Result<Vec<Row>, postgres::Error> {
+    if ctx.dry_run && is_write_sql(sql) {
+        record_dry_run(ctx, sql, args);
+        return Ok(vec![]);
+    }
+    let start = Instant::now();
+    let res = client.query(sql, args);
+    let rows = res.as_ref().ok().map(|rows| rows.len() as u64);
+    log_sql(ctx, sql, args, start.elapsed(), rows);
+    res
+}
+
+// Executes SQL query, fatal on error
+pub fn query_sql(client: &mut Client, ctx: &Ctx, sql: &str, args: &[&(dyn ToSql + Sync)]) -> Vec<Row> {
+    let res = query_sql_with_err(client, ctx, sql, args);
+    fatal_no_log(&res);
+    res.expect("cannot execute SQL query")
+}
+
+// Executes multiple semicolon separated SQL statements without parameters
+pub fn batch_sql_with_err(client: &mut Client, ctx: &Ctx, sql: &str) -> Result<(), postgres::Error> {
+    if ctx.dry_run && split_sql(sql).into_iter().any(is_write_sql) {
+        record_dry_run(ctx, sql, &[]);
+        return Ok(());
+    }
+    let start = Instant::now();
+    let res = client.batch_execute(sql);
+    log_sql(ctx, sql, &[], start.elapsed(), res.as_ref().ok().map(|_| 0));
+    res
+}
+
+// Executes multiple semicolon separated SQL statements, fatal on error
+pub fn batch_sql(client: &mut Client, ctx: &Ctx, sql: &str) {
+    fatal_no_log(&batch_sql_with_err(client, ctx, sql));
+}
+
+// Query result with all values as text, as returned by the simple query protocol
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextRows {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+// Executes one or more semicolon separated SQL statements without parameters, returns rows of the last statement returning rows
+// Values of any type (including numeric) are returned as text
+// In dry run mode statements containing writes are only recorded and return no rows
+pub fn text_query_sql_with_err(client: &mut Client, ctx: &Ctx, sql: &str) -> Result<TextRows, postgres::Error> {
+    if ctx.dry_run && split_sql(sql).into_iter().any(is_write_sql) {
+        record_dry_run(ctx, sql, &[]);
+        return Ok(TextRows::default());
+    }
+    let start = Instant::now();
+    let res = client.simple_query(sql);
+    let mut result = TextRows::default();
+    if let Ok(msgs) = &res {
+        for msg in msgs {
+            match msg {
+                postgres::SimpleQueryMessage::RowDescription(cols) => {
+                    result = TextRows {
+                        columns: cols.iter().map(|c| c.name().to_string()).collect(),
+                        rows: vec![],
+                    };
+                }
+                postgres::SimpleQueryMessage::Row(row) => {
+                    result
+                        .rows
+                        .push((0..row.len()).map(|i| row.get(i).map(|v| v.to_string())).collect());
+                }
+                _ => {}
+            }
+        }
+    }
+    log_sql(ctx, sql, &[], start.elapsed(), res.as_ref().ok().map(|_| result.rows.len() as u64));
+    res.map(|_| result)
+}
+
+#[cfg(test)]
+mod pgconn_tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_secrets() {
+        let cases = [
+            ("alter role gha_admin password 'secret'", "alter role gha_admin password '***'"),
+            ("alter role x with password = 'it''s'", "alter role x with password = '***'"),
+            ("host=h user=u password=secret dbname=d", "host=h user=u password=*** dbname=d"),
+            ("select password from users where password = $1", "select password from users where password = $1"),
+            ("select 'password' as x, 'passwordless'", "select 'password' as x, 'passwordless'"),
+        ];
+        for (sql, expected) in cases {
+            assert_eq!(mask_secrets(sql), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_mask_params() {
+        let ctx = Ctx {
+            pg_pass: "password".to_string(),
+            ..Default::default()
+        };
+        let (pass, text, id) = ("password".to_string(), "my password is long", 1i64);
+        let conn = "password=password host=h";
+        assert_eq!(
+            mask_params(&ctx, &[&pass, &text, &id, &conn]),
+            r#"[***, "my password is long", 1, "password=*** host=h"]"#
+        );
+    }
+
+    #[test]
+    fn test_is_write_sql() {
+        let cases = [
+            ("select 1", false),
+            ("select 'update' as action", false),
+            ("select \"delete\" from t", false),
+            ("select E'it\\'s an update' from t", false),
+            ("select $$insert$$, $tag$ delete $tag$", false),
+            ("select 1 -- update\n", false),
+            ("/* insert */ select 1", false),
+            ("select * into t2 from t", true),
+            ("with x as (delete from t returning *) select * from x", true),
+            ("  (select 1)", false),
+            ("update t set a = 'select'", true),
+            ("-- select\ninsert into t values(1)", true),
+            ("create table t(i int)", true),
+            ("begin", false),
+            ("", false),
+        ];
+        for (sql, expected) in cases {
+            assert_eq!(is_write_sql(sql), expected, "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_split_sql() {
+        let cases: [(&str, &[&str]); 7] = [
+            ("select 1; select 2;", &["select 1", " select 2"]),
+            ("select ';'; select 2", &["select ';'", " select 2"]),
+            ("select 'it''s;'", &["select 'it''s;'"]),
+            ("select \"a;b\" from t", &["select \"a;b\" from t"]),
+            (
+                "do $$ begin update t set a = 1; end $$; select 1",
+                &["do $$ begin update t set a = 1; end $$", " select 1"],
+            ),
+            ("select $1; -- a; b\nselect 2 /* ; */", &["select $1", " -- a; b\nselect 2 /* ; */"]),
+            ("select 'unterminated; update t", &["select 'unterminated; update t"]),
+        ];
+        for (sql, expected) in cases {
+            assert_eq!(split_sql(sql), expected, "{sql}");
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// skip_dates.yaml: hours known to be broken or missing in GHA archives
+// skip_dates:
+//   - '2016-10-21 18:00:00'
+#[derive(Debug, Deserialize)]
+struct SkipDatesYaml {
+    #[serde(default)]
+    skip_dates: Vec<String>,
+}
+
+// Parses skip_dates.yaml contents, dates are truncated to full hours
+pub fn parse_skip_dates(data: &str) -> Result<HashSet<DateTime<Utc>>, String> {
+    let yaml: SkipDatesYaml = serde_yaml::from_str(data).map_err(|e| e.to_string())?;
+    let mut dates = HashSet::new();
+    for date in &yaml.skip_dates {
+        let dt = time_parse_any(date).map_err(|e| format!("skip date: {e}"))?;
+        dates.insert(hour_start(dt));
+    }
+    Ok(dates)
+}
+
+// Reads hours to skip from GHA2DB_SKIP_DATES_YAML (resolved via GHA2DB_DATADIR)
+// A missing default file means nothing to skip, a missing file set explicitly is an error
+pub fn skip_dates(ctx: &Ctx) -> Result<HashSet<DateTime<Utc>>, String> {
+    let path = ctx.data_file(&ctx.skip_dates_yaml);
+    let data = match std::fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e)
+            if e.kind() == std::io::ErrorKind::NotFound
+                && !env_is_set("GHA2DB_SKIP_DATES_YAML") =>
+        {
+            if ctx.debug > 0 {
+                printf(&format!("{path}: not found, no hours to skip"));
+            }
+            return Ok(HashSet::new());
+        }
+        Err(e) => return Err(format!("{path}: {e}")),
+    };
+    parse_skip_dates(&data).map_err(|e| format!("{path}: {e}"))
+}
+
+#[cfg(test)]
+mod skip_dates_tests {
+    use super::*;
+
+    fn hour(s: &str) -> DateTime<Utc> {
+        parse_gha_time(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_skip_dates() {
+        let data = "skip_dates:
+  - '2016-10-21 18:00:00'
+  - '2016-10-22 07:45'
+  - 2016-10-23T05:59:59Z
+  - '2016-10-24'
+  - ' 2016-10-21 18:30:00 '
+";
+        let dates = parse_skip_dates(data).unwrap();
+        let expected = HashSet::from([
+            hour("2016-10-21T18:00:00Z"),
+            hour("2016-10-22T07:00:00Z"),
+            hour("2016-10-23T05:00:00Z"),
+            hour("2016-10-24T00:00:00Z"),
+        ]);
+        assert_eq!(dates, expected);
+
+        for data in ["", "# nothing to skip\n", "skip_dates: []\n", "skip_dates:\n"] {
+            assert_eq!(parse_skip_dates(data), Ok(HashSet::new()), "{data:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_skip_dates_invalid() {
+        for (data, error) in [
+            (
+                "skip_dates:\n  - '2016-13-01 00:00:00'\n",
+                "skip date: invalid date \"2016-13-01 00:00:00\", expected 'YYYY-MM-DD HH:MI:SS'",
+            ),
+            (
+                "skip_dates:\n  - '2016-10-21 18:00:00'\n  - yesterday\n",
+                "skip date: invalid date \"yesterday\"",
+            ),
+            ("skip_dates: '2016-10-21 18:00:00'\n", "skip_dates: invalid type: string"),
+            (
+                "skip_dates:\n  - [2016]\n",
+                "skip_dates[0]: invalid type: sequence, expected a string at line 2 column 5",
+            ),
+        ] {
+            let err = parse_skip_dates(data).unwrap_err();
+            assert!(err.starts_with(error), "{data:?}: {err}");
+        }
+    }
+}
@@ -21,3 +21,108 @@ where
         }
     }
 }
+
+// Removes NUL bytes (Postgres cannot store them) and truncates to at most `n` bytes on a character boundary
+pub fn trunc_to_bytes(s: &str, n: usize) -> String {
+    let mut s = s.replace('\0', "");
+    if s.len() > n {
+        let mut i = n;
+        while !s.is_char_boundary(i) {
+            i -= 1;
+        }
+        s.truncate(i);
+    }
+    s
+}
+
+// Postgres identifier: truncated to PG_MAX_IDENT bytes like Postgres does, so names match catalog entries
+pub fn pg_ident(s: &str) -> String {
+    trunc_to_bytes(s, PG_MAX_IDENT)
+}
+
+// Folds Unicode into ASCII: decomposes (NFKD), drops control and non-ASCII characters (accents), recomposes (NFC)
+// "Zürich" -> "Zurich", "ﬁ" -> "fi", "\tx\n" -> "x", "日本" -> ""
+pub fn strip_unicode(s: &str) -> String {
+    s.nfkd().filter(|c| (' '..'\u{7f}').contains(c)).nfc().collect()
+}
+
+// Series and column name from a free-form name (repo group, company, country, ...), same as Go's NormalizeName:
+// trimmed, lower case, Unicode folded, "-", "/", "." and spaces replaced with "_", other punctuation removed
+// For example "Ünïcode Org/Repo-Name" -> "unicode_org_repo_name", "日本" -> "" (see ts_table)
+pub fn normalize_name(s: &str) -> String {
+    strip_unicode(&s.trim().to_lowercase())
+        .chars()
+        .filter_map(|c| match c {
+            '-' | '/' | '.' | ' ' => Some('_'),
+            ',' | '\'' | '"' | ')' | '(' | ']' | '[' | '`' | '$' | ':' | ';' | '!' | '?' | '@' | '#' | '%' | '^'
+            | '&' | '*' | '+' | '=' | '{' | '}' | '|' | '\\' | '<' | '>' | '~' => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+// Parses UTC date-time in one of the formats used in env variables and YAML files: "YYYY-MM-DD HH:MI:SS", "YYYY-MM-DD HH:MI", "YYYY-MM-DDTHH:MI:SSZ" or "YYYY-MM-DD"
+pub fn time_parse_any(s: &str) -> Result<DateTime<Utc>, String> {
+    let s = s.trim();
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%SZ"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .map(|dt| dt.and_utc())
+        .ok_or_else(|| format!("invalid date {s:?}, expected 'YYYY-MM-DD HH:MI:SS'"))
+}
+
+#[cfg(test)]
+mod string_tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_unicode() {
+        for (s, expected) in [
+            ("Zürich", "Zurich"),
+            ("ﬁ", "fi"),
+            ("Ångström", "Angstrom"),
+            ("naïve café", "naive cafe"),
+            ("Straße", "Strae"),
+            ("\tx\n", "x"),
+            ("日本", ""),
+            ("日本 Org", " Org"),
+            ("", ""),
+        ] {
+            assert_eq!(strip_unicode(s), expected, "{s:?}");
+        }
+    }
+
+    // Expected values follow Go's NormalizeName: trimmed before Unicode is stripped, so spaces
+    // next to removed characters stay as "_"
+    #[test]
+    fn test_normalize_name() {
+        for (s, expected) in [
+            ("Ünïcode Org/Repo-Name", "unicode_org_repo_name"),
+            ("  Kubernetes/kubectl ", "kubernetes_kubectl"),
+            ("日本 Org", "_org"),
+            ("Org 日本", "org_"),
+            ("Org 日本 Name", "org__name"),
+            ("\tTab\n", "tab"),
+            ("x\ty", "xy"),
+            ("A.B,C", "a_bc"),
+            ("SIG Apps", "sig_apps"),
+            ("Mozilla 🦊", "mozilla_"),
+            ("C++ (Lang)", "c_lang"),
+            ("it's \"ok\"!", "its_ok"),
+            ("a_b:c;d=e", "a_bcde"),
+            ("[x]{y}<z>|~`$@#%^&*?\\", "xyz"),
+            ("", ""),
+            ("   ", ""),
+            ("日本", ""),
+            (" 日本\t", ""),
+            ("🦊", ""),
+        ] {
+            assert_eq!(normalize_name(s), expected, "{s:?}");
+        }
+    }
+}
@@ -0,0 +1,654 @@
+// gha_* tables, see DevStats `structure` tool
+// Each event related table also has dup_* columns duplicating event's actor/repo/type/date to avoid joins
+// This is schema version 1 applied by migration 1 and must not change, add new statements to STRUCTURE_CHANGES instead
+const STRUCTURE_TABLES: &[&str] = &[
+    "create table if not exists gha_events(
+        id bigint not null primary key,
+        type varchar(40) not null,
+        actor_id bigint not null,
+        repo_id bigint not null,
+        public boolean not null,
+        created_at timestamp not null,
+        org_id bigint,
+        forkee_id bigint,
+        dup_actor_login varchar(120) not null,
+        dup_repo_name varchar(160) not null
+    )",
+    "create table if not exists gha_actors(
+        id bigint not null primary key,
+        login varchar(120) not null,
+        name varchar(120),
+        country_id varchar(2),
+        country_name text,
+        sex varchar(1),
+        sex_prob double precision,
+        tz varchar(40),
+        tz_offset int,
+        age int
+    )",
+    "create table if not exists gha_actors_emails(
+        actor_id bigint not null,
+        email varchar(120) not null,
+        origin smallint not null default 0,
+        primary key(actor_id, email)
+    )",
+    "create table if not exists gha_actors_names(
+        actor_id bigint not null,
+        name varchar(120) not null,
+        origin smallint not null default 0,
+        primary key(actor_id, name)
+    )",
+    "create table if not exists gha_companies(
+        name varchar(160) not null primary key
+    )",
+    "create table if not exists gha_actors_affiliations(
+        actor_id bigint not null,
+        company_name varchar(160) not null,
+        original_company_name varchar(160) not null,
+        dt_from timestamp not null,
+        dt_to timestamp not null,
+        source varchar(30) not null default '',
+        primary key(actor_id, company_name, dt_from, dt_to)
+    )",
+    "create table if not exists gha_repos(
+        id bigint not null,
+        name varchar(160) not null,
+        org_id bigint,
+        org_login varchar(100),
+        repo_group varchar(80),
+        alias varchar(160),
+        license_key varchar(30),
+        license_name varchar(160),
+        license_prob double precision,
+        created_at timestamp,
+        updated_at timestamp,
+        primary key(id, name)
+    )",
+    "create table if not exists gha_orgs(
+        id bigint not null primary key,
+        login varchar(100) not null
+    )",
+    "create table if not exists gha_payloads(
+        event_id bigint not null primary key,
+        push_id bigint,
+        size int,
+        ref varchar(200),
+        head varchar(40),
+        befor varchar(40),
+        action varchar(40),
+        issue_id bigint,
+        pull_request_id bigint,
+        comment_id bigint,
+        ref_type varchar(20),
+        master_branch varchar(200),
+        description text,
+        number int,
+        forkee_id bigint,
+        release_id bigint,
+        member_id bigint,
+        commit varchar(40),
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null
+    )",
+    "create table if not exists gha_commits(
+        sha varchar(40) not null,
+        event_id bigint not null,
+        author_name varchar(160) not null,
+        encrypted_email varchar(160) not null,
+        message text not null,
+        is_distinct boolean not null,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        primary key(sha, event_id)
+    )",
+    "create table if not exists gha_pages(
+        sha varchar(40) not null,
+        event_id bigint not null,
+        action varchar(20) not null,
+        title varchar(300) not null,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        primary key(sha, event_id, action, title)
+    )",
+    "create table if not exists gha_comments(
+        id bigint not null primary key,
+        event_id bigint not null,
+        body text not null,
+        created_at timestamp not null,
+        updated_at timestamp not null,
+        user_id bigint not null,
+        commit_id varchar(40),
+        original_commit_id varchar(40),
+        diff_hunk text,
+        position int,
+        original_position int,
+        path text,
+        pull_request_review_id bigint,
+        line int,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        dup_user_login varchar(120) not null
+    )",
+    "create table if not exists gha_issues(
+        id bigint not null,
+        event_id bigint not null,
+        assignee_id bigint,
+        body text,
+        closed_at timestamp,
+        comments int not null,
+        created_at timestamp not null,
+        locked boolean not null,
+        milestone_id bigint,
+        number int not null,
+        state varchar(20) not null,
+        title text not null,
+        updated_at timestamp not null,
+        user_id bigint not null,
+        is_pull_request boolean not null,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        dup_user_login varchar(120) not null,
+        dupn_assignee_login varchar(120),
+        primary key(id, event_id)
+    )",
+    "create table if not exists gha_issues_assignees(
+        issue_id bigint not null,
+        event_id bigint not null,
+        assignee_id bigint not null,
+        primary key(issue_id, event_id, assignee_id)
+    )",
+    "create table if not exists gha_labels(
+        id bigint not null primary key,
+        name varchar(160) not null,
+        color varchar(8) not null,
+        is_default boolean
+    )",
+    "create table if not exists gha_issues_labels(
+        issue_id bigint not null,
+        event_id bigint not null,
+        label_id bigint not null,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        dup_issue_number int not null,
+        dup_label_name varchar(160) not null,
+        primary key(issue_id, event_id, label_id)
+    )",
+    "create table if not exists gha_milestones(
+        id bigint not null,
+        event_id bigint not null,
+        closed_at timestamp,
+        closed_issues int not null,
+        created_at timestamp not null,
+        creator_id bigint,
+        description text,
+        due_on timestamp,
+        number int not null,
+        open_issues int not null,
+        state varchar(20) not null,
+        title varchar(200) not null,
+        updated_at timestamp not null,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        dupn_creator_login varchar(120),
+        primary key(id, event_id)
+    )",
+    "create table if not exists gha_forkees(
+        id bigint not null,
+        event_id bigint not null,
+        name varchar(100) not null,
+        full_name varchar(200) not null,
+        owner_id bigint not null,
+        description text,
+        fork boolean not null,
+        created_at timestamp not null,
+        updated_at timestamp not null,
+        pushed_at timestamp,
+        homepage text,
+        size int not null,
+        language varchar(80),
+        stargazers_count int not null,
+        has_issues boolean not null,
+        has_projects boolean,
+        has_downloads boolean not null,
+        has_wiki boolean not null,
+        has_pages boolean,
+        forks int not null,
+        default_branch varchar(200) not null,
+        open_issues int not null,
+        watchers int not null,
+        public boolean,
+        org_id bigint,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        dup_owner_login varchar(120) not null,
+        primary key(id, event_id)
+    )",
+    "create table if not exists gha_releases(
+        id bigint not null,
+        event_id bigint not null,
+        tag_name varchar(200) not null,
+        target_commitish varchar(200) not null,
+        name varchar(200),
+        draft boolean not null,
+        author_id bigint not null,
+        prerelease boolean not null,
+        created_at timestamp not null,
+        published_at timestamp,
+        body text,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        dup_author_login varchar(120) not null,
+        primary key(id, event_id)
+    )",
+    "create table if not exists gha_assets(
+        id bigint not null,
+        event_id bigint not null,
+        name varchar(200) not null,
+        label varchar(120),
+        uploader_id bigint not null,
+        content_type varchar(80) not null,
+        state varchar(20) not null,
+        size int not null,
+        download_count int not null,
+        created_at timestamp not null,
+        updated_at timestamp not null,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        dup_uploader_login varchar(120) not null,
+        primary key(id, event_id)
+    )",
+    "create table if not exists gha_releases_assets(
+        release_id bigint not null,
+        event_id bigint not null,
+        asset_id bigint not null,
+        primary key(release_id, event_id, asset_id)
+    )",
+    "create table if not exists gha_pull_requests(
+        id bigint not null,
+        event_id bigint not null,
+        user_id bigint not null,
+        base_sha varchar(40) not null,
+        head_sha varchar(40) not null,
+        merged_by_id bigint,
+        assignee_id bigint,
+        milestone_id bigint,
+        number int not null,
+        state varchar(20) not null,
+        locked boolean,
+        title text not null,
+        body text,
+        created_at timestamp not null,
+        updated_at timestamp not null,
+        closed_at timestamp,
+        merged_at timestamp,
+        merge_commit_sha varchar(40),
+        merged boolean,
+        mergeable boolean,
+        rebaseable boolean,
+        mergeable_state varchar(20),
+        comments int,
+        review_comments int,
+        maintainer_can_modify boolean,
+        commits int,
+        additions int,
+        deletions int,
+        changed_files int,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        dup_user_login varchar(120) not null,
+        dupn_assignee_login varchar(120),
+        dupn_merged_by_login varchar(120),
+        primary key(id, event_id)
+    )",
+    "create table if not exists gha_pull_requests_assignees(
+        pull_request_id bigint not null,
+        event_id bigint not null,
+        assignee_id bigint not null,
+        primary key(pull_request_id, event_id, assignee_id)
+    )",
+    "create table if not exists gha_pull_requests_requested_reviewers(
+        pull_request_id bigint not null,
+        event_id bigint not null,
+        requested_reviewer_id bigint not null,
+        primary key(pull_request_id, event_id, requested_reviewer_id)
+    )",
+    "create table if not exists gha_branches(
+        sha varchar(40) not null,
+        event_id bigint not null,
+        user_id bigint,
+        repo_id bigint not null,
+        label varchar(200) not null,
+        ref varchar(200) not null,
+        dupn_forkee_name varchar(160),
+        dupn_user_login varchar(120),
+        primary key(sha, event_id)
+    )",
+    "create table if not exists gha_teams(
+        id bigint not null,
+        event_id bigint not null,
+        name varchar(120) not null,
+        slug varchar(100) not null,
+        permission varchar(20) not null,
+        dup_actor_id bigint not null,
+        dup_actor_login varchar(120) not null,
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        primary key(id, event_id)
+    )",
+    "create table if not exists gha_teams_repositories(
+        team_id bigint not null,
+        event_id bigint not null,
+        repository_id bigint not null,
+        primary key(team_id, event_id, repository_id)
+    )",
+    "create table if not exists gha_texts(
+        event_id bigint,
+        body text,
+        created_at timestamp not null,
+        actor_id bigint not null,
+        actor_login varchar(120) not null,
+        repo_id bigint not null,
+        repo_name varchar(160) not null,
+        type varchar(40) not null
+    )",
+    "create table if not exists gha_parsed(
+        dt timestamp not null primary key
+    )",
+    "create table if not exists gha_events_commits_files(
+        sha varchar(40) not null,
+        event_id bigint not null,
+        path text not null,
+        size bigint not null,
+        dt timestamp not null,
+        repo_group varchar(80),
+        dup_repo_id bigint not null,
+        dup_repo_name varchar(160) not null,
+        dup_type varchar(40) not null,
+        dup_created_at timestamp not null,
+        primary key(sha, event_id, path)
+    )",
+    "create table if not exists gha_skip_commits(
+        sha varchar(40) not null primary key,
+        dt timestamp not null,
+        reason int not null
+    )",
+    "create table if not exists gha_postprocess_scripts(
+        ord int not null,
+        path text not null,
+        primary key(ord, path)
+    )",
+    "create table if not exists gha_computed(
+        metric text not null,
+        dt timestamp not null,
+        primary key(metric, dt)
+    )",
+    "create table if not exists gha_vars(
+        name varchar(100) not null primary key,
+        value_i bigint,
+        value_f double precision,
+        value_s text,
+        value_dt timestamp
+    )",
+];
+
+// Schema changes made after version 1, each one is also a migration (migrations.rs) so existing databases get it
+const STRUCTURE_CHANGES: &[&[&str]] = &[
+    &[GHA_IMPORT_HOURS_TABLE],
+    &[GHA_ACTORS_IS_BOT],
+    GHA_SHARED_EVENT_ID,
+];
+
+// gha2db import checkpoints: one row per project and hour, status is "started", "done" or "missing" (no GHA file)
+const GHA_IMPORT_HOURS_TABLE: &str = "create table if not exists gha_import_hours(
+        project text not null,
+        dt timestamp not null,
+        status varchar(16) not null,
+        events bigint not null default 0,
+        written bigint not null default 0,
+        started_at timestamp not null default now(),
+        finished_at timestamp,
+        primary key(project, dt)
+    )";
+
+// Bot/automation accounts flag set by gha2db from bots.yaml patterns
+const GHA_ACTORS_IS_BOT: &str =
+    "alter table gha_actors add column if not exists is_bot boolean not null default false";
+
+// Event that wrote rows shared between events, gha2db keeps values from the earliest one (see GhaTable)
+// Rows written before this change have null event_id and are never replaced
+const GHA_SHARED_EVENT_ID: &[&str] = &[
+    "alter table gha_actors add column if not exists event_id bigint",
+    "alter table gha_orgs add column if not exists event_id bigint",
+    "alter table gha_repos add column if not exists event_id bigint",
+    "alter table gha_labels add column if not exists event_id bigint",
+];
+
+// Indexes on gha_* tables, optional (GHA2DB_INDEX), slow to create on big databases
+const STRUCTURE_INDEXES: &[&str] = &[
+    "create index if not exists events_type_idx on gha_events(type)",
+    "create index if not exists events_actor_id_idx on gha_events(actor_id)",
+    "create index if not exists events_repo_id_idx on gha_events(repo_id)",
+    "create index if not exists events_org_id_idx on gha_events(org_id)",
+    "create index if not exists events_forkee_id_idx on gha_events(forkee_id)",
+    "create index if not exists events_created_at_idx on gha_events(created_at)",
+    "create index if not exists events_dup_actor_login_idx on gha_events(dup_actor_login)",
+    "create index if not exists events_dup_repo_name_idx on gha_events(dup_repo_name)",
+    "create index if not exists actors_login_idx on gha_actors(login)",
+    "create index if not exists actors_lower_login_idx on gha_actors(lower(login))",
+    "create index if not exists actors_country_id_idx on gha_actors(country_id)",
+    "create index if not exists actors_emails_email_idx on gha_actors_emails(email)",
+    "create index if not exists actors_affiliations_company_name_idx on gha_actors_affiliations(company_name)",
+    "create index if not exists actors_affiliations_dt_from_idx on gha_actors_affiliations(dt_from)",
+    "create index if not exists actors_affiliations_dt_to_idx on gha_actors_affiliations(dt_to)",
+    "create index if not exists repos_name_idx on gha_repos(name)",
+    "create index if not exists repos_org_id_idx on gha_repos(org_id)",
+    "create index if not exists repos_repo_group_idx on gha_repos(repo_group)",
+    "create index if not exists repos_alias_idx on gha_repos(alias)",
+    "create index if not exists orgs_login_idx on gha_orgs(login)",
+    "create index if not exists payloads_action_idx on gha_payloads(action)",
+    "create index if not exists payloads_issue_id_idx on gha_payloads(issue_id)",
+    "create index if not exists payloads_pull_request_id_idx on gha_payloads(pull_request_id)",
+    "create index if not exists payloads_comment_id_idx on gha_payloads(comment_id)",
+    "create index if not exists payloads_dup_type_idx on gha_payloads(dup_type)",
+    "create index if not exists payloads_dup_created_at_idx on gha_payloads(dup_created_at)",
+    "create index if not exists commits_event_id_idx on gha_commits(event_id)",
+    "create index if not exists commits_dup_actor_id_idx on gha_commits(dup_actor_id)",
+    "create index if not exists commits_dup_repo_id_idx on gha_commits(dup_repo_id)",
+    "create index if not exists commits_dup_created_at_idx on gha_commits(dup_created_at)",
+    "create index if not exists pages_event_id_idx on gha_pages(event_id)",
+    "create index if not exists pages_dup_created_at_idx on gha_pages(dup_created_at)",
+    "create index if not exists comments_event_id_idx on gha_comments(event_id)",
+    "create index if not exists comments_user_id_idx on gha_comments(user_id)",
+    "create index if not exists comments_created_at_idx on gha_comments(created_at)",
+    "create index if not exists comments_dup_repo_id_idx on gha_comments(dup_repo_id)",
+    "create index if not exists comments_dup_type_idx on gha_comments(dup_type)",
+    "create index if not exists issues_event_id_idx on gha_issues(event_id)",
+    "create index if not exists issues_user_id_idx on gha_issues(user_id)",
+    "create index if not exists issues_number_idx on gha_issues(number)",
+    "create index if not exists issues_state_idx on gha_issues(state)",
+    "create index if not exists issues_created_at_idx on gha_issues(created_at)",
+    "create index if not exists issues_closed_at_idx on gha_issues(closed_at)",
+    "create index if not exists issues_updated_at_idx on gha_issues(updated_at)",
+    "create index if not exists issues_is_pull_request_idx on gha_issues(is_pull_request)",
+    "create index if not exists issues_dup_repo_id_idx on gha_issues(dup_repo_id)",
+    "create index if not exists issues_dup_created_at_idx on gha_issues(dup_created_at)",
+    "create index if not exists issues_labels_label_id_idx on gha_issues_labels(label_id)",
+    "create index if not exists issues_labels_dup_created_at_idx on gha_issues_labels(dup_created_at)",
+    "create index if not exists issues_labels_dup_label_name_idx on gha_issues_labels(dup_label_name)",
+    "create index if not exists labels_name_idx on gha_labels(name)",
+    "create index if not exists milestones_event_id_idx on gha_milestones(event_id)",
+    "create index if not exists forkees_event_id_idx on gha_forkees(event_id)",
+    "create index if not exists forkees_owner_id_idx on gha_forkees(owner_id)",
+    "create index if not exists releases_event_id_idx on gha_releases(event_id)",
+    "create index if not exists releases_dup_created_at_idx on gha_releases(dup_created_at)",
+    "create index if not exists assets_event_id_idx on gha_assets(event_id)",
+    "create index if not exists pull_requests_event_id_idx on gha_pull_requests(event_id)",
+    "create index if not exists pull_requests_user_id_idx on gha_pull_requests(user_id)",
+    "create index if not exists pull_requests_number_idx on gha_pull_requests(number)",
+    "create index if not exists pull_requests_state_idx on gha_pull_requests(state)",
+    "create index if not exists pull_requests_created_at_idx on gha_pull_requests(created_at)",
+    "create index if not exists pull_requests_closed_at_idx on gha_pull_requests(closed_at)",
+    "create index if not exists pull_requests_merged_at_idx on gha_pull_requests(merged_at)",
+    "create index if not exists pull_requests_dup_repo_id_idx on gha_pull_requests(dup_repo_id)",
+    "create index if not exists pull_requests_dup_created_at_idx on gha_pull_requests(dup_created_at)",
+    "create index if not exists branches_repo_id_idx on gha_branches(repo_id)",
+    "create index if not exists teams_event_id_idx on gha_teams(event_id)",
+    "create index if not exists texts_event_id_idx on gha_texts(event_id)",
+    "create index if not exists texts_created_at_idx on gha_texts(created_at)",
+    "create index if not exists texts_actor_id_idx on gha_texts(actor_id)",
+    "create index if not exists texts_repo_id_idx on gha_texts(repo_id)",
+    "create index if not exists texts_type_idx on gha_texts(type)",
+    "create index if not exists events_commits_files_path_idx on gha_events_commits_files(path)",
+    "create index if not exists events_commits_files_dt_idx on gha_events_commits_files(dt)",
+    "create index if not exists events_commits_files_repo_group_idx on gha_events_commits_files(repo_group)",
+    "create index if not exists computed_dt_idx on gha_computed(dt)",
+];
+
+// DB tools: summary tables, views and helper schemas used by metrics SQLs
+const STRUCTURE_TOOLS: &[&str] = &[
+    "create table if not exists gha_issues_events_labels(
+        issue_id bigint not null,
+        event_id bigint not null,
+        label_id bigint not null,
+        label_name varchar(160) not null,
+        created_at timestamp not null,
+        actor_id bigint not null,
+        actor_login varchar(120) not null,
+        repo_id bigint not null,
+        repo_name varchar(160) not null,
+        type varchar(40) not null,
+        issue_number int not null
+    )",
+    "create index if not exists issues_events_labels_issue_id_idx on gha_issues_events_labels(issue_id)",
+    "create index if not exists issues_events_labels_label_name_idx on gha_issues_events_labels(label_name)",
+    "create index if not exists issues_events_labels_created_at_idx on gha_issues_events_labels(created_at)",
+    "create index if not exists issues_events_labels_repo_id_idx on gha_issues_events_labels(repo_id)",
+    "create table if not exists gha_issues_pull_requests(
+        issue_id bigint not null,
+        pull_request_id bigint not null,
+        number int not null,
+        repo_id bigint not null,
+        repo_name varchar(160) not null,
+        created_at timestamp not null,
+        primary key(issue_id, pull_request_id)
+    )",
+    "create index if not exists issues_pull_requests_number_idx on gha_issues_pull_requests(number)",
+    "create index if not exists issues_pull_requests_repo_id_idx on gha_issues_pull_requests(repo_id)",
+    "create index if not exists issues_pull_requests_created_at_idx on gha_issues_pull_requests(created_at)",
+    "create or replace view gha_view_last_week_texts as
+        select event_id, body, created_at, actor_id, actor_login, repo_id, repo_name, type
+        from gha_texts
+        where created_at > now() - '1 week'::interval",
+    "create or replace view gha_view_last_month_texts as
+        select event_id, body, created_at, actor_id, actor_login, repo_id, repo_name, type
+        from gha_texts
+        where created_at > now() - '1 month'::interval",
+    "create schema if not exists current_state",
+    "create or replace function current_state.label_prefix(some_label text) returns text as $$
+        select case
+            when $1 ~ '^[a-zA-Z0-9]+[/_-]' then regexp_replace($1, '^([a-zA-Z0-9]+)[/_-].+$', '\\1')
+            else 'general'
+        end;
+    $$ language sql immutable",
+    "create or replace function current_state.label_suffix(some_label text) returns text as $$
+        select case
+            when $1 ~ '^[a-zA-Z0-9]+[/_-]' then regexp_replace($1, '^[a-zA-Z0-9]+[/_-](.+)$', '\\1')
+            else $1
+        end;
+    $$ language sql immutable",
+    "insert into gha_postprocess_scripts(ord, path) values
+        (0, 'util_sql/gha_issues_events_labels.sql'),
+        (1, 'util_sql/gha_issues_pull_requests.sql')
+        on conflict do nothing",
+];
+
+// Runs one structure phase, each statement separately so errors point to the failing one
+fn structure_phase(client: &mut Client, ctx: &Ctx, phase: &str, sqls: &[&str]) {
+    let start = Instant::now();
+    for sql in sqls {
+        exec_sql(client, ctx, sql, &[]);
+    }
+    if ctx.debug >= 0 {
+        printf(&format!(
+            "structure {phase}: {} statements took {:?}",
+            sqls.len(),
+            start.elapsed()
+        ));
+    }
+}
+
+// Creates gha_* tables in the latest schema version and records all migrations as applied
+pub fn create_tables(client: &mut Client, ctx: &Ctx) {
+    structure_phase(client, ctx, "tables", STRUCTURE_TABLES);
+    structure_phase(client, ctx, "schema changes", &STRUCTURE_CHANGES.concat());
+    fatal_no_log_str(migrations_stamp(client, ctx));
+}
+
+// Creates gha_* tables indexes
+pub fn create_indexes(client: &mut Client, ctx: &Ctx) {
+    structure_phase(client, ctx, "indexes", STRUCTURE_INDEXES);
+}
+
+// Creates summary tables, views and helper functions
+pub fn create_tools(client: &mut Client, ctx: &Ctx) {
+    structure_phase(client, ctx, "tools", STRUCTURE_TOOLS);
+}
+
+// Creates all enabled structure phases: tables (unless GHA2DB_SKIPTABLE), indexes (if GHA2DB_INDEX), tools (unless GHA2DB_SKIPTOOLS)
+pub fn structure(client: &mut Client, ctx: &Ctx) {
+    if ctx.table {
+        create_tables(client, ctx);
+    }
+    if ctx.index {
+        create_indexes(client, ctx);
+    }
+    if ctx.tools {
+        create_tools(client, ctx);
+    }
+}
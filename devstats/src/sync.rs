@@ -0,0 +1,466 @@
+// gha2db_sync: hourly pipeline of a single project (GHA2DB_PROJECT): import new GHA hours, GitHub API enrichment, repos,
+// metrics from metrics.yaml, tags, columns, annotations and vars; each step can be skipped with its GHA2DB_SKIP* variable
+
+// Single step of the sync: skipped steps have "skipped" result and zero duration
+#[derive(Debug, Clone)]
+pub struct SyncStep {
+    pub name: String,
+    pub result: Result<String, String>,
+    pub took: Duration,
+}
+
+// Collects steps in the order they were run
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub steps: Vec<SyncStep>,
+}
+
+impl SyncReport {
+    // Runs the step unless skip is set and records its result and duration
+    fn step<F>(&mut self, name: &str, skip: bool, f: F)
+    where
+        F: FnOnce() -> Result<String, String>,
+    {
+        if skip {
+            self.skipped(name);
+            return;
+        }
+        let start = Instant::now();
+        let result = f();
+        if let Err(e) = &result {
+            printf(&format!("{name}: error: {e}"));
+        }
+        self.steps.push(SyncStep {
+            name: name.to_string(),
+            result,
+            took: start.elapsed(),
+        });
+    }
+
+    fn skipped(&mut self, name: &str) {
+        self.steps.push(SyncStep {
+            name: name.to_string(),
+            result: Ok("skipped".to_string()),
+            took: Duration::ZERO,
+        });
+    }
+
+    pub fn failed(&self) -> usize {
+        self.steps.iter().filter(|s| s.result.is_err()).count()
+    }
+}
+
+// Single calc_metric run planned by sync: time series period with aggregate ("d", "w7") or histogram quick ranges ("d,w", "anno")
+#[derive(Debug, Clone)]
+pub struct SyncMetricTask<'a> {
+    pub metric: &'a Metric,
+    pub period: String,
+}
+
+impl SyncMetricTask<'_> {
+    pub fn name(&self) -> String {
+        format!("calc_metric {} {} {}", self.metric.sql, self.metric.series_name_or_func, self.period)
+    }
+
+    // gha_computed key of time series metric period, its latest dt is the end of the last successful sync
+    fn computed_key(&self) -> String {
+        format!(
+            "ts:{}:{}:{}",
+            self.metric.sql,
+            normalize_name(&self.metric.series_name_or_func),
+            self.period
+        )
+    }
+}
+
+// GHA2DB_ONLY_METRICS and GHA2DB_SKIP_METRICS filter metrics by their "sql" name
+fn sync_metric_selected(ctx: &Ctx, metric: &Metric) -> bool {
+    (ctx.only_metrics.is_empty() || ctx.only_metrics.contains_key(&metric.sql)) && !ctx.skip_metrics.contains_key(&metric.sql)
+}
+
+// GHA2DB_ONLY_METRICS and GHA2DB_SKIP_METRICS names that match no metric, usually typos
+pub fn sync_metrics_unmatched(ctx: &Ctx, metrics: &[Metric]) -> Vec<String> {
+    let mut names: Vec<String> = ctx
+        .only_metrics
+        .keys()
+        .chain(ctx.skip_metrics.keys())
+        .filter(|name| !metrics.iter().any(|m| m.sql == **name))
+        .cloned()
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// Plans calc_metric runs for the hour `to`: enabled and selected metrics of this project with periods scheduled at this hour
+// Tasks are shuffled unless GHA2DB_SKIP_RAND is set, so a failing or slow metric doesn't always delay the same ones
+pub fn sync_metric_tasks<'a>(
+    ctx: &Ctx,
+    rng: &mut dyn RandSource,
+    metrics: &'a [Metric],
+    to: DateTime<Utc>,
+) -> Result<Vec<SyncMetricTask<'a>>, String> {
+    let mut tasks = Vec::new();
+    for metric in metrics {
+        if metric.disabled
+            || (!metric.project.is_empty() && metric.project != ctx.project)
+            || !sync_metric_selected(ctx, metric)
+        {
+            continue;
+        }
+        if metric.histogram {
+            let periods: Vec<&str> = if metric.annotations_ranges {
+                vec!["anno"]
+            } else {
+                metric.periods.iter().map(|p| p.as_str()).collect()
+            };
+            let mut due = Vec::new();
+            for period in periods {
+                if compute_period_at_this_date(ctx, rng, period, to, true)? {
+                    due.push(period);
+                }
+            }
+            if !due.is_empty() {
+                tasks.push(SyncMetricTask {
+                    metric,
+                    period: due.join(","),
+                });
+            }
+            continue;
+        }
+        for period in &metric.periods {
+            if !compute_period_at_this_date(ctx, rng, period, to, false)? {
+                continue;
+            }
+            for agg in metric.aggregates() {
+                if metric.skips(period, agg) {
+                    continue;
+                }
+                tasks.push(SyncMetricTask {
+                    metric,
+                    period: if agg == 1 { period.clone() } else { format!("{period}{agg}") },
+                });
+            }
+        }
+    }
+    if !ctx.skip_rand {
+        rand_shuffle(rng, &mut tasks);
+    }
+    Ok(tasks)
+}
+
+// Last hour with data: last imported hour, or the hour of the latest event when imports have no checkpoints
+fn sync_last_hour(
+    client: &mut Client,
+    ctx: &Ctx,
+    key: &str,
+) -> Result<Option<DateTime<Utc>>, String> {
+    if let Some(dt) = last_imported_hour(client, ctx, key).map_err(|e| e.to_string())? {
+        return Ok(Some(dt));
+    }
+    let rows = query_sql_with_err(client, ctx, "select max(created_at) from gha_events", &[]).map_err(|e| e.to_string())?;
+    Ok(rows
+        .first()
+        .and_then(|row| row.get::<_, Option<NaiveDateTime>>(0))
+        .map(|dt| hour_start(dt.and_utc())))
+}
+
+// Time series metrics continue from the end of their last sync (recalculating its period), new ones from
+// start_from or GHA2DB_STARTDT, metrics with last_hours only calculate that many last hours
+fn sync_metric_from(
+    client: &mut Client,
+    ctx: &Ctx,
+    task: &SyncMetricTask,
+    to: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+    let rows = query_sql_with_err(
+        client,
+        ctx,
+        "select max(dt) from gha_computed where metric = $1",
+        &[&task.computed_key()],
+    )
+    .map_err(|e| e.to_string())?;
+    let start = task.metric.start_from.unwrap_or(ctx.default_start_date);
+    let mut from = rows
+        .first()
+        .and_then(|row| row.get::<_, Option<NaiveDateTime>>(0))
+        .map_or(start, |dt| dt.and_utc().max(start));
+    if task.metric.last_hours > 0 {
+        from = from.max(to - chrono::Duration::hours(task.metric.last_hours as i64));
+    }
+    Ok(from)
+}
+
+// Runs calc_metric tool for a single task, metric's env is added to its environment
+fn sync_metric(
+    client: &mut Client,
+    ctx: &Ctx,
+    task: &SyncMetricTask,
+    to: DateTime<Utc>,
+    env: &HashMap<String, String>,
+) -> Result<String, String> {
+    let from = if task.metric.histogram { to } else { sync_metric_from(client, ctx, task, to)? };
+    let (sfrom, sto) = (
+        from.format("%Y-%m-%d %H:%M:%S").to_string(),
+        to.format("%Y-%m-%d %H:%M:%S").to_string(),
+    );
+    let mut env = env.clone();
+    env.extend(task.metric.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+    exec_command(
+        ctx,
+        &[
+            "calc_metric",
+            &task.metric.sql,
+            &sfrom,
+            &sto,
+            &task.period,
+            &task.metric.series_name_or_func,
+        ],
+        &env,
+    )?;
+    if !task.metric.histogram {
+        exec_sql_with_err(
+            client,
+            ctx,
+            "insert into gha_computed(metric, dt) values($1, $2) on conflict do nothing",
+            &[&task.computed_key(), &to.naive_utc()],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(format!("{sfrom} - {sto}"))
+}
+
+// Runs the whole sync, steps that failed are reported and the sync continues with the next ones
+// (when ctx.exec_fatal is set failed tools end the process)
+pub fn gha2db_sync(ctx: &Ctx, filter: &GhaRepoFilter) -> Result<SyncReport, String> {
+    let mut report = SyncReport::default();
+    let mut client = pg_conn_with_err(ctx).map_err(|e| e.to_string())?;
+    let no_env = HashMap::new();
+    let key = import_key(ctx, filter);
+    // Metrics are read and GHA2DB_ONLY_METRICS/GHA2DB_SKIP_METRICS checked before the import, so typos show up
+    // even when there is no new data and a broken metrics.yaml doesn't wait for the import to fail
+    let yaml = if ctx.skip_tsdb { None } else { Some(metrics_yaml(ctx)?) };
+    for name in yaml.iter().flat_map(|yaml| sync_metrics_unmatched(ctx, &yaml.metrics)) {
+        printf(&format!("warning: metric {name:?} from GHA2DB_ONLY_METRICS or GHA2DB_SKIP_METRICS not found in {}", ctx.metrics_yaml));
+    }
+
+    // Postgres part: new GHA hours, GitHub API and repositories
+    report.step("gha2db", ctx.skip_pdb, || {
+        let from = last_imported_hour(&mut client, ctx, &key)
+            .map_err(|e| e.to_string())?
+            .map_or(ctx.default_start_date, |dt| dt + chrono::Duration::hours(1));
+        let to = hour_start(Utc::now()) - chrono::Duration::hours(1);
+        if from > to {
+            return Ok("up to date".to_string());
+        }
+        let stats = gha2db(ctx, from, to, filter)?;
+        Ok(format!(
+            "{} - {}: {} files, {} missing, {} events",
+            from.format("%Y-%m-%d %H"),
+            to.format("%Y-%m-%d %H"),
+            stats.files,
+            stats.files_missing,
+            stats.events
+        ))
+    });
+    report.step("ghapi2db", ctx.skip_pdb || ctx.skip_ghapi, || {
+        exec_command(ctx, &["ghapi2db"], &no_env).map(|_| "ok".to_string())
+    });
+    report.step("get_repos", ctx.skip_pdb || ctx.skip_get_repos, || {
+        exec_command(ctx, &["get_repos"], &no_env).map(|_| "ok".to_string())
+    });
+
+    match &yaml {
+        Some(yaml) => sync_tsdb(&mut report, &mut client, ctx, &key, &yaml.metrics)?,
+        None => {
+            for name in ["metrics", "tags", "columns", "annotations"] {
+                report.skipped(name);
+            }
+        }
+    }
+    report.step("vars", ctx.skip_vars, || {
+        exec_command(ctx, &["vars"], &no_env).map(|_| "ok".to_string())
+    });
+    Ok(report)
+}
+
+// Time series part: metrics scheduled at the last hour with data, tags, columns and annotations
+fn sync_tsdb(
+    report: &mut SyncReport,
+    client: &mut Client,
+    ctx: &Ctx,
+    key: &str,
+    metrics: &[Metric],
+) -> Result<(), String> {
+    let no_env = HashMap::new();
+    let Some(to) = sync_last_hour(client, ctx, key)? else {
+        report.step("metrics", false, || Ok("no data".to_string()));
+        return Ok(());
+    };
+    let mut rng = TimeRand::new();
+    // TS tables are reset once here, calc_metric runs must not truncate tables written by previous runs
+    let mut env = HashMap::new();
+    if ctx.reset_tsdb {
+        report.step("reset_tsdb", false, || {
+            reset_ts_tables(client, ctx).map(|n| format!("{n} tables truncated"))
+        });
+        env.insert("GHA2DB_RESETTSDB".to_string(), "".to_string());
+    }
+    let tasks = sync_metric_tasks(ctx, &mut rng, metrics, to)?;
+    if tasks.is_empty() {
+        report.step("metrics", false, || Ok("nothing scheduled".to_string()));
+    }
+    for task in &tasks {
+        report.step(&task.name(), false, || sync_metric(client, ctx, task, to, &env));
+    }
+    report.step("tags", ctx.skip_tags, || {
+        exec_command(ctx, &["tags"], &no_env).map(|_| "ok".to_string())
+    });
+    let columns = ctx.run_columns || compute_tags_cols_at_this_date(ctx, &mut rng, to);
+    report.step("columns", ctx.skip_columns || !columns, || {
+        exec_command(ctx, &["columns"], &no_env).map(|_| "ok".to_string())
+    });
+    report.step("annotations", ctx.skip_annotations, || {
+        exec_command(ctx, &["annotations"], &no_env).map(|_| "ok".to_string())
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod sync_tests {
+    use super::*;
+
+    // Always picks the first element, counts calls
+    struct ZeroRand(usize);
+
+    impl RandSource for ZeroRand {
+        fn below(&mut self, _n: u32) -> u32 {
+            self.0 += 1;
+            0
+        }
+    }
+
+    fn metric(sql: &str, periods: &[&str]) -> Metric {
+        Metric {
+            sql: sql.to_string(),
+            series_name_or_func: sql.to_string(),
+            periods: periods.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn metrics() -> Vec<Metric> {
+        vec![
+            Metric {
+                aggregate: vec![1, 7],
+                skip: vec!["w7".to_string()],
+                ..metric("prs", &["d", "w"])
+            },
+            metric("issues", &["m"]),
+            Metric {
+                histogram: true,
+                ..metric("hist_langs", &["d", "w"])
+            },
+            Metric {
+                histogram: true,
+                annotations_ranges: true,
+                ..metric("hist_anno", &["d"])
+            },
+            Metric {
+                disabled: true,
+                ..metric("disabled", &["d"])
+            },
+            Metric {
+                project: "other".to_string(),
+                ..metric("other_project", &["d"])
+            },
+        ]
+    }
+
+    fn names(list: &[&str]) -> HashMap<String, bool> {
+        list.iter().map(|n| (n.to_string(), true)).collect()
+    }
+
+    // All periods are due with GHA2DB_COMPUTE_ALL, tasks are in metrics.yaml order with GHA2DB_SKIP_RAND
+    fn sync_ctx(only: &[&str], skip: &[&str], skip_rand: bool) -> Ctx {
+        Ctx {
+            project: "acme".to_string(),
+            compute_all: true,
+            only_metrics: names(only),
+            skip_metrics: names(skip),
+            skip_rand,
+            ..Default::default()
+        }
+    }
+
+    fn task_names(ctx: &Ctx, rng: &mut dyn RandSource, metrics: &[Metric]) -> Vec<String> {
+        let to = time_parse_any("2021-03-17 10:00:00").unwrap();
+        sync_metric_tasks(ctx, rng, metrics, to)
+            .unwrap()
+            .iter()
+            .map(|t| format!("{} {}", t.metric.sql, t.period))
+            .collect()
+    }
+
+    #[test]
+    fn test_sync_metric_selected() {
+        let prs = metric("prs", &["d"]);
+        let issues = metric("issues", &["d"]);
+        let selected = |only: &[&str], skip: &[&str]| -> Vec<bool> {
+            let ctx = sync_ctx(only, skip, true);
+            vec![sync_metric_selected(&ctx, &prs), sync_metric_selected(&ctx, &issues)]
+        };
+        assert_eq!(selected(&[], &[]), [true, true]);
+        assert_eq!(selected(&["prs"], &[]), [true, false]);
+        assert_eq!(selected(&[], &["prs"]), [false, true]);
+        // Skip wins over only
+        assert_eq!(selected(&["prs", "issues"], &["issues"]), [true, false]);
+        // Names are "sql" names, not series names or descriptions
+        assert_eq!(selected(&["PRs", "issues.sql"], &[]), [false, false]);
+    }
+
+    #[test]
+    fn test_sync_metrics_unmatched() {
+        let metrics = metrics();
+        assert!(sync_metrics_unmatched(&sync_ctx(&[], &[], true), &metrics).is_empty());
+        let ctx = sync_ctx(&["prs", "pr", "zz"], &["disabled", "isues", "zz"], true);
+        // Sorted and deduplicated, disabled metrics are still known names
+        assert_eq!(sync_metrics_unmatched(&ctx, &metrics), ["isues", "pr", "zz"]);
+    }
+
+    #[test]
+    fn test_sync_metric_tasks() {
+        let metrics = metrics();
+        let mut rng = ZeroRand(0);
+        let all = ["prs d", "prs d7", "prs w", "issues m", "hist_langs d,w", "hist_anno anno"];
+        assert_eq!(task_names(&sync_ctx(&[], &[], true), &mut rng, &metrics), all);
+        assert_eq!(
+            task_names(&sync_ctx(&["prs", "hist_anno", "disabled"], &[], true), &mut rng, &metrics),
+            ["prs d", "prs d7", "prs w", "hist_anno anno"]
+        );
+        assert_eq!(
+            task_names(&sync_ctx(&[], &["prs", "hist_langs"], true), &mut rng, &metrics),
+            ["issues m", "hist_anno anno"]
+        );
+        assert!(task_names(&sync_ctx(&["other_project"], &[], true), &mut rng, &metrics).is_empty());
+        // No random numbers used with GHA2DB_SKIP_RAND
+        assert_eq!(rng.0, 0);
+
+        // Shuffled otherwise: Fisher-Yates always swapping with the first task moves it to the end
+        let tasks = task_names(&sync_ctx(&[], &[], false), &mut rng, &metrics);
+        assert_eq!(tasks, [&all[1..], &all[..1]].concat());
+        assert_eq!(rng.0, all.len() - 1);
+        // Seeded shuffles are reproducible permutations of the metrics.yaml order
+        let mut orders = HashSet::new();
+        for seed in 1..=8 {
+            let ctx = sync_ctx(&[], &[], false);
+            let tasks = task_names(&ctx, &mut TimeRand::with_seed(seed), &metrics);
+            assert_eq!(tasks, task_names(&ctx, &mut TimeRand::with_seed(seed), &metrics), "{seed}");
+            let mut expected: Vec<String> = all.iter().map(|t| t.to_string()).collect();
+            rand_shuffle(&mut TimeRand::with_seed(seed), &mut expected);
+            assert_eq!(tasks, expected, "{seed}");
+            orders.insert(tasks);
+        }
+        assert!(orders.len() > 1);
+    }
+}
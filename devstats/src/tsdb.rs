@@ -0,0 +1,335 @@
+// Time series data is stored in Postgres: each series name has its own "s<name>" table
+// with time, series (sub series of merged tables), period and one column per field, names are limited to PG_MAX_IDENT bytes
+
+// Time series point field value
+#[derive(Debug, Clone, PartialEq)]
+pub enum TsValue {
+    Float(f64),
+    Text(String),
+}
+
+impl TsValue {
+    // Numbers become floats, everything else text
+    pub fn parse(s: &str) -> Self {
+        match s.trim().parse::<f64>() {
+            Ok(v) if v.is_finite() => TsValue::Float(v),
+            _ => TsValue::Text(s.to_string()),
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self, TsValue::Text(_))
+    }
+
+    // Parameter for a text or double precision column, missing values are NULLs
+    fn param(v: Option<&TsValue>, text: bool) -> SqlParam {
+        match (v, text) {
+            (Some(TsValue::Float(v)), false) => Box::new(*v),
+            (Some(TsValue::Float(v)), true) => Box::new(v.to_string()),
+            (Some(TsValue::Text(s)), _) => Box::new(s.clone()),
+            (None, false) => Box::new(None::<f64>),
+            (None, true) => Box::new(None::<String>),
+        }
+    }
+}
+
+// Single time series point: written into "s<name>" table
+#[derive(Debug, Clone, PartialEq)]
+pub struct TsPoint {
+    pub t: DateTime<Utc>,
+    pub name: String,
+    pub series: String,
+    pub period: String,
+    pub fields: BTreeMap<String, TsValue>,
+}
+
+impl TsPoint {
+    pub fn new(t: DateTime<Utc>, name: &str, period: &str) -> Self {
+        TsPoint {
+            t,
+            name: name.to_string(),
+            series: "".to_string(),
+            period: period.to_string(),
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+// Column types of "s<name>" table: column -> is text, None if the table doesn't exist
+fn ts_table_columns(client: &mut Client, ctx: &Ctx, table: &str) -> Result<Option<BTreeMap<String, bool>>, String> {
+    let rows = query_sql_with_err(
+        client,
+        ctx,
+        "select column_name::text, data_type::text from information_schema.columns \
+        where table_schema = current_schema() and table_name = $1",
+        &[&table],
+    )
+    .map_err(|e| format!("{table}: {e}"))?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(
+        rows.iter()
+            .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1) == "text"))
+            .collect(),
+    ))
+}
+
+// Creates "s<name>" table with given field columns or adds missing columns to the existing one
+// A double precision column receiving text values is converted to text, returns final field column types
+fn ensure_ts_table(
+    client: &mut Client,
+    ctx: &Ctx,
+    table: &str,
+    fields: &BTreeMap<&str, bool>,
+) -> Result<BTreeMap<String, bool>, String> {
+    let qtable = quote_ident(table);
+    let columns = match ts_table_columns(client, ctx, table)? {
+        Some(columns) => columns,
+        None => {
+            exec_sql_with_err(
+                client,
+                ctx,
+                &format!(
+                    "create table if not exists {qtable}(time timestamp not null, series text not null default '', \
+                    period text not null default '', primary key(time, series, period))"
+                ),
+                &[],
+            )
+            .map_err(|e| format!("{table}: {e}"))?;
+            BTreeMap::new()
+        }
+    };
+    let mut types = BTreeMap::new();
+    for (f, text) in fields {
+        let sql = match columns.get(*f) {
+            None => format!(
+                "alter table {qtable} add column if not exists {} {}",
+                quote_ident(f),
+                if *text { "text" } else { "double precision" }
+            ),
+            Some(false) if *text => format!("alter table {qtable} alter column {} type text", quote_ident(f)),
+            Some(col_text) => {
+                types.insert(f.to_string(), *col_text);
+                continue;
+            }
+        };
+        exec_sql_with_err(client, ctx, &sql, &[]).map_err(|e| format!("{table}: {e}"))?;
+        types.insert(f.to_string(), *text);
+    }
+    Ok(types)
+}
+
+// Table of time series `name` (normalize_name result), None for an empty name (like "日本" normalized),
+// it would write into a shared "s" table
+fn ts_table(name: &str) -> Option<String> {
+    if name.is_empty() {
+        return None;
+    }
+    Some(pg_ident(&format!("s{name}")))
+}
+
+// Points grouped by "s<name>" table and key (time, series, period), fields of points with the same key are merged
+type TsKey<'a> = (DateTime<Utc>, &'a str, &'a str);
+type TsTables<'a> = BTreeMap<&'a str, BTreeMap<TsKey<'a>, BTreeMap<String, &'a TsValue>>>;
+
+// Later points win: their values replace values of the same fields, other fields are kept
+fn merge_ts_points(points: &[TsPoint]) -> TsTables<'_> {
+    let mut tables: TsTables = BTreeMap::new();
+    for p in points {
+        let fields = tables
+            .entry(&p.name)
+            .or_default()
+            .entry((p.t, &p.series, &p.period))
+            .or_default();
+        fields.extend(p.fields.iter().map(|(f, v)| (pg_ident(f), v)));
+    }
+    tables
+}
+
+// Time series writer, with GHA2DB_RESETTSDB it remembers tables it already truncated
+// Each table is truncated before the writer's first write into it, so all its points are regenerated
+#[derive(Debug, Default)]
+pub struct TsWriter {
+    reset: HashSet<String>,
+}
+
+impl TsWriter {
+    fn reset_table(&mut self, client: &mut Client, ctx: &Ctx, table: &str) -> Result<(), String> {
+        if !ctx.reset_tsdb || self.reset.contains(table) {
+            return Ok(());
+        }
+        exec_sql_with_err(client, ctx, &format!("truncate {}", quote_ident(table)), &[])
+            .map_err(|e| format!("{table}: {e}"))?;
+        self.reset.insert(table.to_string());
+        Ok(())
+    }
+
+    // Writes points (upsert by time, series and period), creating missing "s<name>" tables and columns
+    // Points with the same key are merged (later values win), nothing is written with GHA2DB_SKIPTSDB
+    // Returns number of written (inserted or updated) points
+    pub fn write(&mut self, client: &mut Client, ctx: &Ctx, points: &[TsPoint]) -> Result<u64, String> {
+        if ctx.skip_tsdb {
+            return Ok(0);
+        }
+        let mut written = 0;
+        for (name, points) in merge_ts_points(points) {
+            let Some(table) = ts_table(name) else {
+                printf(&format!("warning: skipped {} points with an empty time series name", points.len()));
+                continue;
+            };
+            // Field columns: text if any point has a text value, double precision otherwise
+            let mut fields: BTreeMap<&str, bool> = BTreeMap::new();
+            for values in points.values() {
+                for (f, v) in values {
+                    *fields.entry(f.as_str()).or_default() |= v.is_text();
+                }
+            }
+            let types = ensure_ts_table(client, ctx, &table, &fields)?;
+            self.reset_table(client, ctx, &table)?;
+            let mut columns = vec!["time".to_string(), "series".to_string(), "period".to_string()];
+            columns.extend(types.keys().map(|f| quote_ident(f)));
+            let columns: Vec<&str> = columns.iter().map(|c| c.as_str()).collect();
+            let mut bulk = BulkInsert::new(ctx, &quote_ident(&table), &columns);
+            if types.is_empty() {
+                bulk.set_suffix("on conflict do nothing");
+            } else {
+                let set: Vec<String> = columns[3..].iter().map(|c| format!("{c} = excluded.{c}")).collect();
+                bulk.set_suffix(&format!("on conflict(time, series, period) do update set {}", set.join(", ")));
+            }
+            for ((t, series, period), values) in points {
+                let mut row: Vec<SqlParam> = vec![
+                    Box::new(t.naive_utc()),
+                    Box::new(series.to_string()),
+                    Box::new(period.to_string()),
+                ];
+                for (f, text) in &types {
+                    row.push(TsValue::param(values.get(f.as_str()).copied(), *text));
+                }
+                written += bulk.add(client, row)?;
+            }
+            written += bulk.flush(client)?;
+        }
+        Ok(written)
+    }
+}
+
+// Deletes all points of given period from "s<name>" table (if it exists), returns number of deleted points
+pub fn delete_ts_period(client: &mut Client, ctx: &Ctx, name: &str, period: &str) -> Result<u64, String> {
+    if ctx.skip_tsdb {
+        return Ok(0);
+    }
+    let Some(table) = ts_table(name) else {
+        return Ok(0);
+    };
+    let rows = query_sql_with_err(client, ctx, "select to_regclass($1) is not null", &[&quote_ident(&table)])
+        .map_err(|e| format!("{table}: {e}"))?;
+    if !rows.first().is_some_and(|row| row.get::<_, bool>(0)) {
+        return Ok(0);
+    }
+    exec_sql_with_err(
+        client,
+        ctx,
+        &format!("delete from {} where period = $1", quote_ident(&table)),
+        &[&period],
+    )
+    .map_err(|e| format!("{table}: {e}"))
+}
+
+// Truncates all time series tables ("s<name>" tables with time, series and period columns)
+// and forgets computed metrics and past quick ranges, returns number of truncated tables
+pub fn reset_ts_tables(client: &mut Client, ctx: &Ctx) -> Result<u64, String> {
+    let rows = query_sql_with_err(
+        client,
+        ctx,
+        "select table_name::text from information_schema.columns where table_schema = current_schema() \
+        and table_name like 's%' and column_name in ('time', 'series', 'period') group by table_name having count(*) = 3",
+        &[],
+    )
+    .map_err(|e| e.to_string())?;
+    for row in &rows {
+        let table: String = row.get(0);
+        exec_sql_with_err(client, ctx, &format!("truncate {}", quote_ident(&table)), &[])
+            .map_err(|e| format!("{table}: {e}"))?;
+    }
+    exec_sql_with_err(
+        client,
+        ctx,
+        "delete from gha_computed where metric like 'ts:%' or metric like 'hist:%'",
+        &[],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(rows.len() as u64)
+}
+
+#[cfg(test)]
+mod tsdb_tests {
+    use super::*;
+
+    fn point(h: i64, name: &str, series: &str, period: &str, fields: &[(&str, TsValue)]) -> TsPoint {
+        let t = time_parse_any("2021-03-01 00:00:00").unwrap() + chrono::Duration::hours(h);
+        let mut p = TsPoint::new(t, name, period);
+        p.series = series.to_string();
+        p.fields = fields.iter().map(|(f, v)| (f.to_string(), v.clone())).collect();
+        p
+    }
+
+    #[test]
+    fn test_ts_value_parse() {
+        assert_eq!(TsValue::parse("3"), TsValue::Float(3.0));
+        assert_eq!(TsValue::parse(" -2.5 "), TsValue::Float(-2.5));
+        assert_eq!(TsValue::parse("1e3"), TsValue::Float(1000.0));
+        // Non finite numbers can't be stored in double precision columns as numbers, they are kept as text
+        for s in ["NaN", "inf", "-inf", "infinity"] {
+            assert_eq!(TsValue::parse(s), TsValue::Text(s.to_string()), "{s}");
+        }
+        // Text is kept as is (not trimmed)
+        for s in ["", "Go", " v1.2 ", "1,5", "0x10"] {
+            assert_eq!(TsValue::parse(s), TsValue::Text(s.to_string()), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn test_merge_ts_points() {
+        let f = TsValue::Float;
+        let points = vec![
+            point(0, "prs", "", "d", &[("value", f(1.0)), ("descr", TsValue::Text("PRs".to_string()))]),
+            point(1, "prs", "", "d", &[("value", f(2.0))]),
+            point(0, "prs", "", "w", &[("value", f(3.0))]),
+            point(0, "prs", "acme", "d", &[("value", f(4.0))]),
+            point(0, "issues", "", "d", &[("value", f(5.0))]),
+            // Same key as the first point: value replaced, descr kept, new field added
+            point(0, "prs", "", "d", &[("value", f(6.0)), ("other", f(7.0))]),
+        ];
+        let tables = merge_ts_points(&points);
+        assert_eq!(tables.keys().collect::<Vec<_>>(), [&"issues", &"prs"]);
+        let prs = &tables["prs"];
+        let keys: Vec<(i64, &str, &str)> = prs
+            .keys()
+            .map(|(t, series, period)| ((*t - points[0].t).num_hours(), *series, *period))
+            .collect();
+        assert_eq!(keys, [(0, "", "d"), (0, "", "w"), (0, "acme", "d"), (1, "", "d")]);
+        let merged = &prs[&(points[0].t, "", "d")];
+        assert_eq!(merged.keys().collect::<Vec<_>>(), ["descr", "other", "value"]);
+        assert_eq!(merged["value"], &f(6.0));
+        assert_eq!(merged["descr"], &TsValue::Text("PRs".to_string()));
+        assert_eq!(merged["other"], &f(7.0));
+        // Field names are truncated to PG_MAX_IDENT bytes, so points merge into the same column
+        let long = "x".repeat(70);
+        let points = vec![
+            point(0, "prs", "", "d", &[(&long, f(1.0))]),
+            point(0, "prs", "", "d", &[(&long[..65], f(2.0))]),
+        ];
+        let tables = merge_ts_points(&points);
+        let merged = &tables["prs"][&(points[0].t, "", "d")];
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[&long[..PG_MAX_IDENT]], &f(2.0));
+    }
+
+    #[test]
+    fn test_ts_table() {
+        assert_eq!(ts_table("prs_d"), Some("sprs_d".to_string()));
+        assert_eq!(ts_table(&"x".repeat(70)), Some(format!("s{}", "x".repeat(PG_MAX_IDENT - 1))));
+        assert_eq!(ts_table(&normalize_name("日本")), None);
+    }
+}